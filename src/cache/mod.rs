@@ -0,0 +1,276 @@
+//! Build state kept between runs
+//!
+//! Stored in `.hexo-cache/cache.bin` in the site directory, encoded with
+//! bincode, which is much faster to read than JSON for sites with thousands
+//! of entries. A `cache.json` left by an older version is read once and
+//! replaced. A missing, unreadable or outdated cache is treated as empty,
+//! which only costs a full rebuild.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Directory holding the cache, relative to the site directory
+pub const CACHE_DIR: &str = ".hexo-cache";
+
+/// Bumped whenever the stored format changes, discarding older caches
+const CACHE_VERSION: u32 = 3;
+
+/// Version of the JSON cache, which is still read
+const JSON_CACHE_VERSION: u32 = 1;
+
+/// Source file an asset in `public/` was copied from, as it was when copied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetRecord {
+    pub source: PathBuf,
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch
+    pub mtime: u128,
+}
+
+impl AssetRecord {
+    /// Record of `source` as it is now
+    pub fn of(source: &Path) -> std::io::Result<Self> {
+        let metadata = fs::metadata(source)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        Ok(Self {
+            source: source.to_path_buf(),
+            size: metadata.len(),
+            mtime,
+        })
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct CacheData {
+    /// First, so it can be checked before decoding the rest
+    version: u32,
+    /// Copied assets by output path relative to `public/`
+    #[serde(default)]
+    assets: BTreeMap<String, AssetRecord>,
+    /// When the last full build finished, in seconds since the Unix epoch
+    #[serde(default)]
+    last_build: Option<i64>,
+    /// Templates each template was seen rendering through `partial` or
+    /// `fragment_cache`
+    #[serde(default)]
+    templates: BTreeMap<String, BTreeSet<String>>,
+    /// Pages, feeds and other files the last full build rendered, by path
+    /// relative to `public/`
+    #[serde(default)]
+    outputs: BTreeSet<String>,
+}
+
+/// The build cache of a site
+pub struct CacheDb {
+    dir: PathBuf,
+    data: CacheData,
+}
+
+impl CacheDb {
+    /// Load the cache of the site in `base_dir`, empty if there is none
+    pub fn load<P: AsRef<Path>>(base_dir: P) -> Self {
+        let dir = base_dir.as_ref().join(CACHE_DIR);
+        let data = Self::read(&dir).unwrap_or_default();
+        Self { dir, data }
+    }
+
+    fn read(dir: &Path) -> Option<CacheData> {
+        let path = dir.join("cache.bin");
+        let (version, data) = match fs::read(&path) {
+            Ok(bytes) => {
+                let version = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?);
+                if version != CACHE_VERSION {
+                    tracing::info!("Build cache is from another version, rebuilding");
+                    return None;
+                }
+                (
+                    version,
+                    bincode::deserialize::<CacheData>(&bytes).map_err(|e| e.to_string()),
+                )
+            }
+            Err(_) => {
+                let path = dir.join("cache.json");
+                let content = fs::read_to_string(&path).ok()?;
+                (
+                    JSON_CACHE_VERSION,
+                    serde_json::from_str::<CacheData>(&content).map_err(|e| e.to_string()),
+                )
+            }
+        };
+        match data {
+            Ok(data) if data.version == version => Some(data),
+            Ok(_) => {
+                tracing::info!("Build cache is from another version, rebuilding");
+                None
+            }
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable build cache in {:?}: {}", dir, e);
+                None
+            }
+        }
+    }
+
+    /// Write the cache back to disk
+    pub fn save(&mut self) -> Result<()> {
+        self.data.version = CACHE_VERSION;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join("cache.bin"), bincode::serialize(&self.data)?)?;
+        let json = self.dir.join("cache.json");
+        if json.exists() {
+            fs::remove_file(json)?;
+        }
+        Ok(())
+    }
+
+    /// Assets copied by the last build
+    pub fn assets(&self) -> &BTreeMap<String, AssetRecord> {
+        &self.data.assets
+    }
+
+    pub fn set_assets(&mut self, assets: BTreeMap<String, AssetRecord>) {
+        self.data.assets = assets;
+    }
+
+    /// Templates each template was seen rendering, see
+    /// `TemplateRenderer::template_usage`
+    pub fn template_usage(&self) -> &BTreeMap<String, BTreeSet<String>> {
+        &self.data.templates
+    }
+
+    pub fn set_template_usage(&mut self, templates: BTreeMap<String, BTreeSet<String>>) {
+        self.data.templates = templates;
+    }
+
+    /// Files the last full build rendered (assets aren't included)
+    pub fn outputs(&self) -> &BTreeSet<String> {
+        &self.data.outputs
+    }
+
+    pub fn set_outputs(&mut self, outputs: BTreeSet<String>) {
+        self.data.outputs = outputs;
+    }
+
+    /// When the last full build finished, if there was one
+    pub fn last_build(&self) -> Option<DateTime<Utc>> {
+        self.data
+            .last_build
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+    }
+
+    pub fn set_last_build(&mut self, time: DateTime<Utc>) {
+        self.data.last_build = Some(time.timestamp());
+    }
+}
+
+/// SHA-256 of the file at `path` as lowercase hex, read a chunk at a time
+/// so large and binary files can be hashed too
+pub fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut chunk = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&chunk[..n]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Posts and pages that changed since the content was loaded, and how far
+/// the change reaches
+#[derive(Debug, Default)]
+pub struct ChangeSet {
+    /// Source paths (relative to `source/`) of changed posts
+    pub posts: BTreeSet<String>,
+    /// Source paths of changed pages
+    pub pages: BTreeSet<String>,
+    /// Something other pages can show changed (a title, date, path, tags,
+    /// ...) or a post or page was added or removed, so every page needs
+    /// rendering
+    pub all: bool,
+    /// URL paths that no longer have a post or page
+    pub removed: Vec<String>,
+}
+
+impl ChangeSet {
+    pub fn is_empty(&self) -> bool {
+        self.posts.is_empty() && self.pages.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("a.png");
+        fs::write(&source, "png").unwrap();
+
+        let mut cache = CacheDb::load(dir.path());
+        assert!(cache.assets().is_empty());
+        let record = AssetRecord::of(&source).unwrap();
+        assert_eq!(record.size, 3);
+        cache.set_assets(BTreeMap::from([("a.png".to_string(), record.clone())]));
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        cache.set_last_build(now);
+        cache.save().unwrap();
+
+        let cache = CacheDb::load(dir.path());
+        assert_eq!(cache.assets()["a.png"], record);
+        assert_eq!(cache.last_build(), Some(now));
+
+        fs::write(dir.path().join(CACHE_DIR).join("cache.bin"), "oops").unwrap();
+        assert!(CacheDb::load(dir.path()).assets().is_empty());
+    }
+
+    #[test]
+    fn test_hash_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.bin");
+        fs::write(&path, [0xff, 0x00, 0xfe]).unwrap();
+        let hash = hash_file(&path).unwrap();
+        assert_eq!(hash.len(), 64);
+        fs::write(&path, "abc").unwrap();
+        assert_eq!(
+            hash_file(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_ne!(hash, hash_file(&path).unwrap());
+    }
+
+    #[test]
+    fn test_cache_json_migration() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join(CACHE_DIR);
+        fs::create_dir_all(&cache_dir).unwrap();
+        fs::write(
+            cache_dir.join("cache.json"),
+            r#"{"version": 1, "assets": {"a.png": {"source": "a.png", "size": 3, "mtime": 7}}}"#,
+        )
+        .unwrap();
+
+        let mut cache = CacheDb::load(dir.path());
+        assert_eq!(cache.assets()["a.png"].mtime, 7);
+        cache.save().unwrap();
+        assert!(!cache_dir.join("cache.json").exists());
+        assert_eq!(CacheDb::load(dir.path()).assets()["a.png"].size, 3);
+    }
+}
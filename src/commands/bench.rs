@@ -0,0 +1,166 @@
+//! Benchmark builds of a synthesized site
+
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::commands::generate::Rebuilder;
+use crate::Hexo;
+
+const TAGS: &[&str] = &[
+    "rust",
+    "web",
+    "performance",
+    "testing",
+    "design",
+    "tooling",
+    "databases",
+    "linux",
+    "networking",
+    "security",
+    "tutorial",
+    "notes",
+];
+
+const CATEGORIES: &[&str] = &["Programming", "Operations", "Writing", "Life"];
+
+const WORDS: &[&str] = &[
+    "build", "cache", "render", "page", "theme", "server", "request", "thread", "memory", "index",
+    "feed", "template", "post", "archive", "layout", "the", "a", "with", "and", "for", "when",
+    "quickly", "every", "small", "large", "change",
+];
+
+/// Small deterministic generator, so every run builds the same site
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, n: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.0 >> 33) as usize) % n
+    }
+
+    fn words(&mut self, count: usize) -> String {
+        (0..count)
+            .map(|_| WORDS[self.next(WORDS.len())])
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Synthesize a site with `posts` posts in `dir`, then time a cold full
+/// build, a warm full build and an incremental rebuild after editing one
+/// post. The site is removed afterwards unless `keep`.
+pub fn run(dir: Option<&Path>, posts: usize, keep: bool) -> Result<()> {
+    let dir = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => std::env::temp_dir().join(format!("hexo-rs-bench-{}", uuid::Uuid::new_v4())),
+    };
+    // It is removed afterwards, so don't touch anything already there
+    if fs::read_dir(&dir).is_ok_and(|mut entries| entries.next().is_some()) {
+        anyhow::bail!("{:?} is not empty, pick an empty directory", dir);
+    }
+    let result = bench(&dir, posts);
+    if keep {
+        println!("Benchmark site kept in {:?}", dir);
+    } else {
+        fs::remove_dir_all(&dir)?;
+    }
+    let timings = result?;
+
+    println!("{:<12}  {:>10}", "build", "time");
+    for (name, time) in timings {
+        println!("{:<12}  {:>8.1}ms", name, time.as_secs_f64() * 1000.0);
+    }
+    Ok(())
+}
+
+fn bench(dir: &Path, posts: usize) -> Result<Vec<(&'static str, Duration)>> {
+    let sources = write_site(dir, posts)?;
+    tracing::info!("Synthesized {} posts in {:?}", posts, dir);
+    let hexo = Hexo::new(dir)?;
+    let mut timings = Vec::new();
+
+    let start = Instant::now();
+    crate::commands::generate::run(&hexo)?;
+    timings.push(("full (cold)", start.elapsed()));
+
+    // Loads the content for the incremental rebuild too
+    let mut rebuilder = Rebuilder::new();
+    let start = Instant::now();
+    rebuilder.rebuild(&hexo, &[])?;
+    timings.push(("full (warm)", start.elapsed()));
+
+    if let Some(source) = sources.first() {
+        let content = fs::read_to_string(source)?;
+        fs::write(source, content + "\nOne more paragraph.\n")?;
+        let start = Instant::now();
+        rebuilder.rebuild(&hexo, std::slice::from_ref(source))?;
+        timings.push(("incremental", start.elapsed()));
+    }
+    Ok(timings)
+}
+
+/// Write the config and posts, returning the paths of the posts
+fn write_site(dir: &Path, posts: usize) -> Result<Vec<PathBuf>> {
+    let posts_dir = dir.join("source/_posts");
+    fs::create_dir_all(&posts_dir)?;
+    fs::write(
+        dir.join("_config.yml"),
+        "title: Benchmark\nurl: http://example.com\ntheme: builtin:vexo\n",
+    )?;
+
+    let mut rng = Lcg(posts as u64);
+    let start = chrono::NaiveDate::from_ymd_opt(2015, 1, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .unwrap_or_default();
+    let mut paths = Vec::with_capacity(posts);
+    for i in 0..posts {
+        let title = format!("Post {} about {}", i + 1, rng.words(3));
+        let date = start + chrono::Duration::hours(6 * i as i64 + rng.next(6) as i64);
+        let mut tags: Vec<_> = (0..1 + rng.next(4))
+            .map(|_| TAGS[rng.next(TAGS.len())])
+            .collect();
+        tags.sort();
+        tags.dedup();
+        let category = CATEGORIES[rng.next(CATEGORIES.len())];
+
+        let mut body = String::new();
+        for section in 0..2 + rng.next(4) {
+            body.push_str(&format!("## Section {}\n\n", section + 1));
+            for _ in 0..1 + rng.next(3) {
+                let words = 40 + rng.next(80);
+                body.push_str(&rng.words(words));
+                body.push_str(".\n\n");
+            }
+            if rng.next(3) == 0 {
+                body.push_str("```rust\nfn main() {\n    println!(\"hello\");\n}\n```\n\n");
+            }
+            if rng.next(3) == 0 {
+                for _ in 0..3 {
+                    body.push_str(&format!("- {}\n", rng.words(6)));
+                }
+                body.push('\n');
+            }
+        }
+
+        let path = posts_dir.join(format!("post-{:05}.md", i + 1));
+        fs::write(
+            &path,
+            format!(
+                "---\ntitle: {}\ndate: {}\ntags: [{}]\ncategories: [{}]\n---\n{}\n<!-- more -->\n\n{}",
+                title,
+                date.format("%Y-%m-%d %H:%M:%S"),
+                tags.join(", "),
+                category,
+                rng.words(30),
+                body
+            ),
+        )?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
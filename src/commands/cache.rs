@@ -0,0 +1,121 @@
+//! Inspect and reset the build cache
+
+use anyhow::Result;
+use std::fs;
+use walkdir::WalkDir;
+
+use crate::cache::{AssetRecord, CacheDb, CACHE_DIR};
+use crate::generator::same_content;
+use crate::helpers::format_size;
+use crate::Hexo;
+
+/// Print where the cache is, how big it is and what it holds
+pub fn status(hexo: &Hexo) -> Result<()> {
+    let dir = hexo.base_dir.join(CACHE_DIR);
+    if !dir.exists() {
+        println!("No build cache in {:?}", dir);
+        return Ok(());
+    }
+
+    let size: u64 = WalkDir::new(&dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum();
+    let cache = CacheDb::load(&hexo.base_dir);
+
+    println!("Cache: {:?} ({})", dir, format_size(size));
+    println!("Assets: {}", cache.assets().len());
+    println!("Rendered files: {}", cache.outputs().len());
+    match cache.last_build() {
+        Some(time) => println!(
+            "Last build: {}",
+            time.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+        ),
+        None => println!("Last build: never"),
+    }
+    Ok(())
+}
+
+/// Delete the cache, so the next build starts from scratch
+pub fn clear(hexo: &Hexo) -> Result<()> {
+    let dir = hexo.base_dir.join(CACHE_DIR);
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+        tracing::info!("Deleted: {:?}", dir);
+    }
+    Ok(())
+}
+
+/// Check the cache against `public/`, returning the problems found (also
+/// printed). Entries whose source changed aren't problems: the next build
+/// copies them again.
+pub fn verify(hexo: &Hexo) -> Result<Vec<String>> {
+    let cache = CacheDb::load(&hexo.base_dir);
+    let mut problems = Vec::new();
+    let mut outdated = 0;
+
+    for (key, record) in cache.assets() {
+        if AssetRecord::of(&record.source).ok().as_ref() != Some(record) {
+            outdated += 1;
+            continue;
+        }
+        // The next build would skip this asset, so the copy has to be right
+        let dest = hexo.public_dir.join(key);
+        if !dest.exists() {
+            problems.push(format!("{}: missing from {:?}", key, hexo.public_dir));
+        } else if !same_content(&record.source, &dest) {
+            problems.push(format!("{}: differs from {:?}", key, record.source));
+        }
+    }
+
+    println!(
+        "Checked {} cached assets ({} out of date)",
+        cache.assets().len(),
+        outdated
+    );
+    for problem in &problems {
+        println!("  {}", problem);
+    }
+    if problems.is_empty() {
+        println!("Cache is consistent with {:?}", hexo.public_dir);
+    } else {
+        println!("Run `hexo-rs cache clear` to rebuild from scratch");
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::AssetCopier;
+    use std::path::Path;
+
+    #[test]
+    fn test_verify_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let hexo = Hexo::new(dir.path()).unwrap();
+        let source = dir.path().join("a.css");
+        fs::write(&source, "body{}").unwrap();
+
+        let mut assets = AssetCopier::new(&hexo.public_dir);
+        assets.copy(&source, Path::new("a.css"));
+        let mut cache = CacheDb::load(dir.path());
+        assets.finish(&mut cache).unwrap();
+        cache.save().unwrap();
+        assert!(verify(&hexo).unwrap().is_empty());
+
+        // Edited in public/ behind the cache's back
+        fs::write(hexo.public_dir.join("a.css"), "p{}{}").unwrap();
+        assert_eq!(verify(&hexo).unwrap().len(), 1);
+        fs::remove_file(hexo.public_dir.join("a.css")).unwrap();
+        assert_eq!(verify(&hexo).unwrap().len(), 1);
+
+        clear(&hexo).unwrap();
+        assert!(!dir.path().join(CACHE_DIR).exists());
+        assert!(verify(&hexo).unwrap().is_empty());
+    }
+}
@@ -1,15 +1,18 @@
-//! Clean the public directory
+//! Clean the public directory and the build cache
 
 use anyhow::Result;
 use std::fs;
 
+use crate::cache::CACHE_DIR;
 use crate::Hexo;
 
-/// Clean the public directory
+/// Clean the public directory and the build cache
 pub fn run(hexo: &Hexo) -> Result<()> {
-    if hexo.public_dir.exists() {
-        fs::remove_dir_all(&hexo.public_dir)?;
-        tracing::info!("Deleted: {:?}", hexo.public_dir);
+    for dir in [hexo.public_dir.clone(), hexo.base_dir.join(CACHE_DIR)] {
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+            tracing::info!("Deleted: {:?}", dir);
+        }
     }
 
     Ok(())
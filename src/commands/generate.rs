@@ -2,35 +2,286 @@
 
 use anyhow::Result;
 use notify::Watcher;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::channel;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
+use crate::cache::{CacheDb, CACHE_DIR};
 use crate::content::loader::ContentLoader;
+use crate::content::{Page, Post};
 use crate::generator::Generator;
+use crate::helpers::format_size;
+use crate::templates::format_report;
+use crate::theme::template_name;
 use crate::Hexo;
+use walkdir::WalkDir;
+
+/// Templates listed by `generate --profile`
+const PROFILE_LIMIT: usize = 15;
+
+/// Rough memory used per byte of post source: the markdown, the rendered
+/// HTML, excerpts and the copies made while rendering
+const MEMORY_PER_SOURCE_BYTE: u64 = 8;
 
 /// Generate the static site
 pub fn run(hexo: &Hexo) -> Result<()> {
+    run_with(hexo, false, None)
+}
+
+/// Generate the static site, printing the slowest templates if `profile`.
+/// If post content would likely take more than `max_memory` megabytes, it
+/// is kept on disk and read when needed instead.
+pub fn run_with(hexo: &Hexo, profile: bool, max_memory: Option<u64>) -> Result<()> {
     let start = std::time::Instant::now();
+    let started = chrono::Utc::now();
 
     // Load content
-    let loader = ContentLoader::new(hexo);
+    let mut loader = ContentLoader::new(hexo).persist_ids();
+    let spill_dir = hexo.base_dir.join(CACHE_DIR).join("content");
+    let estimate = estimated_memory(hexo);
+    let spill = max_memory.is_some_and(|max| estimate > max * 1024 * 1024);
+    if spill {
+        tracing::info!(
+            "Posts may need about {} of memory, keeping their content on disk",
+            format_size(estimate)
+        );
+        if spill_dir.exists() {
+            fs::remove_dir_all(&spill_dir)?;
+        }
+        fs::create_dir_all(&spill_dir)?;
+        loader = loader.spill_to(spill_dir.clone());
+    }
     let posts = loader.load_posts()?;
     let pages = loader.load_pages()?;
 
     tracing::info!("Loaded {} posts and {} pages", posts.len(), pages.len());
 
     // Generate site
-    let generator = Generator::new(hexo)?;
+    let mut generator = Generator::new(hexo)?;
+    generator.set_started(started);
+    if profile {
+        generator.enable_profiling();
+    }
     generator.generate(&posts, &pages)?;
+    if spill {
+        fs::remove_dir_all(&spill_dir)?;
+    }
 
     let duration = start.elapsed();
     tracing::info!("Generated in {:.2}s", duration.as_secs_f64());
 
+    if profile {
+        println!("Build stages:");
+        for (stage, time) in generator.stage_timings() {
+            println!(
+                "  {:<8}  {:>8.1}ms",
+                stage.name(),
+                time.as_secs_f64() * 1000.0
+            );
+        }
+        println!("Slowest templates (times include the partials they render):");
+        print!(
+            "{}",
+            format_report(&generator.template_timings(), PROFILE_LIMIT)
+        );
+    }
+
     Ok(())
 }
 
+/// Why the output of the last full build can be used as it is: nothing it
+/// was built from changed since, and everything it wrote is still in
+/// `public/`. `None` if the site needs generating.
+pub fn up_to_date(hexo: &Hexo) -> Option<String> {
+    let cache = CacheDb::load(&hexo.base_dir);
+    let last_build = cache.last_build()?;
+    let mut outputs = cache.outputs().iter().chain(cache.assets().keys());
+    if cache.outputs().is_empty() || !outputs.all(|key| hexo.public_dir.join(key).is_file()) {
+        return None;
+    }
+
+    // The cache keeps whole seconds, so anything from that second counts
+    let built = SystemTime::from(last_build);
+    let changed = |path: &Path| {
+        let changed = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .map_or(true, |modified| modified >= built);
+        if changed {
+            tracing::debug!("Changed since the last build: {:?}", path);
+        }
+        changed
+    };
+    // A new hexo-rs may render differently
+    if std::env::current_exe().is_ok_and(|exe| changed(&exe)) {
+        return None;
+    }
+    let configs = fs::read_dir(&hexo.base_dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with("_config") && name.ends_with(".yml")
+        });
+    for entry in configs {
+        if changed(&entry.path()) {
+            return None;
+        }
+    }
+    // Directories too, whose mtime changes when files are removed
+    let themes_dir = hexo.base_dir.join("themes");
+    for dir in [&hexo.source_dir, &themes_dir] {
+        if !dir.exists() {
+            continue;
+        }
+        for entry in WalkDir::new(dir).follow_links(true) {
+            let entry = entry.ok()?;
+            if changed(entry.path()) {
+                return None;
+            }
+        }
+    }
+
+    Some(format!(
+        "nothing changed since the last build at {}",
+        last_build
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M:%S")
+    ))
+}
+
+/// Rough memory needed to hold every post in memory, from the size of
+/// their sources
+fn estimated_memory(hexo: &Hexo) -> u64 {
+    let mut dirs = vec!["_posts"];
+    if hexo.config.render_drafts {
+        dirs.push("_drafts");
+    }
+    let source_bytes: u64 = dirs
+        .iter()
+        .flat_map(|dir| WalkDir::new(hexo.source_dir.join(dir)).follow_links(true))
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.path()
+                .extension()
+                .is_some_and(|ext| ext == "md" || ext == "markdown")
+        })
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum();
+    source_bytes * MEMORY_PER_SOURCE_BYTE
+}
+
+/// Regenerates the site after file changes, keeping the parsed posts and
+/// pages around so that edits to theme templates only re-render the pages
+/// that use them.
+#[derive(Default)]
+pub struct Rebuilder {
+    content: Option<(Vec<Post>, Vec<Page>)>,
+}
+
+impl Rebuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Regenerate after `changed` paths were modified
+    pub fn rebuild(&mut self, hexo: &Hexo, changed: &[PathBuf]) -> Result<()> {
+        let start = std::time::Instant::now();
+
+        let templates: Option<Vec<String>> = changed
+            .iter()
+            .map(|path| template_name(&hexo.theme_dir, path))
+            .collect();
+
+        match (&self.content, templates) {
+            (Some((posts, pages)), Some(templates)) if !templates.is_empty() => {
+                tracing::info!("Templates changed: {}", templates.join(", "));
+                let generator = Generator::new(hexo)?;
+                generator.generate_for_templates(posts, pages, &templates)?;
+            }
+            _ => {
+                if !self.reload_content(hexo, changed)? {
+                    self.generate_all(hexo)?;
+                }
+            }
+        }
+
+        let duration = start.elapsed();
+        tracing::info!("Generated in {:.2}s", duration.as_secs_f64());
+
+        Ok(())
+    }
+
+    /// Load all content without generating, for when `public/` is already
+    /// up to date, so the first rebuild only re-renders what changed
+    pub fn load(&mut self, hexo: &Hexo) -> Result<()> {
+        self.content = None;
+        let loader = ContentLoader::new(hexo).persist_ids();
+        let posts = loader.load_posts()?;
+        let pages = loader.load_pages()?;
+        tracing::info!("Loaded {} posts and {} pages", posts.len(), pages.len());
+        self.content = Some((posts, pages));
+        Ok(())
+    }
+
+    /// Load all content and generate the whole site
+    fn generate_all(&mut self, hexo: &Hexo) -> Result<()> {
+        let started = chrono::Utc::now();
+        self.load(hexo)?;
+        let Some((posts, pages)) = &self.content else {
+            return Ok(());
+        };
+        let mut generator = Generator::new(hexo)?;
+        generator.set_started(started);
+        if let Err(e) = generator.generate(posts, pages) {
+            // Don't keep content a failed build may not match
+            self.content = None;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Re-read changed posts and pages into the loaded content and re-render
+    /// what they affect. Returns false if the changes need a full rebuild.
+    fn reload_content(&mut self, hexo: &Hexo, changed: &[PathBuf]) -> Result<bool> {
+        let Some((posts, pages)) = &mut self.content else {
+            return Ok(false);
+        };
+        let changes = match ContentLoader::new(hexo)
+            .persist_ids()
+            .reload(posts, pages, changed)
+        {
+            Ok(Some(changes)) => changes,
+            Ok(None) => return Ok(false),
+            Err(e) => {
+                self.content = None;
+                return Err(e);
+            }
+        };
+        if changes.is_empty() {
+            return Ok(true);
+        }
+        tracing::info!(
+            "Content changed: {}",
+            changes
+                .posts
+                .iter()
+                .chain(&changes.pages)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let generator = Generator::new(hexo)?;
+        if let Err(e) = generator.generate_for_changes(posts, pages, &changes) {
+            self.content = None;
+            return Err(e);
+        }
+        Ok(true)
+    }
+}
+
 /// Watch for file changes and regenerate
 pub async fn watch(hexo: &Hexo) -> Result<()> {
     let (tx, rx) = channel();
@@ -59,16 +310,26 @@ pub async fn watch(hexo: &Hexo) -> Result<()> {
 
     // Debounce events
     let mut last_rebuild = std::time::Instant::now();
+    let mut rebuilder = Rebuilder::new();
+    if let Err(e) = rebuilder.load(hexo) {
+        tracing::warn!(
+            "Failed to load content, the first rebuild will be full: {}",
+            e
+        );
+    }
+    let mut changed: Vec<PathBuf> = Vec::new();
 
     loop {
         match rx.recv_timeout(Duration::from_millis(100)) {
-            Ok(_event) => {
+            Ok(event) => {
+                changed.extend(event.paths);
                 // Debounce: only rebuild if more than 500ms since last rebuild
                 if last_rebuild.elapsed() > Duration::from_millis(500) {
                     tracing::info!("File changed, regenerating...");
-                    if let Err(e) = run(hexo) {
+                    if let Err(e) = rebuilder.rebuild(hexo, &changed) {
                         tracing::error!("Generation failed: {}", e);
                     }
+                    changed.clear();
                     last_rebuild = std::time::Instant::now();
                 }
             }
@@ -83,3 +344,45 @@ pub async fn watch(hexo: &Hexo) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_up_to_date() {
+        let dir = tempfile::tempdir().unwrap();
+        let hexo = Hexo::new(dir.path()).unwrap();
+        let post = hexo.source_dir.join("_posts/a.md");
+        fs::create_dir_all(post.parent().unwrap()).unwrap();
+        fs::write(&post, "# a").unwrap();
+        fs::create_dir_all(&hexo.public_dir).unwrap();
+        fs::write(hexo.public_dir.join("index.html"), "a").unwrap();
+        assert!(up_to_date(&hexo).is_none());
+
+        let built = chrono::Utc::now() + chrono::Duration::seconds(5);
+        let mut cache = CacheDb::load(dir.path());
+        cache.set_outputs(["index.html".to_string()].into());
+        cache.set_last_build(built);
+        cache.save().unwrap();
+        assert!(up_to_date(&hexo).is_some());
+
+        let later = SystemTime::from(built) + Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&post)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(up_to_date(&hexo).is_none());
+
+        fs::File::options()
+            .write(true)
+            .open(&post)
+            .unwrap()
+            .set_modified(SystemTime::from(built) - Duration::from_secs(5))
+            .unwrap();
+        fs::remove_file(hexo.public_dir.join("index.html")).unwrap();
+        assert!(up_to_date(&hexo).is_none());
+    }
+}
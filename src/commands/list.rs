@@ -5,11 +5,23 @@ use anyhow::Result;
 use crate::content::loader::ContentLoader;
 use crate::Hexo;
 
-/// List site content by type
-pub fn run(hexo: &Hexo, content_type: &str) -> Result<()> {
+/// List site content by type (`drafts` lists unpublished posts instead)
+pub fn run(hexo: &Hexo, content_type: &str, drafts: bool) -> Result<()> {
     let loader = ContentLoader::new(hexo);
 
     match content_type {
+        "post" | "posts" if drafts => {
+            let posts = loader.load_drafts()?;
+            println!("Drafts ({}):", posts.len());
+            for post in posts {
+                println!(
+                    "  {} - {} [{}]",
+                    post.date.format("%Y-%m-%d"),
+                    post.title,
+                    post.source
+                );
+            }
+        }
         "post" | "posts" => {
             let posts = loader.load_posts()?;
             println!("Posts ({}):", posts.len());
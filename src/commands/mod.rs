@@ -1,7 +1,10 @@
 //! Command modules for hexo-rs
 
+pub mod bench;
+pub mod cache;
 pub mod clean;
 pub mod generate;
 pub mod init;
 pub mod list;
 pub mod new;
+pub mod theme;
@@ -0,0 +1,665 @@
+//! Theme management commands
+
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Read;
+use std::ops::Range;
+use std::path::Path;
+use std::process::Command;
+
+use crate::templates::{builtin_languages, builtin_templates};
+use crate::Hexo;
+
+/// Directory (inside the theme's `source/`) that vendored files are stored in
+const VENDOR_DIR: &str = "vendor";
+
+/// File extensions that are worth vendoring
+const VENDOR_EXTENSIONS: &[&str] = &[
+    "js", "mjs", "css", "woff", "woff2", "ttf", "otf", "eot", "svg", "png", "jpg", "gif",
+];
+
+/// Download CDN resources referenced by the theme config into the theme's
+/// source directory and rewrite the references to the local copies
+pub fn vendor(hexo: &Hexo) -> Result<()> {
+    let config_path = hexo.theme_dir.join("_config.yml");
+    if !config_path.exists() {
+        return Err(anyhow!("Theme config not found: {:?}", config_path));
+    }
+
+    let config = fs::read_to_string(&config_path)?;
+    let urls = collect_urls(&config);
+    if urls.is_empty() {
+        println!("No CDN resources found in {:?}", config_path);
+        return Ok(());
+    }
+
+    let vendor_dir = hexo.theme_dir.join("source").join(VENDOR_DIR);
+    fs::create_dir_all(&vendor_dir)?;
+
+    let mut rewrites: BTreeMap<String, String> = BTreeMap::new();
+    let mut vendored = HashMap::new();
+    for url in urls {
+        match vendor_url(&url, &vendor_dir, &mut vendored) {
+            Ok(local) => {
+                println!("Vendored: {} -> {}", url, local);
+                rewrites.insert(url, local);
+            }
+            Err(e) => tracing::warn!("Failed to vendor {}: {}", url, e),
+        }
+    }
+
+    let mut rewritten = config;
+    for (url, local) in &rewrites {
+        rewritten = rewritten.replace(url.as_str(), local);
+    }
+    fs::write(&config_path, rewritten)?;
+
+    println!(
+        "Vendored {} resources into {:?}",
+        rewrites.len(),
+        vendor_dir
+    );
+    Ok(())
+}
+
+/// Clone a theme repository into `themes/<name>` (or update an existing
+/// checkout), optionally pinned to a branch or tag, and make it the site theme
+pub fn install(hexo: &Hexo, url: &str, name: Option<&str>, branch: Option<&str>) -> Result<()> {
+    let name = match name {
+        Some(name) if is_theme_name(name) => name.to_string(),
+        Some(name) => return Err(anyhow!("Invalid theme name {:?}", name)),
+        None => theme_name_from_url(url)
+            .ok_or_else(|| anyhow!("Cannot derive a theme name from {}, use --name", url))?,
+    };
+    let theme_dir = hexo.base_dir.join("themes").join(&name);
+
+    if theme_dir.join(".git").exists() {
+        println!("Updating theme {} in {:?}", name, theme_dir);
+        match branch {
+            // The clone is shallow and has only the branch it was made
+            // from, so fetch the requested branch or tag by name
+            Some(branch) => {
+                let remote_branch = format!("+refs/heads/{0}:refs/remotes/origin/{0}", branch);
+                let tag = format!("+refs/tags/{0}:refs/tags/{0}", branch);
+                if git(
+                    &theme_dir,
+                    &["fetch", "--depth", "1", "origin", &remote_branch],
+                )
+                .is_ok()
+                {
+                    let upstream = format!("origin/{}", branch);
+                    git(&theme_dir, &["checkout", "-B", branch, &upstream])?;
+                    // Later updates pull it, though the clone's refspec
+                    // doesn't cover it
+                    let section = format!("branch.{}", branch);
+                    git(
+                        &theme_dir,
+                        &["config", &format!("{}.remote", section), "origin"],
+                    )?;
+                    let merge = format!("refs/heads/{}", branch);
+                    git(
+                        &theme_dir,
+                        &["config", &format!("{}.merge", section), &merge],
+                    )?;
+                } else {
+                    git(&theme_dir, &["fetch", "--depth", "1", "origin", &tag])?;
+                    git(&theme_dir, &["checkout", "--detach", branch])?;
+                }
+            }
+            None => {
+                git(&theme_dir, &["fetch", "--tags", "origin"])?;
+                // Tags are detached checkouts and have nothing to pull
+                if git(&theme_dir, &["symbolic-ref", "-q", "HEAD"]).is_ok() {
+                    git(&theme_dir, &["pull", "--ff-only"])?;
+                }
+            }
+        }
+    } else if theme_dir.exists() {
+        return Err(anyhow!(
+            "{:?} already exists and is not a git checkout",
+            theme_dir
+        ));
+    } else {
+        println!("Cloning {} into {:?}", url, theme_dir);
+        fs::create_dir_all(hexo.base_dir.join("themes"))?;
+        let mut args = vec!["clone", "--depth", "1"];
+        if let Some(branch) = branch {
+            args.extend(["--branch", branch]);
+        }
+        let target = theme_dir.to_string_lossy().to_string();
+        // `--` keeps a URL starting with `-` from being read as an option
+        args.extend(["--", url, target.as_str()]);
+        git(&hexo.base_dir, &args)?;
+    }
+
+    let config_path = hexo.base_dir.join("_config.yml");
+    let config = fs::read_to_string(&config_path)?;
+    fs::write(&config_path, set_theme(&config, &name))?;
+
+    println!("Installed theme {} (theme: {} in _config.yml)", name, name);
+    Ok(())
+}
+
+/// `_config.yml` of a scaffolded theme
+const SCAFFOLD_CONFIG: &str = r#"# Theme configuration, available in templates as `theme.*`.
+# Site-specific values can be overridden from the site's `theme_config:`.
+
+menu:
+  Home: /
+  Archives: /archives/
+  Tags: /tags/
+
+description: ""
+keyword: ""
+
+# Custom front matter `theme:` keys are merged over these per page
+catalog: true
+comment: {}
+"#;
+
+/// `source/css/style.css` of a scaffolded theme
+const SCAFFOLD_STYLE: &str = r#"/* Copied to public/css/style.css */
+body {
+  max-width: 48rem;
+  margin: 0 auto;
+  padding: 0 1rem;
+  font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", sans-serif;
+  line-height: 1.6;
+}
+
+pre {
+  overflow-x: auto;
+}
+"#;
+
+/// Scaffold a new Tera theme in `themes/<name>`, starting from a copy of the
+/// built-in templates so it renders existing content right away
+pub fn scaffold(hexo: &Hexo, name: &str) -> Result<()> {
+    let theme_dir = hexo.base_dir.join("themes").join(name);
+    if theme_dir.exists() {
+        return Err(anyhow!("{:?} already exists", theme_dir));
+    }
+
+    let layout_dir = theme_dir.join("layout");
+    for (template, source) in builtin_templates() {
+        let path = layout_dir.join(template);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, source)?;
+    }
+
+    let languages_dir = theme_dir.join("languages");
+    fs::create_dir_all(&languages_dir)?;
+    for (lang, source) in builtin_languages() {
+        fs::write(languages_dir.join(format!("{}.yml", lang)), source)?;
+    }
+
+    let css_dir = theme_dir.join("source").join("css");
+    fs::create_dir_all(&css_dir)?;
+    fs::write(css_dir.join("style.css"), SCAFFOLD_STYLE)?;
+    fs::write(theme_dir.join("_config.yml"), SCAFFOLD_CONFIG)?;
+
+    println!("Created theme {} in {:?}", name, theme_dir);
+    println!("Set `theme: {}` in _config.yml to use it", name);
+    Ok(())
+}
+
+/// Sample content `theme check` renders the theme with, as (path under
+/// `source/`, file content)
+const CHECK_CONTENT: &[(&str, &str)] = &[
+    (
+        "_posts/hello-world.md",
+        "---\ntitle: Hello World\ndate: 2024-01-15 10:00:00\ntags: [rust, hexo]\ncategories: [Notes]\ncover: /images/cover.png\n---\nThe first post.\n\n<!-- more -->\n\n## Code\n\n```rust\nfn main() {}\n```\n\n### Table\n\n| a | b |\n|---|---|\n| 1 | 2 |\n",
+    ),
+    (
+        "_posts/second-post.md",
+        "---\ntitle: Second Post\ndate: 2023-06-01 08:30:00\nupdated: 2023-06-02 09:00:00\ntags: [hexo]\nseries: Getting Started\n---\nA post with an ![image](/images/photo.jpg).\n",
+    ),
+    (
+        "about/index.md",
+        "---\ntitle: About\ndate: 2024-01-01 00:00:00\n---\nAbout this site.\n",
+    ),
+];
+
+/// Check that a theme works with hexo-rs: report layout files that can't be
+/// used (such as EJS), templates that fail to parse, references to missing
+/// templates, unknown filters/functions/tests, and errors rendering sample
+/// posts and pages. `name` defaults to the configured theme. Returns the
+/// problems found.
+pub fn check(hexo: &Hexo, name: Option<&str>) -> Result<Vec<String>> {
+    let mut hexo = hexo.clone();
+    if let Some(name) = name {
+        hexo.config.theme = name.to_string();
+        hexo.theme_dir = hexo.base_dir.join("themes").join(name);
+    }
+    println!("Checking theme {}", hexo.config.theme);
+
+    let mut problems = Vec::new();
+    if hexo.config.builtin_theme().is_none() {
+        if !hexo.theme_dir.is_dir() {
+            return Err(anyhow!("Theme directory {:?} not found", hexo.theme_dir));
+        }
+        let layout_dir = hexo.theme_dir.join("layout");
+        for entry in walkdir::WalkDir::new(&layout_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+        {
+            let path = entry.path();
+            if crate::theme::template_name(&hexo.theme_dir, path).is_some() {
+                continue;
+            }
+            let relative = path.strip_prefix(&hexo.theme_dir).unwrap_or(path);
+            let reason = match path.extension().and_then(|e| e.to_str()) {
+                Some("ejs") => "EJS templates are not supported",
+                Some("jsx" | "js") => "JavaScript layouts are not supported",
+                Some("hbs" | "handlebars") => "Handlebars templates are not supported",
+                _ => "not a template hexo-rs can render",
+            };
+            problems.push(format!("{}: {}", relative.display(), reason));
+        }
+    }
+
+    // Render sample content into a scratch site, so the build's cache, post
+    // IDs and manifest don't touch the real site
+    let scratch = tempfile::tempdir()?;
+    check_render(&mut hexo, scratch.path(), &mut problems)?;
+
+    if problems.is_empty() {
+        println!("No problems found");
+    } else {
+        for problem in &problems {
+            println!("- {}", problem);
+        }
+    }
+    Ok(problems)
+}
+
+/// Load the theme's templates and render the sample content with them
+fn check_render(hexo: &mut Hexo, scratch: &Path, problems: &mut Vec<String>) -> Result<()> {
+    // Still relative to the real site
+    let base_dir = std::mem::replace(&mut hexo.base_dir, scratch.to_path_buf());
+    for path in &mut hexo.config.sass.load_paths {
+        *path = base_dir.join(&*path).to_string_lossy().into_owned();
+    }
+    hexo.source_dir = scratch.join("source");
+    hexo.public_dir = scratch.join("public");
+    for (path, content) in CHECK_CONTENT {
+        let path = hexo.source_dir.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+    }
+
+    let mut generator = match crate::generator::Generator::new(hexo) {
+        Ok(generator) => generator,
+        Err(e) => {
+            problems.push(e.to_string());
+            return Ok(());
+        }
+    };
+
+    let renderer = generator.renderer();
+    for (template, reference) in renderer.missing_templates() {
+        problems.push(format!(
+            "{}: references missing template {:?}",
+            template, reference
+        ));
+    }
+    for (template, kind, helper) in renderer.unknown_helpers() {
+        problems.push(format!("{}: unknown {} `{}`", template, kind, helper));
+    }
+
+    generator.collect_errors();
+    let loader = crate::content::loader::ContentLoader::new(hexo);
+    generator.generate(&loader.load_posts()?, &loader.load_pages()?)?;
+    let mut seen = std::collections::HashSet::new();
+    for error in generator.take_errors() {
+        let error = error.to_string();
+        if seen.insert(error.clone()) {
+            problems.push(error);
+        }
+    }
+    Ok(())
+}
+
+/// Run a git command in `dir`, failing with git's stderr
+fn git(dir: &Path, args: &[&str]) -> Result<()> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| anyhow!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// `https://github.com/user/hexo-theme-next.git` -> `next`
+fn theme_name_from_url(url: &str) -> Option<String> {
+    let repo = url
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()?
+        .trim_end_matches(".git");
+    let name = repo.strip_prefix("hexo-theme-").unwrap_or(repo);
+    is_theme_name(name).then(|| name.to_string())
+}
+
+/// Whether `name` names a directory directly inside `themes/`
+fn is_theme_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+/// Point the top-level `theme:` key at `name`, keeping the rest of the file
+fn set_theme(config: &str, name: &str) -> String {
+    let mut found = false;
+    let mut lines: Vec<String> = config
+        .lines()
+        .map(|line| {
+            if !found && line.starts_with("theme:") {
+                found = true;
+                format!("theme: {}", name)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    if !found {
+        lines.push(format!("theme: {}", name));
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Download one resource (and, for stylesheets, the fonts, images and
+/// stylesheets it references) and return its site-relative path. `vendored`
+/// maps URLs already handled to their local path, or to `None` while a
+/// stylesheet's own references are still being downloaded.
+fn vendor_url(
+    url: &str,
+    vendor_dir: &Path,
+    vendored: &mut HashMap<String, Option<String>>,
+) -> Result<String> {
+    if let Some(local) = vendored.get(url) {
+        return local
+            .clone()
+            .ok_or_else(|| anyhow!("{} is imported by a stylesheet it imports", url));
+    }
+    vendored.insert(url.to_string(), None);
+
+    let mut bytes = download(url)?;
+
+    if file_extension(url) == Some("css") {
+        let css = String::from_utf8_lossy(&bytes).to_string();
+        let mut rewritten = css.clone();
+        // Back to front, so the earlier ranges stay valid
+        for (range, asset) in css_references(&css).into_iter().rev() {
+            let absolute = resolve_url(url, &asset);
+            let replacement = match vendor_url(&absolute, vendor_dir, vendored) {
+                Ok(local) => local,
+                Err(e) => {
+                    tracing::warn!("Failed to vendor {}: {}", absolute, e);
+                    absolute
+                }
+            };
+            rewritten.replace_range(range, &format!("\"{}\"", replacement));
+        }
+        bytes = rewritten.into_bytes();
+    }
+
+    let name = hashed_name(url, &bytes);
+    fs::write(vendor_dir.join(&name), &bytes)?;
+    let local = format!("/{}/{}", VENDOR_DIR, name);
+    vendored.insert(url.to_string(), Some(local.clone()));
+    Ok(local)
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    let response = ureq::get(url).call()?;
+    let mut bytes = Vec::new();
+    response.into_reader().read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Find vendorable http(s) URLs in a YAML document
+fn collect_urls(text: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    for token in
+        text.split(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | ',' | '[' | ']'))
+    {
+        let token = token.trim();
+        let is_remote = token.starts_with("https://") || token.starts_with("http://");
+        let vendorable = file_extension(token).is_some_and(|ext| VENDOR_EXTENSIONS.contains(&ext));
+        if is_remote && vendorable && !urls.iter().any(|u| u == token) {
+            urls.push(token.to_string());
+        }
+    }
+    urls
+}
+
+/// References in a stylesheet worth downloading: the byte range of each
+/// `url(...)` argument or `@import` string, quotes included, and the URL in it
+fn css_references(css: &str) -> Vec<(Range<usize>, String)> {
+    let mut references = Vec::new();
+    let mut pos = 0;
+    loop {
+        let rest = &css[pos..];
+        let Some(found) = [rest.find("url("), rest.find("@import")]
+            .into_iter()
+            .flatten()
+            .min()
+        else {
+            break;
+        };
+        let start = pos + found;
+        let (range, url) = if css[start..].starts_with("url(") {
+            let inner = start + 4;
+            let Some(end) = css[inner..].find(')') else {
+                break;
+            };
+            (inner..inner + end, &css[inner..inner + end])
+        } else {
+            let after = start + "@import".len();
+            let rest = &css[after..];
+            let offset = rest.len() - rest.trim_start().len();
+            let quote = rest[offset..].chars().next();
+            match quote {
+                Some(q @ ('"' | '\'')) => {
+                    let open = after + offset;
+                    let Some(end) = css[open + 1..].find(q) else {
+                        break;
+                    };
+                    (open..open + end + 2, &css[open..open + end + 2])
+                }
+                // `@import url(...)` is found as a `url(` next time round
+                _ => {
+                    pos = after;
+                    continue;
+                }
+            }
+        };
+        pos = range.end;
+        let url = url.trim().trim_matches(['"', '\'']);
+        if !url.is_empty() && !url.starts_with("data:") && !url.starts_with('#') {
+            references.push((range, url.to_string()));
+        }
+    }
+    references
+}
+
+/// Resolve a (possibly relative) URL against the URL of the file referencing it
+fn resolve_url(base: &str, url: &str) -> String {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        return url.to_string();
+    }
+    if let Some(rest) = url.strip_prefix("//") {
+        return format!("https://{}", rest);
+    }
+    let scheme_end = base.find("://").map(|i| i + 3).unwrap_or(0);
+    if url.starts_with('/') {
+        let host_end = base[scheme_end..]
+            .find('/')
+            .map(|i| scheme_end + i)
+            .unwrap_or(base.len());
+        return format!("{}{}", &base[..host_end], url);
+    }
+    let dir_end = base.rfind('/').unwrap_or(base.len());
+    format!("{}/{}", &base[..dir_end], url)
+}
+
+/// Extension of the last path segment of a URL, ignoring query and fragment
+fn file_extension(url: &str) -> Option<&str> {
+    let path = url.split(['?', '#']).next()?;
+    let file = path.rsplit('/').next()?;
+    let (_, ext) = file.rsplit_once('.')?;
+    Some(ext)
+}
+
+/// `<hash>-<file name>` so different versions of a library never collide
+fn hashed_name(url: &str, bytes: &[u8]) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let file = path.rsplit('/').next().unwrap_or("resource");
+    format!("{:016x}-{}", fnv1a(bytes), file)
+}
+
+/// FNV-1a 64-bit hash
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_urls() {
+        let yaml = r#"
+mathjax_cdn: https://cdn.jsdelivr.net/npm/mathjax@3/es5/tex-mml-chtml.js
+font: "https://fonts.example.com/inter.css"
+homepage: https://example.com/
+"#;
+        assert_eq!(
+            collect_urls(yaml),
+            vec![
+                "https://cdn.jsdelivr.net/npm/mathjax@3/es5/tex-mml-chtml.js",
+                "https://fonts.example.com/inter.css"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_theme_name_and_config() {
+        assert_eq!(
+            theme_name_from_url("https://github.com/next-theme/hexo-theme-next.git").as_deref(),
+            Some("next")
+        );
+        assert_eq!(
+            theme_name_from_url("git@github.com:user/vexo").as_deref(),
+            Some("vexo")
+        );
+        assert_eq!(theme_name_from_url("https://host/.."), None);
+        assert_eq!(theme_name_from_url("https://host/hexo-theme-."), None);
+        assert!(!is_theme_name("a\\b"));
+        assert!(!is_theme_name(""));
+        assert_eq!(
+            set_theme(
+                "title: Blog\ntheme: landscape # current\ntheme_config: {}\n",
+                "next"
+            ),
+            "title: Blog\ntheme: next\ntheme_config: {}\n"
+        );
+        assert_eq!(
+            set_theme("title: Blog\n", "next"),
+            "title: Blog\ntheme: next\n"
+        );
+    }
+
+    #[test]
+    fn test_css_urls_and_resolution() {
+        let css = "@import \"base.css\";\n\
+                   @font-face { src: url('../fonts/a.woff2') format('woff2'), url(data:abc); }\n\
+                   .a { background: url( a.png ) } .b { background: url(a.png.bak) }";
+        let references = css_references(css);
+        let urls: Vec<&str> = references.iter().map(|(_, url)| url.as_str()).collect();
+        assert_eq!(urls, ["base.css", "../fonts/a.woff2", "a.png", "a.png.bak"]);
+        assert_eq!(&css[references[0].0.clone()], "\"base.css\"");
+        assert_eq!(&css[references[2].0.clone()], " a.png ");
+        assert_eq!(
+            resolve_url("https://cdn.example.com/lib/css/a.css", "../fonts/a.woff2"),
+            "https://cdn.example.com/lib/css/../fonts/a.woff2"
+        );
+        assert_eq!(
+            resolve_url("https://cdn.example.com/lib/a.css", "/x.woff"),
+            "https://cdn.example.com/x.woff"
+        );
+    }
+
+    #[test]
+    fn test_vendor_url_visited() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut vendored = HashMap::from([
+            ("https://cdn.example.com/a.css".to_string(), None),
+            (
+                "https://cdn.example.com/b.woff2".to_string(),
+                Some("/vendor/0-b.woff2".to_string()),
+            ),
+        ]);
+        // A stylesheet still being vendored isn't downloaded again
+        assert!(vendor_url("https://cdn.example.com/a.css", dir.path(), &mut vendored).is_err());
+        assert_eq!(
+            vendor_url("https://cdn.example.com/b.woff2", dir.path(), &mut vendored).unwrap(),
+            "/vendor/0-b.woff2"
+        );
+    }
+
+    #[test]
+    fn test_scaffold_theme() {
+        let dir = tempfile::tempdir().unwrap();
+        let hexo = Hexo::new(dir.path()).unwrap();
+        scaffold(&hexo, "mine").unwrap();
+
+        let theme = crate::theme::ThemeLoader::load(dir.path().join("themes/mine")).unwrap();
+        let templates = theme.templates().unwrap();
+        assert!(templates
+            .iter()
+            .any(|(name, _)| name == "partials/head.html"));
+        assert!(theme.config().contains_key("menu"));
+        assert!(scaffold(&hexo, "mine").is_err());
+    }
+
+    #[test]
+    fn test_check_theme() {
+        let dir = tempfile::tempdir().unwrap();
+        let hexo = Hexo::new(dir.path()).unwrap();
+        scaffold(&hexo, "mine").unwrap();
+        assert_eq!(check(&hexo, Some("mine")).unwrap(), Vec::<String>::new());
+        // The sample build keeps its cache and post IDs to itself
+        assert!(!dir.path().join(crate::cache::CACHE_DIR).exists());
+        assert!(!dir.path().join("post_ids.yml").exists());
+
+        let layout = dir.path().join("themes/mine/layout");
+        fs::write(layout.join("tag.ejs"), "<%- body %>").unwrap();
+        fs::write(
+            layout.join("partials/footer.html"),
+            "{{ config.title | shout }}{% include \"partials/gone.html\" %}",
+        )
+        .unwrap();
+        let problems = check(&hexo, Some("mine")).unwrap().join("\n");
+        assert!(problems.contains("tag.ejs"), "{}", problems);
+        assert!(problems.contains("shout"), "{}", problems);
+        assert!(problems.contains("partials/gone.html"), "{}", problems);
+    }
+}
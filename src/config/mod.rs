@@ -2,9 +2,17 @@
 
 mod site;
 
+pub use site::merge_yaml;
+pub use site::BundleConfig;
+pub use site::ConcurrencyConfig;
 pub use site::ExternalLinkConfig;
+pub use site::FeedConfig;
 pub use site::HighlightConfig;
 pub use site::IndexGeneratorConfig;
+pub use site::ManifestConfig;
+pub use site::MarkdownConfig;
 pub use site::PrettyUrlsConfig;
 pub use site::PrismjsConfig;
+pub use site::SassConfig;
+pub use site::ServerConfig;
 pub use site::SiteConfig;
@@ -1,8 +1,8 @@
 //! Site configuration (_config.yml)
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::Path;
 
@@ -17,6 +17,10 @@ pub struct SiteConfig {
     pub keywords: Option<Vec<String>>,
     pub author: String,
     pub language: String,
+    /// Languages of a multilingual site. The first one is the default and is
+    /// generated at the site root; the others get their own `/<lang>/` tree.
+    #[serde(default)]
+    pub languages: Vec<String>,
     pub timezone: String,
 
     // URL
@@ -34,6 +38,7 @@ pub struct SiteConfig {
     pub tag_dir: String,
     pub archive_dir: String,
     pub category_dir: String,
+    pub series_dir: String,
     pub code_dir: String,
     pub i18n_dir: String,
     #[serde(default)]
@@ -50,11 +55,27 @@ pub struct SiteConfig {
     pub post_asset_folder: bool,
     pub relative_link: bool,
     pub future: bool,
+    /// Use a fixed build time (`SOURCE_DATE_EPOCH`, else the newest post's
+    /// date) instead of the clock, so rebuilding unchanged sources gives
+    /// identical output
+    pub deterministic: bool,
     pub syntax_highlighter: String,
     #[serde(default)]
     pub highlight: HighlightConfig,
     #[serde(default)]
     pub prismjs: PrismjsConfig,
+    #[serde(default)]
+    pub markdown: MarkdownConfig,
+    #[serde(default)]
+    pub sass: SassConfig,
+    #[serde(default)]
+    pub bundle: BundleConfig,
+    #[serde(default)]
+    pub feed: FeedConfig,
+    #[serde(default)]
+    pub concurrency: ConcurrencyConfig,
+    #[serde(default)]
+    pub manifest: ManifestConfig,
 
     // Home page
     #[serde(default)]
@@ -66,6 +87,10 @@ pub struct SiteConfig {
     pub category_map: HashMap<String, String>,
     #[serde(default)]
     pub tag_map: HashMap<String, String>,
+    /// Extra taxonomies collected from front matter: name -> output directory
+    /// (e.g. `series: series`, `authors: authors`)
+    #[serde(default)]
+    pub taxonomies: BTreeMap<String, String>,
 
     // Meta
     pub meta_generator: bool,
@@ -79,6 +104,10 @@ pub struct SiteConfig {
     pub per_page: usize,
     pub pagination_dir: String,
 
+    // Server
+    #[serde(default)]
+    pub server: ServerConfig,
+
     // Extensions
     pub theme: String,
     #[serde(default)]
@@ -98,6 +127,7 @@ impl Default for SiteConfig {
             keywords: None,
             author: "John Doe".to_string(),
             language: "en".to_string(),
+            languages: Vec::new(),
             timezone: String::new(),
 
             url: "http://example.com".to_string(),
@@ -111,6 +141,7 @@ impl Default for SiteConfig {
             tag_dir: "tags".to_string(),
             archive_dir: "archives".to_string(),
             category_dir: "categories".to_string(),
+            series_dir: "series".to_string(),
             code_dir: "downloads/code".to_string(),
             i18n_dir: ":lang".to_string(),
             skip_render: Vec::new(),
@@ -124,15 +155,23 @@ impl Default for SiteConfig {
             post_asset_folder: false,
             relative_link: false,
             future: true,
+            deterministic: false,
             syntax_highlighter: "highlight.js".to_string(),
             highlight: HighlightConfig::default(),
             prismjs: PrismjsConfig::default(),
+            markdown: MarkdownConfig::default(),
+            sass: SassConfig::default(),
+            bundle: BundleConfig::default(),
+            feed: FeedConfig::default(),
+            concurrency: ConcurrencyConfig::default(),
+            manifest: ManifestConfig::default(),
 
             index_generator: IndexGeneratorConfig::default(),
 
             default_category: "uncategorized".to_string(),
             category_map: HashMap::new(),
             tag_map: HashMap::new(),
+            taxonomies: BTreeMap::new(),
 
             meta_generator: true,
 
@@ -143,6 +182,8 @@ impl Default for SiteConfig {
             per_page: 10,
             pagination_dir: "page".to_string(),
 
+            server: ServerConfig::default(),
+
             theme: "landscape".to_string(),
             theme_config: HashMap::new(),
             extra: HashMap::new(),
@@ -151,30 +192,116 @@ impl Default for SiteConfig {
 }
 
 impl SiteConfig {
+    /// Language of content without a `lang` (the first of `languages`)
+    pub fn default_language(&self) -> &str {
+        self.languages.first().unwrap_or(&self.language)
+    }
+
+    /// URL prefix of a language's output tree: empty for the default
+    /// language (or a single-language site), `"<lang>/"` otherwise
+    pub fn language_prefix(&self, lang: &str) -> String {
+        if self.languages.is_empty() || lang == self.default_language() {
+            String::new()
+        } else {
+            format!("{}/", lang)
+        }
+    }
+
     /// Load configuration from a file
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         let content = fs::read_to_string(path.as_ref())?;
         let config: SiteConfig = serde_yaml::from_str(&content)?;
+        config.validate()?;
         Ok(config)
     }
 
+    /// Reject settings that would write outside their own directory or
+    /// that have no effect
+    fn validate(&self) -> Result<()> {
+        if !HIGHLIGHT_OUTPUTS.contains(&self.highlight.output.as_str()) {
+            return Err(anyhow!(
+                "Unknown highlight.output {:?}, expected one of: {}",
+                self.highlight.output,
+                HIGHLIGHT_OUTPUTS.join(", ")
+            ));
+        }
+
+        let builtin = [
+            ("archive_dir", &self.archive_dir),
+            ("tag_dir", &self.tag_dir),
+            ("category_dir", &self.category_dir),
+            ("series_dir", &self.series_dir),
+        ];
+        let mut seen: HashMap<&str, &str> = HashMap::new();
+        for (name, dir) in &self.taxonomies {
+            let trimmed = dir.trim_matches('/');
+            // An empty dir would put the term index at the site root
+            if trimmed.is_empty()
+                || trimmed
+                    .split(['/', '\\'])
+                    .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+            {
+                return Err(anyhow!(
+                    "Invalid directory {:?} for taxonomy {:?}: expected a relative path like \"{}\"",
+                    dir,
+                    name,
+                    slug::slugify(name)
+                ));
+            }
+            if let Some((key, _)) = builtin
+                .iter()
+                .find(|(_, other)| other.trim_matches('/') == trimmed)
+            {
+                return Err(anyhow!(
+                    "Directory {:?} of taxonomy {:?} is already used by {}",
+                    dir,
+                    name,
+                    key
+                ));
+            }
+            if let Some(other) = seen.insert(trimmed, name) {
+                return Err(anyhow!(
+                    "Taxonomies {:?} and {:?} both use directory {:?}",
+                    other,
+                    name,
+                    dir
+                ));
+            }
+        }
+        Ok(())
+    }
+
     /// Merge with theme configuration
     pub fn merge_theme_config(&mut self, theme_config: HashMap<String, serde_yaml::Value>) {
         for (key, value) in theme_config {
-            self.theme_config.insert(key, value);
+            match self.theme_config.get_mut(&key) {
+                Some(existing) => merge_yaml(existing, value),
+                None => {
+                    self.theme_config.insert(key, value);
+                }
+            }
         }
     }
 
-    /// Load theme-specific config from _config.[theme].yml
+    /// Name of the embedded theme selected with `theme: builtin:<name>`
+    pub fn builtin_theme(&self) -> Option<&str> {
+        self.theme.strip_prefix("builtin:")
+    }
+
+    /// Load theme-specific config from _config.[theme].yml.
+    /// The `theme_config` section of the site config takes precedence over it.
     pub fn load_theme_override<P: AsRef<Path>>(&mut self, base_dir: P) -> Result<()> {
+        let theme_name = self.builtin_theme().unwrap_or(&self.theme);
         let theme_config_path = base_dir
             .as_ref()
-            .join(format!("_config.{}.yml", self.theme));
+            .join(format!("_config.{}.yml", theme_name));
 
         if theme_config_path.exists() {
             let content = fs::read_to_string(&theme_config_path)?;
-            let theme_config: HashMap<String, serde_yaml::Value> = serde_yaml::from_str(&content)?;
-            self.merge_theme_config(theme_config);
+            let theme_config: HashMap<String, serde_yaml::Value> =
+                serde_yaml::from_str::<Option<_>>(&content)?.unwrap_or_default();
+            let site_overrides = std::mem::replace(&mut self.theme_config, theme_config);
+            self.merge_theme_config(site_overrides);
             tracing::debug!("Loaded theme override from {:?}", theme_config_path);
         }
 
@@ -182,6 +309,24 @@ impl SiteConfig {
     }
 }
 
+/// Recursively merge `overlay` into `base`: mappings are merged key by key,
+/// any other value replaces what was there
+pub fn merge_yaml(base: &mut serde_yaml::Value, overlay: serde_yaml::Value) {
+    match (base, overlay) {
+        (serde_yaml::Value::Mapping(base), serde_yaml::Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_yaml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
 /// Pretty URL configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -219,6 +364,9 @@ impl Default for ExternalLinkConfig {
     }
 }
 
+/// Values of `highlight.output`
+const HIGHLIGHT_OUTPUTS: &[&str] = &["prism", "classed", "inline"];
+
 /// Highlight.js configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -231,6 +379,11 @@ pub struct HighlightConfig {
     pub exclude_languages: Vec<String>,
     pub wrap: bool,
     pub hljs: bool,
+    /// Syntect color theme used for build-time highlighting
+    pub theme: String,
+    /// Highlight output: "prism" (Prism.js token classes), "classed"
+    /// (classes plus a generated css/highlight.css) or "inline" (style attributes)
+    pub output: String,
 }
 
 impl Default for HighlightConfig {
@@ -243,6 +396,8 @@ impl Default for HighlightConfig {
             exclude_languages: Vec::new(),
             wrap: true,
             hljs: false,
+            theme: "base16-ocean.dark".to_string(),
+            output: "prism".to_string(),
         }
     }
 }
@@ -268,6 +423,154 @@ impl Default for PrismjsConfig {
     }
 }
 
+/// Markdown renderer configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MarkdownConfig {
+    /// Heading shown above the footnotes section (empty for none)
+    pub footnote_heading: String,
+    /// Symbol used for footnote back-references
+    pub footnote_backref: String,
+    /// Wrap standalone images that have a title or alt text in
+    /// `<figure>` with a `<figcaption>`
+    pub figure: bool,
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        Self {
+            footnote_heading: String::new(),
+            footnote_backref: "\u{21a9}\u{fe0e}".to_string(),
+            figure: false,
+        }
+    }
+}
+
+/// SCSS/Sass compilation configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SassConfig {
+    /// Output style: "expanded" or "compressed"
+    pub style: String,
+    /// Extra directories searched by `@import`/`@use`, relative to the site root
+    pub load_paths: Vec<String>,
+}
+
+impl Default for SassConfig {
+    fn default() -> Self {
+        Self {
+            style: "expanded".to_string(),
+            load_paths: Vec::new(),
+        }
+    }
+}
+
+/// Asset bundling configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BundleConfig {
+    /// Minify CSS bundles (comments and extra whitespace are removed).
+    /// JS bundles are only concatenated.
+    pub minify: bool,
+    /// Bundle path -> files concatenated into it, all relative to the public
+    /// directory (e.g. `css/bundle.css: [css/style.css, css/fonts.css]`)
+    pub files: BTreeMap<String, Vec<String>>,
+}
+
+/// Development server configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Editor URL scheme for links on error pages (`{file}` and `{line}` are substituted)
+    pub editor_url: String,
+    /// Command run by the `/__open` endpoint (`{file}` and `{line}` are substituted)
+    pub editor_command: String,
+    /// Serve over HTTPS
+    pub https: bool,
+    /// PEM certificate for HTTPS, relative to the site directory. A
+    /// self-signed one is generated if neither it nor `key` is set.
+    pub cert: Option<String>,
+    /// PEM private key of `cert`
+    pub key: Option<String>,
+    /// Compress responses with gzip or brotli, like most production hosts
+    pub compress: bool,
+    /// `no-cache` to have the browser revalidate every file, or
+    /// `production` to send the cache headers a production host would
+    pub cache: String,
+    /// Seconds files other than HTML are cached for in `production` mode
+    pub max_age: u64,
+    /// Path prefix -> upstream URL that requests under it are forwarded to,
+    /// with their whole path (e.g. `/api: http://localhost:8080`)
+    pub proxy: BTreeMap<String, String>,
+    /// User name for HTTP basic auth. If only `password` is set, any user
+    /// name is accepted. `HEXO_SERVER_USER` overrides it.
+    pub user: Option<String>,
+    /// Password for HTTP basic auth, which is off unless it is set.
+    /// `HEXO_SERVER_PASSWORD` overrides it.
+    pub password: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            editor_url: "vscode://file/{file}:{line}".to_string(),
+            editor_command: "code --goto {file}:{line}".to_string(),
+            https: false,
+            cert: None,
+            key: None,
+            compress: false,
+            cache: "no-cache".to_string(),
+            max_age: 3600,
+            proxy: BTreeMap::new(),
+            user: None,
+            password: None,
+        }
+    }
+}
+
+/// Atom feed configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeedConfig {
+    /// Number of newest posts in the feed, 0 for all
+    pub limit: usize,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self { limit: 20 }
+    }
+}
+
+/// How much of the machine a build uses
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConcurrencyConfig {
+    /// Threads rendering pages, 0 for one per CPU (or `RAYON_NUM_THREADS`)
+    pub jobs: usize,
+    /// Most output files written at the same time, 0 for no limit. Helps
+    /// on network filesystems that slow down under many parallel writes.
+    pub io_limit: usize,
+}
+
+/// Build manifest listing every generated file with its hash and source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ManifestConfig {
+    pub enable: bool,
+    /// Where to write it, relative to the site directory
+    pub path: String,
+}
+
+impl Default for ManifestConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            path: "manifest.json".to_string(),
+        }
+    }
+}
+
 /// Index generator configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -315,4 +618,76 @@ per_page: 20
         assert_eq!(config.theme, "next");
         assert_eq!(config.per_page, 20);
     }
+
+    #[test]
+    fn test_parse_taxonomies() {
+        let yaml = r#"
+taxonomies:
+  topics: topics
+  authors: people
+"#;
+        let config: SiteConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.taxonomies["topics"], "topics");
+        assert_eq!(config.taxonomies["authors"], "people");
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_taxonomy_dirs() {
+        let invalid = |dirs: &[(&str, &str)]| {
+            let mut config = SiteConfig::default();
+            for (name, dir) in dirs {
+                config.taxonomies.insert(name.to_string(), dir.to_string());
+            }
+            config.validate().is_err()
+        };
+        assert!(invalid(&[("authors", "")]));
+        assert!(invalid(&[("authors", "/")]));
+        assert!(invalid(&[("authors", "../people")]));
+        assert!(invalid(&[("authors", "a//b")]));
+        assert!(invalid(&[("authors", "archives/")]));
+        assert!(invalid(&[("authors", "tags")]));
+        assert!(invalid(&[("authors", "people"), ("editors", "/people/")]));
+        assert!(!invalid(&[
+            ("authors", "/people/"),
+            ("topics", "about/topics")
+        ]));
+    }
+
+    #[test]
+    fn test_validate_highlight_output() {
+        let mut config = SiteConfig::default();
+        assert!(config.validate().is_ok());
+        config.highlight.output = "classes".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_theme_override_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("_config.next.yml"),
+            "menu:\n  home: /\n  about: /about/\nsidebar: left\n",
+        )
+        .unwrap();
+        let mut config: SiteConfig =
+            serde_yaml::from_str("theme: next\ntheme_config:\n  menu:\n    about: /me/\n").unwrap();
+        config.load_theme_override(dir.path()).unwrap();
+
+        let menu = &config.theme_config["menu"];
+        assert_eq!(menu["home"].as_str(), Some("/"));
+        assert_eq!(menu["about"].as_str(), Some("/me/"));
+        assert_eq!(config.theme_config["sidebar"].as_str(), Some("left"));
+    }
+
+    #[test]
+    fn test_language_prefix() {
+        let config: SiteConfig = serde_yaml::from_str("languages: [en, zh]").unwrap();
+        assert_eq!(config.default_language(), "en");
+        assert_eq!(config.language_prefix("en"), "");
+        assert_eq!(config.language_prefix("zh"), "zh/");
+
+        let single = SiteConfig::default();
+        assert_eq!(single.language_prefix("zh"), "");
+    }
 }
@@ -0,0 +1,94 @@
+//! Site data files (`source/_data`)
+//!
+//! YAML, JSON and TOML files are exposed to templates as `site.data.<name>`,
+//! where `<name>` is the file path relative to `_data` without its extension
+//! (e.g. `_data/menu.yml` -> `site.data.menu`, `_data/i18n/en.json` -> `site.data["i18n/en"]`).
+
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use super::read_source;
+
+/// Load all data files under `source/_data`
+pub fn load_site_data(source_dir: &Path) -> Result<BTreeMap<String, serde_json::Value>> {
+    let data_dir = source_dir.join("_data");
+    let mut data = BTreeMap::new();
+    if !data_dir.exists() {
+        return Ok(data);
+    }
+
+    for entry in WalkDir::new(&data_dir)
+        .follow_links(true)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
+            continue;
+        };
+        if !matches!(ext, "yml" | "yaml" | "json" | "toml") {
+            continue;
+        }
+
+        let key = path
+            .strip_prefix(&data_dir)?
+            .with_extension("")
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        match parse_data_file(path, ext) {
+            Ok(value) => {
+                data.insert(key, value);
+            }
+            Err(e) => tracing::warn!("Failed to load data file {:?}: {}", path, e),
+        }
+    }
+
+    Ok(data)
+}
+
+fn parse_data_file(path: &Path, ext: &str) -> Result<serde_json::Value> {
+    let content = read_source(path)?;
+    let value = match ext {
+        "json" => serde_json::from_str(&content)?,
+        "toml" => {
+            let value: toml::Value = toml::from_str(&content)?;
+            serde_json::to_value(value)?
+        }
+        "yml" | "yaml" => {
+            let value: serde_yaml::Value = serde_yaml::from_str(&content)?;
+            serde_json::to_value(value)?
+        }
+        _ => return Err(anyhow!("Unsupported data file type: {}", ext)),
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_load_site_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().join("_data");
+        fs::create_dir_all(data_dir.join("i18n")).unwrap();
+        fs::write(data_dir.join("menu.yml"), "- name: Home\n  url: /\n").unwrap();
+        fs::write(data_dir.join("i18n/en.json"), r#"{"hello": "Hello"}"#).unwrap();
+        fs::write(data_dir.join("site.toml"), "owner = \"me\"\n").unwrap();
+        fs::write(data_dir.join("notes.txt"), "ignored").unwrap();
+
+        let data = load_site_data(dir.path()).unwrap();
+        assert_eq!(data.len(), 3);
+        assert_eq!(data["menu"][0]["name"], "Home");
+        assert_eq!(data["i18n/en"]["hello"], "Hello");
+        assert_eq!(data["site"]["owner"], "me");
+    }
+}
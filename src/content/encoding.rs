@@ -0,0 +1,66 @@
+//! Source file decoding
+//!
+//! Legacy posts are sometimes saved as GBK, Big5 or Shift_JIS. Instead of
+//! failing on them, detect the encoding and transcode to UTF-8 with a warning.
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::Path;
+
+/// Read a source file as UTF-8, transcoding from a detected legacy encoding if needed
+pub fn read_source(path: &Path) -> Result<String> {
+    let bytes = fs::read(path)?;
+    decode_source(&bytes, path)
+}
+
+fn decode_source(bytes: &[u8], path: &Path) -> Result<String> {
+    // Strip a UTF-8 byte order mark so front-matter detection still works
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+
+    let bytes = match String::from_utf8(bytes.to_vec()) {
+        Ok(content) => return Ok(content),
+        Err(e) => e.into_bytes(),
+    };
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(&bytes, true);
+    let encoding = detector.guess(None, true);
+
+    let (content, _, had_errors) = encoding.decode(&bytes);
+    if had_errors {
+        return Err(anyhow!(
+            "{:?} is not valid UTF-8 and could not be decoded as {} either; \
+            please convert it to UTF-8",
+            path,
+            encoding.name()
+        ));
+    }
+
+    tracing::warn!(
+        "{:?} is not UTF-8, transcoded from {}. Consider saving it as UTF-8.",
+        path,
+        encoding.name()
+    );
+    Ok(content.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_utf8_with_bom() {
+        let content =
+            decode_source(b"\xEF\xBB\xBF---\ntitle: a\n---\n", Path::new("a.md")).unwrap();
+        assert!(content.starts_with("---"));
+    }
+
+    #[test]
+    fn test_decode_gbk() {
+        let (gbk, _, _) =
+            encoding_rs::GBK.encode("---\ntitle: 你好世界\n---\n\n这是一篇用旧编码保存的文章。");
+        let content = decode_source(&gbk, Path::new("legacy.md")).unwrap();
+        assert!(content.contains("你好世界"));
+        assert!(content.contains("旧编码"));
+    }
+}
@@ -72,6 +72,8 @@ pub struct FrontMatter {
     pub title: Option<String>,
     pub date: Option<String>,
     pub updated: Option<String>,
+    /// Comments are enabled unless a post sets `comments: false` (Hexo behavior)
+    #[serde(default = "default_comments")]
     pub comments: bool,
     #[serde(deserialize_with = "string_or_vec", default)]
     pub tags: Vec<String>,
@@ -83,7 +85,22 @@ pub struct FrontMatter {
     /// Posts are published by default (Hexo behavior)
     #[serde(default = "default_published")]
     pub published: bool,
+    /// `draft: true` hides a post just like `published: false`
+    pub draft: bool,
     pub lang: Option<String>,
+    pub uuid: Option<String>,
+    /// Series this post belongs to
+    pub series: Option<String>,
+    /// Pin weight (`sticky: 10` or `sticky: true`)
+    pub sticky: Option<serde_yaml::Value>,
+    /// Alias for `sticky` used by some themes (`top: true`)
+    pub top: Option<serde_yaml::Value>,
+    /// Old URLs that should redirect to this post or page
+    #[serde(deserialize_with = "string_or_vec", default)]
+    pub alias: Vec<String>,
+    /// Jekyll-style name for `alias`
+    #[serde(deserialize_with = "string_or_vec", default)]
+    pub redirect_from: Vec<String>,
     #[serde(rename = "disableNunjucks")]
     pub disable_nunjucks: bool,
 
@@ -97,20 +114,32 @@ fn default_published() -> bool {
     true
 }
 
+/// Default value for comments field - true to match Hexo behavior
+fn default_comments() -> bool {
+    true
+}
+
 impl Default for FrontMatter {
     fn default() -> Self {
         Self {
             title: None,
             date: None,
             updated: None,
-            comments: false,
+            comments: true,
             tags: Vec::new(),
             categories: Vec::new(),
             layout: None,
             permalink: None,
             excerpt: None,
             published: true, // Posts are published by default
+            draft: false,
             lang: None,
+            uuid: None,
+            series: None,
+            sticky: None,
+            top: None,
+            alias: Vec::new(),
+            redirect_from: Vec::new(),
             disable_nunjucks: false,
             extra: HashMap::new(),
         }
@@ -262,14 +291,47 @@ impl FrontMatter {
         self.date.as_ref().and_then(|s| parse_date_string(s))
     }
 
+    /// Whether the post should be generated (`published` and not a `draft`)
+    pub fn is_published(&self) -> bool {
+        self.published && !self.draft
+    }
+
+    /// Old URLs from both `alias` and `redirect_from`, without duplicates
+    pub fn aliases(&self) -> Vec<String> {
+        let mut aliases: Vec<String> = Vec::new();
+        for alias in self.alias.iter().chain(&self.redirect_from) {
+            let alias = alias.trim();
+            if !alias.is_empty() && !aliases.iter().any(|a| a == alias) {
+                aliases.push(alias.to_string());
+            }
+        }
+        aliases
+    }
+
+    /// Pin weight from `sticky`/`top`; 0 means the post is not pinned.
+    /// `true` counts as 1 so numeric weights can order several pinned posts.
+    pub fn sticky_weight(&self) -> i64 {
+        let weight = |v: &serde_yaml::Value| match v {
+            serde_yaml::Value::Bool(b) => i64::from(*b),
+            serde_yaml::Value::Number(n) => n.as_i64().unwrap_or(0),
+            serde_yaml::Value::String(s) => s.trim().parse().unwrap_or(0),
+            _ => 0,
+        };
+        self.sticky
+            .as_ref()
+            .map(weight)
+            .or_else(|| self.top.as_ref().map(weight))
+            .unwrap_or(0)
+    }
+
     /// Parse the updated date string into a DateTime
     pub fn parse_updated(&self) -> Option<DateTime<Local>> {
         self.updated.as_ref().and_then(|s| parse_date_string(s))
     }
 }
 
-/// Parse a date string in various formats
-fn parse_date_string(s: &str) -> Option<DateTime<Local>> {
+/// Parse a date string in the formats front matter dates are written in
+pub fn parse_date_string(s: &str) -> Option<DateTime<Local>> {
     let s = s.trim();
 
     // Try various formats
@@ -375,6 +437,43 @@ Content here.
         assert_eq!(fm.categories, vec!["Blog"]);
     }
 
+    #[test]
+    fn test_published_and_draft() {
+        let (fm, _) = FrontMatter::parse("---\ntitle: a\n---\n").unwrap();
+        assert!(fm.is_published());
+        let (fm, _) = FrontMatter::parse("---\ntitle: a\npublished: false\n---\n").unwrap();
+        assert!(!fm.is_published());
+        let (fm, _) = FrontMatter::parse("---\ntitle: a\ndraft: true\n---\n").unwrap();
+        assert!(!fm.is_published());
+    }
+
+    #[test]
+    fn test_sticky_weight() {
+        let (fm, _) = FrontMatter::parse("---\ntitle: a\nsticky: 5\n---\n").unwrap();
+        assert_eq!(fm.sticky_weight(), 5);
+        let (fm, _) = FrontMatter::parse("---\ntitle: a\ntop: true\n---\n").unwrap();
+        assert_eq!(fm.sticky_weight(), 1);
+        let (fm, _) = FrontMatter::parse("---\ntitle: a\n---\n").unwrap();
+        assert_eq!(fm.sticky_weight(), 0);
+    }
+
+    #[test]
+    fn test_comments_default_on() {
+        let (fm, _) = FrontMatter::parse("---\ntitle: a\n---\n").unwrap();
+        assert!(fm.comments);
+        let (fm, _) = FrontMatter::parse("---\ntitle: a\ncomments: false\n---\n").unwrap();
+        assert!(!fm.comments);
+    }
+
+    #[test]
+    fn test_aliases() {
+        let (fm, _) = FrontMatter::parse(
+            "---\ntitle: a\nalias: /old/\nredirect_from:\n  - /older/\n  - /old/\n---\n",
+        )
+        .unwrap();
+        assert_eq!(fm.aliases(), vec!["/old/", "/older/"]);
+    }
+
     #[test]
     fn test_markdown_separator_not_yaml() {
         // Content that uses --- as markdown separator, not YAML front-matter
@@ -0,0 +1,100 @@
+//! Persistent post IDs
+//!
+//! Each post gets a UUID that survives permalink changes. IDs are taken from
+//! the `uuid` front-matter field when present, otherwise they are stored in a
+//! `post_ids.yml` sidecar map (keyed by source path) in the site directory.
+//! Deterministic builds derive missing IDs from the source path instead of
+//! drawing random ones, so a fresh checkout gets the same IDs every time.
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Sidecar file name, kept next to `_config.yml` so it can be committed
+const POST_IDS_FILE: &str = "post_ids.yml";
+
+/// Map of post source paths to their persistent IDs
+pub struct PostIds {
+    path: PathBuf,
+    ids: BTreeMap<String, String>,
+    dirty: bool,
+    deterministic: bool,
+}
+
+impl PostIds {
+    /// Load the ID map from the site directory (empty if it doesn't exist yet).
+    /// With `deterministic`, new IDs are v5 UUIDs of the source path.
+    pub fn load<P: AsRef<Path>>(base_dir: P, deterministic: bool) -> Result<Self> {
+        let path = base_dir.as_ref().join(POST_IDS_FILE);
+        let ids = if path.exists() {
+            let content = fs::read_to_string(&path)?;
+            serde_yaml::from_str::<Option<BTreeMap<String, String>>>(&content)?.unwrap_or_default()
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            path,
+            ids,
+            dirty: false,
+            deterministic,
+        })
+    }
+
+    /// Get the ID for a post, generating a new one if it has none yet
+    pub fn get_or_create(&mut self, source: &str) -> String {
+        if let Some(id) = self.ids.get(source) {
+            return id.clone();
+        }
+        let id = if self.deterministic {
+            uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_URL, source.as_bytes()).to_string()
+        } else {
+            uuid::Uuid::new_v4().to_string()
+        };
+        self.ids.insert(source.to_string(), id.clone());
+        self.dirty = true;
+        id
+    }
+
+    /// Write the map back if new IDs were generated
+    pub fn save(&mut self) -> Result<()> {
+        if self.dirty {
+            fs::write(&self.path, serde_yaml::to_string(&self.ids)?)?;
+            self.dirty = false;
+            tracing::debug!("Saved post IDs to {:?}", self.path);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_persist_across_loads() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut ids = PostIds::load(dir.path(), false).unwrap();
+        let first = ids.get_or_create("_posts/hello.md");
+        assert_eq!(ids.get_or_create("_posts/hello.md"), first);
+        ids.save().unwrap();
+
+        let mut reloaded = PostIds::load(dir.path(), false).unwrap();
+        assert_eq!(reloaded.get_or_create("_posts/hello.md"), first);
+        assert_ne!(reloaded.get_or_create("_posts/other.md"), first);
+    }
+
+    #[test]
+    fn test_deterministic_ids() {
+        let first = PostIds::load(tempfile::tempdir().unwrap().path(), true)
+            .unwrap()
+            .get_or_create("_posts/hello.md");
+        let second = PostIds::load(tempfile::tempdir().unwrap().path(), true)
+            .unwrap()
+            .get_or_create("_posts/hello.md");
+        assert_eq!(first, second);
+        assert_eq!(uuid::Uuid::parse_str(&first).unwrap().get_version_num(), 5);
+    }
+}
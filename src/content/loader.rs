@@ -2,48 +2,114 @@
 
 use anyhow::Result;
 use chrono::Local;
+use std::cell::Cell;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
-use super::{FrontMatter, MarkdownRenderer, Page, Post};
+use super::{read_source, FrontMatter, MarkdownRenderer, Page, Post, PostIds};
+use crate::cache::ChangeSet;
 use crate::Hexo;
 
 /// Loads content from the source directory
 pub struct ContentLoader<'a> {
     hexo: &'a Hexo,
     renderer: MarkdownRenderer,
+    /// Directory post content is spilled to, see `spill_to`
+    spill_dir: Option<PathBuf>,
+    spilled: Cell<usize>,
+    /// Write new post IDs back to `post_ids.yml`, see `persist_ids`
+    persist_ids: bool,
 }
 
 impl<'a> ContentLoader<'a> {
     /// Create a new content loader
     pub fn new(hexo: &'a Hexo) -> Self {
-        let renderer =
-            MarkdownRenderer::with_options("base16-ocean.dark", hexo.config.highlight.line_number);
-        Self { hexo, renderer }
+        let renderer = MarkdownRenderer::with_config(hexo.config.markdown.clone())
+            .with_highlight(hexo.config.highlight.clone());
+        Self {
+            hexo,
+            renderer,
+            spill_dir: None,
+            spilled: Cell::new(0),
+            persist_ids: false,
+        }
+    }
+
+    /// Save the IDs generated for posts without one, so they stay the same in
+    /// later builds. Without this, loading leaves the site untouched.
+    pub fn persist_ids(mut self) -> Self {
+        self.persist_ids = true;
+        self
+    }
+
+    /// Move the content of each loaded post to a file in `dir` (which must
+    /// exist) instead of keeping it in memory, see `Post::spill`
+    pub fn spill_to(mut self, dir: PathBuf) -> Self {
+        self.spill_dir = Some(dir);
+        self
     }
 
-    /// Load all posts from source/_posts
+    /// Load all published posts from source/_posts
+    /// (plus unpublished posts and source/_drafts when `render_drafts` is set)
     pub fn load_posts(&self) -> Result<Vec<Post>> {
-        let posts_dir = self.hexo.source_dir.join("_posts");
+        let mut posts = self.load_posts_from("_posts")?;
+        if self.hexo.config.render_drafts {
+            posts.extend(self.load_posts_from("_drafts")?);
+        } else {
+            posts.retain(|p| p.published);
+        }
+
+        // Sort by date descending (newest first)
+        posts.sort_by(|a, b| b.date.cmp(&a.date));
+
+        Ok(posts)
+    }
+
+    /// Load unpublished posts: `published: false`/`draft: true` posts in
+    /// source/_posts and everything in source/_drafts
+    pub fn load_drafts(&self) -> Result<Vec<Post>> {
+        let mut drafts = self.load_posts_from("_posts")?;
+        drafts.retain(|p| !p.published);
+        drafts.extend(self.load_posts_from("_drafts")?);
+
+        drafts.sort_by_key(|p| std::cmp::Reverse(p.date));
+
+        Ok(drafts)
+    }
+
+    /// Load every post in a source sub-directory, regardless of published state
+    fn load_posts_from(&self, dir: &str) -> Result<Vec<Post>> {
+        let posts_dir = self.hexo.source_dir.join(dir);
         if !posts_dir.exists() {
             return Ok(Vec::new());
         }
 
+        let is_drafts_dir = dir == "_drafts";
         let mut posts = Vec::new();
+        let mut ids = PostIds::load(&self.hexo.base_dir, self.hexo.config.deterministic)?;
 
         for entry in WalkDir::new(&posts_dir)
             .follow_links(true)
+            .sort_by_file_name()
             .into_iter()
             .filter_map(|e| e.ok())
         {
             let path = entry.path();
             if path.is_file() && is_markdown_file(path) {
                 match self.load_post(path) {
-                    Ok(post) => {
-                        if post.published || self.hexo.config.render_drafts {
-                            posts.push(post);
+                    Ok(mut post) => {
+                        if post.uuid.is_empty() {
+                            post.uuid = ids.get_or_create(&post.source);
+                        }
+                        if is_drafts_dir {
+                            post.published = false;
+                        }
+                        if let Some(dir) = &self.spill_dir {
+                            let n = self.spilled.replace(self.spilled.get() + 1);
+                            post.spill(&dir.join(format!("{}.html", n)))?;
                         }
+                        posts.push(post);
                     }
                     Err(e) => {
                         tracing::warn!("Failed to load post {:?}: {}", path, e);
@@ -52,16 +118,20 @@ impl<'a> ContentLoader<'a> {
             }
         }
 
-        // Sort by date descending (newest first)
-        posts.sort_by(|a, b| b.date.cmp(&a.date));
+        if self.persist_ids {
+            ids.save()?;
+        }
 
         Ok(posts)
     }
 
     /// Load a single post from a file
     fn load_post(&self, path: &Path) -> Result<Post> {
-        let content = fs::read_to_string(path)?;
+        let content = read_source(path)?;
         let (fm, body) = FrontMatter::parse(&content)?;
+        let sticky = fm.sticky_weight();
+        let aliases = fm.aliases();
+        let published = fm.is_published();
 
         // Get file metadata for dates
         let metadata = fs::metadata(path)?;
@@ -103,7 +173,9 @@ impl<'a> ContentLoader<'a> {
             .unwrap_or("untitled")
             .to_string();
         let slug = filename_slug;
-        let permalink_path = self.generate_permalink(&date, &slug, &categories);
+        let lang = self.post_language(fm.lang.as_deref(), &source);
+        let permalink_path =
+            self.generate_permalink(&date, &slug, &categories, lang.as_deref().unwrap_or(""));
         let permalink = format!(
             "{}{}",
             self.hexo.config.url.trim_end_matches('/'),
@@ -137,20 +209,142 @@ impl<'a> ContentLoader<'a> {
         post.path = permalink_path.clone();
         post.permalink = permalink;
         post.comments = fm.comments;
-        post.published = fm.published;
-        post.lang = fm.lang;
+        post.published = published;
+        post.lang = lang;
         post.slug = slug;
+        // An invalid `uuid` is left for the ID map to fill in
+        post.uuid = fm
+            .uuid
+            .and_then(|id| match uuid::Uuid::parse_str(id.trim()) {
+                Ok(id) => Some(id.hyphenated().to_string()),
+                Err(e) => {
+                    tracing::warn!("{}: invalid uuid {:?}: {}", post.source, id, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        post.sticky = sticky;
+        post.aliases = aliases;
+        post.series = fm.series.filter(|s| !s.trim().is_empty());
         post.extra = fm.extra;
 
         Ok(post)
     }
 
+    /// Reload the posts and pages among `changed` paths into already loaded
+    /// `posts` and `pages`, adding new files and dropping deleted ones.
+    /// Returns `None`, leaving both untouched, if something other than post
+    /// or page markdown changed and everything has to be loaded again.
+    pub fn reload(
+        &self,
+        posts: &mut Vec<Post>,
+        pages: &mut Vec<Page>,
+        changed: &[PathBuf],
+    ) -> Result<Option<ChangeSet>> {
+        // (source, is post) of each changed file
+        let mut sources = Vec::new();
+        for path in changed {
+            let Ok(relative) = path.strip_prefix(&self.hexo.source_dir) else {
+                return Ok(None);
+            };
+            if !is_markdown_file(path) {
+                return Ok(None);
+            }
+            let first = relative
+                .components()
+                .next()
+                .and_then(|c| c.as_os_str().to_str())
+                .unwrap_or_default();
+            let source = relative.to_string_lossy().to_string();
+            match first {
+                "_posts" => sources.push((source, true)),
+                "_drafts" if self.hexo.config.render_drafts => sources.push((source, true)),
+                "_drafts" => {}
+                _ if first.starts_with('_') => return Ok(None),
+                _ => sources.push((source, false)),
+            }
+        }
+
+        let mut changes = ChangeSet::default();
+        let mut ids = PostIds::load(&self.hexo.base_dir, self.hexo.config.deterministic)?;
+        for (source, is_post) in sources {
+            let path = self.hexo.source_dir.join(&source);
+            if is_post {
+                let old = posts
+                    .iter()
+                    .position(|p| p.source == source)
+                    .map(|i| posts.remove(i));
+                let new = match path.is_file() {
+                    true => {
+                        let mut post = self.load_post(&path)?;
+                        if post.uuid.is_empty() {
+                            post.uuid = ids.get_or_create(&post.source);
+                        }
+                        if source.starts_with("_drafts") {
+                            post.published = false;
+                        }
+                        Some(post).filter(|p| p.published || self.hexo.config.render_drafts)
+                    }
+                    false => None,
+                };
+                match (&old, &new) {
+                    (Some(old), Some(new)) => {
+                        changes.all |= post_listing(old) != post_listing(new);
+                        if old.path != new.path {
+                            changes.removed.push(old.path.clone());
+                        }
+                    }
+                    (Some(old), None) => {
+                        changes.all = true;
+                        changes.removed.push(old.path.clone());
+                    }
+                    (None, Some(_)) => changes.all = true,
+                    (None, None) => continue,
+                }
+                posts.extend(new);
+                changes.posts.insert(source);
+            } else {
+                let old = pages
+                    .iter()
+                    .position(|p| p.source == source)
+                    .map(|i| pages.remove(i));
+                let new = match path.is_file() {
+                    true => Some(self.load_page(&path)?),
+                    false => None,
+                };
+                match (&old, &new) {
+                    (Some(old), Some(new)) => {
+                        changes.all |= page_listing(old) != page_listing(new);
+                        if old.path != new.path {
+                            changes.removed.push(old.path.clone());
+                        }
+                    }
+                    (Some(old), None) => {
+                        changes.all = true;
+                        changes.removed.push(old.path.clone());
+                    }
+                    (None, Some(_)) => changes.all = true,
+                    (None, None) => continue,
+                }
+                pages.extend(new);
+                changes.pages.insert(source);
+            }
+        }
+        if self.persist_ids {
+            ids.save()?;
+        }
+
+        posts.sort_by_key(|p| std::cmp::Reverse(p.date));
+        Ok(Some(changes))
+    }
+
     /// Load all pages (non-post markdown files)
     pub fn load_pages(&self) -> Result<Vec<Page>> {
         let mut pages = Vec::new();
 
         for entry in WalkDir::new(&self.hexo.source_dir)
             .follow_links(true)
+            .sort_by_file_name()
             .into_iter()
             .filter_map(|e| e.ok())
         {
@@ -184,8 +378,9 @@ impl<'a> ContentLoader<'a> {
 
     /// Load a single page from a file
     fn load_page(&self, path: &Path) -> Result<Page> {
-        let content = fs::read_to_string(path)?;
+        let content = read_source(path)?;
         let (fm, body) = FrontMatter::parse(&content)?;
+        let aliases = fm.aliases();
 
         // Get file metadata
         let metadata = fs::metadata(path)?;
@@ -251,7 +446,24 @@ impl<'a> ContentLoader<'a> {
         page.path = page_path;
         page.permalink = permalink;
         page.comments = fm.comments;
-        page.lang = fm.lang;
+        page.lang = if self.hexo.config.languages.is_empty() {
+            fm.lang
+        } else {
+            let lang = fm.lang.filter(|lang| {
+                let known = self.hexo.config.languages.contains(lang);
+                if !known {
+                    tracing::warn!(
+                        "{}: lang {:?} is not in `languages`, using {:?}",
+                        page.source,
+                        lang,
+                        self.hexo.config.default_language()
+                    );
+                }
+                known
+            });
+            lang.or_else(|| Some(self.hexo.config.default_language().to_string()))
+        };
+        page.aliases = aliases;
         page.extra = fm.extra;
 
         Ok(page)
@@ -263,6 +475,7 @@ impl<'a> ContentLoader<'a> {
         date: &chrono::DateTime<Local>,
         slug: &str,
         categories: &[String],
+        lang: &str,
     ) -> String {
         let pattern = &self.hexo.config.permalink;
 
@@ -282,12 +495,77 @@ impl<'a> ContentLoader<'a> {
             .replace(":category", &category)
             .replace(":id", slug);
 
+        // Non-default languages live in their own tree unless the pattern
+        // already places `:lang` itself
+        let result = if pattern.contains(":lang") {
+            result.replace(":lang", lang)
+        } else {
+            format!("{}{}", self.hexo.config.language_prefix(lang), result)
+        };
+
         format!(
             "{}{}",
             self.hexo.config.root,
             result.trim_start_matches('/')
         )
     }
+
+    /// Language of a post: `lang` front matter, then a `_posts/<lang>/`
+    /// directory, then the site default. Only set on multilingual sites.
+    fn post_language(&self, front_matter: Option<&str>, source: &str) -> Option<String> {
+        let languages = &self.hexo.config.languages;
+        if languages.is_empty() {
+            return front_matter.map(String::from);
+        }
+        if let Some(lang) = front_matter {
+            if languages.iter().any(|l| l == lang) {
+                return Some(lang.to_string());
+            }
+            tracing::warn!(
+                "{}: lang {:?} is not in `languages`, using {:?}",
+                source,
+                lang,
+                self.hexo.config.default_language()
+            );
+            return Some(self.hexo.config.default_language().to_string());
+        }
+        let from_dir = Path::new(source)
+            .components()
+            .nth(1)
+            .and_then(|c| c.as_os_str().to_str())
+            .filter(|dir| languages.iter().any(|l| l == dir));
+        Some(
+            from_dir
+                .unwrap_or(self.hexo.config.default_language())
+                .to_string(),
+        )
+    }
+}
+
+/// What other pages can show of a post: in lists, navigation, series, tag
+/// clouds and translation links
+fn post_listing(post: &Post) -> impl PartialEq + '_ {
+    (
+        (
+            &post.title,
+            post.date,
+            &post.path,
+            &post.tags,
+            &post.categories,
+        ),
+        (
+            &post.lang,
+            &post.series,
+            post.sticky,
+            &post.layout,
+            post.cover(),
+        ),
+    )
+}
+
+/// What other pages can show of a page
+fn page_listing(page: &Page) -> impl PartialEq + '_ {
+    (&page.title, page.date, &page.path, &page.layout, &page.lang)
 }
 
 /// Check if a file is a markdown file
@@ -303,3 +581,152 @@ fn parse_categories(categories: &[String]) -> Vec<String> {
     // For now, flatten any nested structure
     categories.to_vec()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_front_matter_uuid() {
+        let dir = tempfile::tempdir().unwrap();
+        let hexo = Hexo::new(dir.path()).unwrap();
+        let posts_dir = hexo.source_dir.join("_posts");
+        fs::create_dir_all(&posts_dir).unwrap();
+        fs::write(
+            posts_dir.join("a.md"),
+            "---\ntitle: A\nuuid: 6BA7B810-9DAD-11D1-80B4-00C04FD430C8\n---\na\n",
+        )
+        .unwrap();
+        fs::write(
+            posts_dir.join("b.md"),
+            "---\ntitle: B\nuuid: first-post\n---\nb\n",
+        )
+        .unwrap();
+
+        let posts = ContentLoader::new(&hexo).load_posts().unwrap();
+        let uuid = |title: &str| {
+            posts
+                .iter()
+                .find(|p| p.title == title)
+                .map(|p| p.uuid.clone())
+                .unwrap()
+        };
+        assert_eq!(uuid("A"), "6ba7b810-9dad-11d1-80b4-00c04fd430c8");
+        assert!(uuid::Uuid::parse_str(&uuid("B")).is_ok());
+        // Only a loader asked to persist IDs writes them
+        assert!(!dir.path().join("post_ids.yml").exists());
+        ContentLoader::new(&hexo)
+            .persist_ids()
+            .load_posts()
+            .unwrap();
+        assert!(dir.path().join("post_ids.yml").exists());
+    }
+
+    #[test]
+    fn test_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let hexo = Hexo::new(dir.path()).unwrap();
+        let posts_dir = hexo.source_dir.join("_posts");
+        fs::create_dir_all(&posts_dir).unwrap();
+        let post = |title: &str, body: &str| {
+            format!(
+                "---\ntitle: {}\ndate: 2024-01-01 00:00:00\n---\n{}\n",
+                title, body
+            )
+        };
+        fs::write(posts_dir.join("a.md"), post("A", "one")).unwrap();
+        fs::write(
+            hexo.source_dir.join("about.md"),
+            "---\ntitle: About\n---\nhi\n",
+        )
+        .unwrap();
+
+        let loader = ContentLoader::new(&hexo).persist_ids();
+        let mut posts = loader.load_posts().unwrap();
+        let mut pages = loader.load_pages().unwrap();
+        let a = posts_dir.join("a.md");
+        let uuid = posts[0].uuid.clone();
+
+        // A body edit only touches the post itself
+        fs::write(&a, post("A", "two")).unwrap();
+        let changes = loader
+            .reload(&mut posts, &mut pages, std::slice::from_ref(&a))
+            .unwrap()
+            .unwrap();
+        assert!(!changes.all);
+        assert_eq!(changes.posts, BTreeSet::from(["_posts/a.md".to_string()]));
+        assert!(posts[0].content.contains("two"));
+        assert_eq!(posts[0].uuid, uuid);
+
+        // A new title shows up in lists
+        fs::write(&a, post("B", "two")).unwrap();
+        let changes = loader
+            .reload(&mut posts, &mut pages, std::slice::from_ref(&a))
+            .unwrap()
+            .unwrap();
+        assert!(changes.all);
+
+        // Adding and removing files
+        let b = posts_dir.join("b.md");
+        fs::write(&b, post("C", "three")).unwrap();
+        let changes = loader
+            .reload(&mut posts, &mut pages, std::slice::from_ref(&b))
+            .unwrap()
+            .unwrap();
+        assert!(changes.all);
+        assert_eq!(posts.len(), 2);
+        fs::remove_file(&b).unwrap();
+        let changes = loader
+            .reload(&mut posts, &mut pages, &[b])
+            .unwrap()
+            .unwrap();
+        assert_eq!(changes.removed.len(), 1);
+        assert_eq!(posts.len(), 1);
+
+        let about = hexo.source_dir.join("about.md");
+        fs::write(&about, "---\ntitle: About\n---\nbye\n").unwrap();
+        let changes = loader
+            .reload(&mut posts, &mut pages, &[about])
+            .unwrap()
+            .unwrap();
+        assert_eq!(changes.pages, BTreeSet::from(["about.md".to_string()]));
+        assert!(pages[0].content.contains("bye"));
+
+        // Anything else needs a full reload
+        let data = hexo.source_dir.join("_data/menu.yml");
+        assert!(loader
+            .reload(&mut posts, &mut pages, &[data])
+            .unwrap()
+            .is_none());
+        let config = dir.path().join("_config.yml");
+        assert!(loader
+            .reload(&mut posts, &mut pages, &[config])
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_spill() {
+        let dir = tempfile::tempdir().unwrap();
+        let hexo = Hexo::new(dir.path()).unwrap();
+        let posts_dir = hexo.source_dir.join("_posts");
+        fs::create_dir_all(&posts_dir).unwrap();
+        fs::write(
+            posts_dir.join("a.md"),
+            "---\ntitle: A\n---\n![x](/x.png)\n\nbody\n",
+        )
+        .unwrap();
+        let spill_dir = dir.path().join("spill");
+        fs::create_dir_all(&spill_dir).unwrap();
+
+        let loaded = ContentLoader::new(&hexo).load_posts().unwrap();
+        let spilled = ContentLoader::new(&hexo)
+            .spill_to(spill_dir)
+            .load_posts()
+            .unwrap();
+        assert!(spilled[0].content.is_empty() && spilled[0].raw.is_empty());
+        assert_eq!(spilled[0].full_content().unwrap(), loaded[0].content);
+        assert_eq!(spilled[0].cover().as_deref(), Some("/x.png"));
+    }
+}
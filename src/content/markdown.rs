@@ -1,23 +1,37 @@
 //! Markdown rendering with syntax highlighting
 
 use anyhow::Result;
+use std::collections::HashMap;
+
 use pulldown_cmark::{
     html, CodeBlockKind, CowStr, Event, HeadingLevel, LinkType, Options, Parser, Tag, TagEnd,
 };
-use syntect::html::{ClassStyle, ClassedHTMLGenerator};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::{
+    css_for_theme_with_class_style, styled_line_to_highlighted_html, ClassStyle,
+    ClassedHTMLGenerator, IncludeBackground,
+};
 use syntect::parsing::SyntaxSet;
 
+use crate::config::{HighlightConfig, MarkdownConfig};
+
+/// Class prefix for "classed" highlight output, keeps syntect classes from clashing with theme CSS
+const HIGHLIGHT_CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
 /// Markdown renderer
 pub struct MarkdownRenderer {
     syntax_set: SyntaxSet,
+    config: MarkdownConfig,
+    highlight: HighlightConfig,
+    /// Color theme for "inline" highlighting
+    theme: Option<Theme>,
 }
 
 impl MarkdownRenderer {
     /// Create a new markdown renderer
     pub fn new() -> Self {
-        Self {
-            syntax_set: SyntaxSet::load_defaults_newlines(),
-        }
+        Self::with_config(MarkdownConfig::default())
     }
 
     /// Create with custom settings (kept for API compatibility)
@@ -25,6 +39,27 @@ impl MarkdownRenderer {
         Self::new()
     }
 
+    /// Create a renderer using the site's markdown configuration
+    pub fn with_config(config: MarkdownConfig) -> Self {
+        Self {
+            syntax_set: SyntaxSet::load_defaults_newlines(),
+            config,
+            highlight: HighlightConfig::default(),
+            theme: None,
+        }
+    }
+
+    /// Use the site's highlight configuration for code blocks
+    pub fn with_highlight(mut self, highlight: HighlightConfig) -> Self {
+        self.theme = if highlight.output == "inline" {
+            load_highlight_theme(&highlight.theme)
+        } else {
+            None
+        };
+        self.highlight = highlight;
+        self
+    }
+
     /// Render markdown to HTML
     pub fn render(&self, markdown: &str) -> Result<String> {
         // Enable most options but NOT YAML metadata blocks
@@ -47,11 +82,24 @@ impl MarkdownRenderer {
         // Track heading state for adding IDs and anchor links
         let mut in_heading: Option<HeadingLevel> = None;
         let mut heading_text = String::new();
+        // The heading's rendered content, which also keeps footnote references
+        let mut heading_html = String::new();
 
         // Track link state for adding target="_blank" to external links
         let mut in_external_link: Option<(String, String)> = None; // (url, title)
         let mut link_text = String::new();
 
+        // Track footnotes: numbers are assigned in order of first reference,
+        // definitions are pulled out of the flow and rendered at the end
+        let mut footnote_numbers: HashMap<String, usize> = HashMap::new();
+        let mut footnote_ref_counts: HashMap<String, usize> = HashMap::new();
+        let mut footnote_defs: HashMap<String, Vec<Event>> = HashMap::new();
+        let mut in_footnote: Option<(String, usize)> = None; // (label, start index in events)
+
+        // Track open lists and items so task list markers can annotate them
+        let mut list_starts: Vec<usize> = Vec::new();
+        let mut item_starts: Vec<usize> = Vec::new();
+
         for event in parser {
             match event {
                 Event::Start(Tag::CodeBlock(kind)) => {
@@ -80,18 +128,94 @@ impl MarkdownRenderer {
                 Event::Text(text) if in_code_block => {
                     code_block_content.push_str(&text);
                 }
+                Event::FootnoteReference(label) => {
+                    let label = label.to_string();
+                    let next = footnote_numbers.len() + 1;
+                    let num = *footnote_numbers.entry(label.clone()).or_insert(next);
+                    let count = footnote_ref_counts.entry(label).or_insert(0);
+                    let ref_id = if *count == 0 {
+                        format!("fnref{}", num)
+                    } else {
+                        format!("fnref{}:{}", num, count)
+                    };
+                    *count += 1;
+                    let ref_html = format!(
+                        "<sup class=\"footnote-ref\"><a href=\"#fn{}\" id=\"{}\">[{}]</a></sup>",
+                        num, ref_id, num
+                    );
+                    if in_external_link.is_some() {
+                        link_text.push_str(&ref_html);
+                    } else if in_heading.is_some() {
+                        heading_html.push_str(&ref_html);
+                    } else {
+                        events.push(Event::Html(CowStr::from(ref_html)));
+                    }
+                }
+                Event::Start(Tag::List(start)) => {
+                    list_starts.push(events.len());
+                    events.push(Event::Start(Tag::List(start)));
+                }
+                Event::End(TagEnd::List(ordered)) => {
+                    list_starts.pop();
+                    events.push(Event::End(TagEnd::List(ordered)));
+                }
+                Event::Start(Tag::Item) => {
+                    item_starts.push(events.len());
+                    events.push(Event::Start(Tag::Item));
+                }
+                Event::End(TagEnd::Item) => {
+                    item_starts.pop();
+                    events.push(Event::End(TagEnd::Item));
+                }
+                // Render GitHub task lists as disabled checkboxes with
+                // markdown-it-task-lists classes so themes can style them
+                Event::TaskListMarker(checked) => {
+                    if let Some(&idx) = item_starts.last() {
+                        events[idx] = Event::Html(CowStr::from("<li class=\"task-list-item\">"));
+                    }
+                    if let Some(&idx) = list_starts.last() {
+                        if let Event::Start(Tag::List(start)) = &events[idx] {
+                            let list_html = match start {
+                                Some(1) => "<ol class=\"contains-task-list\">\n".to_string(),
+                                Some(n) => {
+                                    format!("<ol class=\"contains-task-list\" start=\"{}\">\n", n)
+                                }
+                                None => "<ul class=\"contains-task-list\">\n".to_string(),
+                            };
+                            events[idx] = Event::Html(CowStr::from(list_html));
+                        }
+                    }
+                    let checkbox = if checked {
+                        "<input type=\"checkbox\" class=\"task-list-item-checkbox\" disabled checked> "
+                    } else {
+                        "<input type=\"checkbox\" class=\"task-list-item-checkbox\" disabled> "
+                    };
+                    events.push(Event::Html(CowStr::from(checkbox)));
+                }
+                Event::Start(Tag::FootnoteDefinition(label)) => {
+                    in_footnote = Some((label.to_string(), events.len()));
+                }
+                Event::End(TagEnd::FootnoteDefinition) => {
+                    if let Some((label, start)) = in_footnote.take() {
+                        let body: Vec<Event> = events.drain(start..).collect();
+                        footnote_defs.insert(label, body);
+                    }
+                }
                 // Handle heading start - capture the level and prepare to collect text
                 Event::Start(Tag::Heading { level, .. }) => {
                     in_heading = Some(level);
                     heading_text.clear();
+                    heading_html.clear();
                 }
                 // Collect text inside headings
                 Event::Text(ref text) if in_heading.is_some() => {
                     heading_text.push_str(text);
+                    heading_html.push_str(&html_escape(text));
                     // Don't push the event yet, we'll create a custom heading
                 }
                 Event::Code(ref code) if in_heading.is_some() => {
                     heading_text.push_str(code);
+                    heading_html.push_str(&html_escape(code));
                     // Don't push the event yet
                 }
                 // Handle heading end - generate heading with ID and anchor
@@ -104,15 +228,15 @@ impl MarkdownRenderer {
                         // Generate heading HTML like Hexo:
                         let escaped_id = html_escape_attr(&id);
                         let escaped_title = html_escape_attr(&heading_text);
-                        let escaped_text = html_escape(&heading_text);
-                        let heading_html = format!(
+                        let html = format!(
                             "<h{} id=\"{}\"><a href=\"#{}\" class=\"headerlink\" title=\"{}\"></a>{}</h{}>",
-                            level_num, escaped_id, escaped_id, escaped_title, escaped_text, level_num
+                            level_num, escaped_id, escaped_id, escaped_title, heading_html, level_num
                         );
 
-                        events.push(Event::Html(CowStr::from(heading_html)));
+                        events.push(Event::Html(CowStr::from(html)));
                         in_heading = None;
                         heading_text.clear();
+                        heading_html.clear();
                     }
                 }
                 // Handle external links - add target="_blank" rel="noopener"
@@ -173,12 +297,84 @@ impl MarkdownRenderer {
             }
         }
 
+        if self.config.figure {
+            events = wrap_figures(events);
+        }
+
         let mut html_output = String::new();
         html::push_html(&mut html_output, events.into_iter());
 
+        if !footnote_numbers.is_empty() {
+            html_output.push_str(&self.render_footnotes(
+                &footnote_numbers,
+                &footnote_ref_counts,
+                footnote_defs,
+            ));
+        }
+
         Ok(html_output)
     }
 
+    /// Render the collected footnote definitions as a section with back-references
+    /// (markup follows markdown-it-footnote so existing theme CSS applies)
+    fn render_footnotes(
+        &self,
+        numbers: &HashMap<String, usize>,
+        ref_counts: &HashMap<String, usize>,
+        mut defs: HashMap<String, Vec<Event>>,
+    ) -> String {
+        let mut ordered: Vec<(&String, &usize)> = numbers.iter().collect();
+        ordered.sort_by_key(|(_, num)| **num);
+
+        let mut html_output =
+            String::from("<hr class=\"footnotes-sep\">\n<section class=\"footnotes\">\n");
+        if !self.config.footnote_heading.is_empty() {
+            html_output.push_str(&format!(
+                "<h2 class=\"footnotes-heading\">{}</h2>\n",
+                html_escape(&self.config.footnote_heading)
+            ));
+        }
+        html_output.push_str("<ol class=\"footnotes-list\">\n");
+
+        for (label, num) in ordered {
+            // References without a matching definition have nothing to list
+            let Some(body) = defs.remove(label) else {
+                continue;
+            };
+            let mut item = String::new();
+            html::push_html(&mut item, body.into_iter());
+
+            let count = ref_counts.get(label).copied().unwrap_or(1);
+            let backrefs: String = (0..count)
+                .map(|i| {
+                    let ref_id = if i == 0 {
+                        format!("fnref{}", num)
+                    } else {
+                        format!("fnref{}:{}", num, i)
+                    };
+                    format!(
+                        " <a href=\"#{}\" class=\"footnote-backref\">{}</a>",
+                        ref_id, self.config.footnote_backref
+                    )
+                })
+                .collect();
+
+            // Place back-references inside the last paragraph when possible
+            let item = item.trim_end();
+            let item = match item.strip_suffix("</p>") {
+                Some(rest) => format!("{}{}</p>", rest, backrefs),
+                None => format!("{}{}", item, backrefs),
+            };
+            html_output.push_str(&format!(
+                "<li id=\"fn{}\" class=\"footnote-item\">{}</li>\n",
+                num, item
+            ));
+        }
+
+        html_output.push_str("</ol>\n</section>\n");
+        html_output
+    }
+
     /// Highlight a code block - output Prism.js compatible format with syntax highlighting
     fn highlight_code(&self, code: &str, lang: Option<&str>) -> String {
         let lang = lang.unwrap_or("plain");
@@ -188,6 +384,57 @@ impl MarkdownRenderer {
             .syntax_set
             .find_syntax_by_token(lang)
             .or_else(|| self.syntax_set.find_syntax_by_extension(lang));
+        // The info string after the fence is arbitrary text
+        let lang = &html_escape_attr(lang);
+
+        match (self.highlight.output.as_str(), syntax) {
+            ("classed", Some(syntax)) => {
+                let mut generator = ClassedHTMLGenerator::new_with_class_style(
+                    syntax,
+                    &self.syntax_set,
+                    HIGHLIGHT_CLASS_STYLE,
+                );
+                for line in syntect::util::LinesWithEndings::from(code) {
+                    let _ = generator.parse_html_for_line_which_includes_newline(line);
+                }
+                return format!(
+                    "<pre class=\"hl-code language-{}\" data-language=\"{}\"><code>{}</code></pre>",
+                    lang,
+                    lang,
+                    generator.finalize()
+                );
+            }
+            ("inline", Some(syntax)) => {
+                if let Some(theme) = &self.theme {
+                    let mut highlighter = HighlightLines::new(syntax, theme);
+                    let mut html = String::new();
+                    for line in syntect::util::LinesWithEndings::from(code) {
+                        let regions = highlighter
+                            .highlight_line(line, &self.syntax_set)
+                            .unwrap_or_default();
+                        html.push_str(
+                            &styled_line_to_highlighted_html(&regions, IncludeBackground::No)
+                                .unwrap_or_else(|_| html_escape(line)),
+                        );
+                    }
+                    let background = theme
+                        .settings
+                        .background
+                        .map(|c| {
+                            format!(
+                                " style=\"background-color:#{:02x}{:02x}{:02x};\"",
+                                c.r, c.g, c.b
+                            )
+                        })
+                        .unwrap_or_default();
+                    return format!(
+                        "<pre class=\"hl-code language-{}\" data-language=\"{}\"{}><code>{}</code></pre>",
+                        lang, lang, background, html
+                    );
+                }
+            }
+            _ => {}
+        }
 
         let highlighted = if let Some(syntax) = syntax {
             // Use ClassedHTMLGenerator with Prism-compatible class names
@@ -230,12 +477,105 @@ impl MarkdownRenderer {
     }
 }
 
+/// Stylesheet for "classed" highlight output, `None` for other output modes
+pub fn highlight_stylesheet(highlight: &HighlightConfig) -> Option<String> {
+    if highlight.output != "classed" {
+        return None;
+    }
+    let theme = load_highlight_theme(&highlight.theme)?;
+    css_for_theme_with_class_style(&theme, HIGHLIGHT_CLASS_STYLE).ok()
+}
+
+/// Look up a bundled syntect theme by name
+fn load_highlight_theme(name: &str) -> Option<Theme> {
+    let mut themes = ThemeSet::load_defaults().themes;
+    let theme = themes.remove(name);
+    if theme.is_none() {
+        tracing::warn!(
+            "Unknown highlight theme '{}', available: {}",
+            name,
+            themes.keys().cloned().collect::<Vec<_>>().join(", ")
+        );
+    }
+    theme
+}
+
 impl Default for MarkdownRenderer {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// Replace paragraphs that hold nothing but an image with a `<figure>`,
+/// captioned by the image title (or alt text when there is no title)
+fn wrap_figures(events: Vec<Event>) -> Vec<Event> {
+    let mut result: Vec<Event> = Vec::with_capacity(events.len());
+    let mut i = 0;
+
+    while i < events.len() {
+        if let Some((figure, len)) = figure_at(&events[i..]) {
+            result.push(Event::Html(CowStr::from(figure)));
+            i += len;
+        } else {
+            result.push(events[i].clone());
+            i += 1;
+        }
+    }
+
+    result
+}
+
+/// Figure markup and event count if `events` starts with an image-only paragraph
+fn figure_at(events: &[Event]) -> Option<(String, usize)> {
+    let (
+        Event::Start(Tag::Paragraph),
+        Some(Event::Start(Tag::Image {
+            dest_url, title, ..
+        })),
+    ) = (events.first()?, events.get(1))
+    else {
+        return None;
+    };
+
+    let mut alt = String::new();
+    let mut end = 2;
+    loop {
+        match events.get(end)? {
+            Event::Text(text) | Event::Code(text) => alt.push_str(text),
+            Event::SoftBreak | Event::HardBreak => alt.push(' '),
+            Event::End(TagEnd::Image) => break,
+            _ => return None,
+        }
+        end += 1;
+    }
+    if !matches!(events.get(end + 1)?, Event::End(TagEnd::Paragraph)) {
+        return None;
+    }
+
+    let caption = if title.is_empty() {
+        alt.as_str()
+    } else {
+        title
+    };
+    if caption.trim().is_empty() {
+        return None;
+    }
+
+    let title_attr = if title.is_empty() {
+        String::new()
+    } else {
+        format!(" title=\"{}\"", html_escape_attr(title))
+    };
+    let figure = format!(
+        "<figure><img src=\"{}\" alt=\"{}\"{}><figcaption>{}</figcaption></figure>\n",
+        html_escape_attr(dest_url),
+        html_escape_attr(&alt),
+        title_attr,
+        html_escape(caption)
+    );
+    Some((figure, end + 2))
+}
+
 /// Simple HTML escaping
 fn html_escape(s: &str) -> String {
     s.replace('&', "&amp;")
@@ -376,6 +716,123 @@ mod tests {
         assert!(!html.contains("<p>some code here</p>"));
     }
 
+    #[test]
+    fn test_render_footnotes() {
+        let renderer = MarkdownRenderer::new();
+        let html = renderer
+            .render(
+                "Text with a note[^a] and another[^b], again[^a].\n\n[^b]: Second.\n[^a]: First.\n",
+            )
+            .unwrap();
+        println!("Generated HTML: {}", html);
+        assert!(html.contains(r##"<a href="#fn1" id="fnref1">[1]</a>"##));
+        assert!(html.contains(r##"<a href="#fn2" id="fnref2">[2]</a>"##));
+        assert!(html.contains(r##"<a href="#fn1" id="fnref1:1">[1]</a>"##));
+        // Definitions are listed in reference order with back-references
+        let first = html.find(r#"<li id="fn1""#).unwrap();
+        let second = html.find(r#"<li id="fn2""#).unwrap();
+        assert!(first < second);
+        assert!(html.contains(r##"First. <a href="#fnref1" class="footnote-backref">"##));
+        assert!(html.contains(r##"<a href="#fnref1:1" class="footnote-backref">"##));
+        assert!(!html.contains("footnotes-heading"));
+    }
+
+    #[test]
+    fn test_render_footnotes_heading() {
+        let renderer = MarkdownRenderer::with_config(MarkdownConfig {
+            footnote_heading: "Notes".to_string(),
+            ..Default::default()
+        });
+        let html = renderer.render("Body[^1].\n\n[^1]: Note.\n").unwrap();
+        assert!(html.contains(r#"<h2 class="footnotes-heading">Notes</h2>"#));
+    }
+
+    #[test]
+    fn test_render_footnote_in_heading() {
+        let renderer = MarkdownRenderer::new();
+        let html = renderer
+            .render("## Setup[^1]\n\nBody.\n\n[^1]: Note.\n")
+            .unwrap();
+        assert!(html.contains(
+            r##"<h2 id="Setup"><a href="#Setup" class="headerlink" title="Setup"></a>Setup<sup class="footnote-ref"><a href="#fn1" id="fnref1">[1]</a></sup></h2>"##
+        ));
+        assert!(html.contains(r#"<li id="fn1""#));
+    }
+
+    #[test]
+    fn test_render_task_list() {
+        let renderer = MarkdownRenderer::new();
+        let html = renderer
+            .render("- [ ] todo\n- [x] done\n\nText\n\n- plain\n")
+            .unwrap();
+        println!("Generated HTML: {}", html);
+        assert!(html.contains(r#"<ul class="contains-task-list">"#));
+        assert!(html.contains(
+            r#"<li class="task-list-item"><input type="checkbox" class="task-list-item-checkbox" disabled> todo</li>"#
+        ));
+        assert!(html.contains(r#"class="task-list-item-checkbox" disabled checked> done"#));
+        // Ordinary lists are left alone
+        assert!(html.contains("<ul>\n<li>plain</li>"));
+    }
+
+    #[test]
+    fn test_render_figures() {
+        let renderer = MarkdownRenderer::with_config(MarkdownConfig {
+            figure: true,
+            ..Default::default()
+        });
+        let html = renderer
+            .render(
+                "![A cat](/cat.png \"Our cat\")\n\n![](/dog.png)\n\nInline ![b](/b.png) image\n",
+            )
+            .unwrap();
+        assert!(html.contains(
+            r#"<figure><img src="/cat.png" alt="A cat" title="Our cat"><figcaption>Our cat</figcaption></figure>"#
+        ));
+        assert!(html.contains(r#"<p><img src="/dog.png" alt="" /></p>"#));
+        assert!(html.contains(r#"<p>Inline <img src="/b.png" alt="b" /> image</p>"#));
+    }
+
+    #[test]
+    fn test_render_code_block_inline_theme() {
+        let renderer = MarkdownRenderer::new().with_highlight(HighlightConfig {
+            output: "inline".to_string(),
+            ..Default::default()
+        });
+        let html = renderer.render("```rust\nfn main() {}\n```").unwrap();
+        assert!(html.contains(r#"<pre class="hl-code language-rust""#));
+        assert!(html.contains("style=\"color:#"));
+    }
+
+    #[test]
+    fn test_render_code_block_classed_theme() {
+        let highlight = HighlightConfig {
+            output: "classed".to_string(),
+            ..Default::default()
+        };
+        let renderer = MarkdownRenderer::new().with_highlight(highlight.clone());
+        let html = renderer.render("```rust\nfn main() {}\n```").unwrap();
+        assert!(html.contains("class=\"hl-"));
+        let css = highlight_stylesheet(&highlight).unwrap();
+        assert!(css.contains(".hl-"));
+        assert!(highlight_stylesheet(&HighlightConfig::default()).is_none());
+        // No stylesheet to link for an unknown theme
+        assert!(highlight_stylesheet(&HighlightConfig {
+            theme: "no-such-theme".to_string(),
+            ..highlight
+        })
+        .is_none());
+    }
+
+    #[test]
+    fn test_code_block_language_escaped() {
+        let html = MarkdownRenderer::new()
+            .render("```x\"><script>\nalert(1)\n```")
+            .unwrap();
+        assert!(!html.contains("<script>"), "{}", html);
+        assert!(html.contains("language-x&quot;&gt;&lt;script&gt;"));
+    }
+
     #[test]
     fn test_split_excerpt() {
         let content = "This is excerpt.\n<!-- more -->\nThis is more content.";
@@ -1,10 +1,16 @@
 //! Content module - handles posts, pages, and content processing
 
+mod data;
+mod encoding;
 mod frontmatter;
+mod ids;
 pub mod loader;
 mod markdown;
 mod post;
 
-pub use frontmatter::FrontMatter;
-pub use markdown::MarkdownRenderer;
+pub use data::load_site_data;
+pub use encoding::read_source;
+pub use frontmatter::{parse_date_string, FrontMatter};
+pub use ids::PostIds;
+pub use markdown::{highlight_stylesheet, MarkdownRenderer};
 pub use post::{Page, Post};
@@ -2,8 +2,10 @@
 
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 /// A blog post
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -62,6 +64,18 @@ pub struct Post {
     /// Slug (URL-friendly name)
     pub slug: String,
 
+    /// Stable unique ID (used for feed entry IDs)
+    pub uuid: String,
+
+    /// Series this post belongs to
+    pub series: Option<String>,
+
+    /// Pin weight; pinned posts (> 0) sort first on index pages
+    pub sticky: i64,
+
+    /// Old URL paths that redirect to this post
+    pub aliases: Vec<String>,
+
     /// Photos for gallery posts
     pub photos: Vec<String>,
 
@@ -71,6 +85,10 @@ pub struct Post {
     /// Custom front-matter fields
     #[serde(flatten)]
     pub extra: HashMap<String, serde_yaml::Value>,
+
+    /// File `content` was moved to by `spill`
+    #[serde(skip)]
+    pub spilled: Option<PathBuf>,
 }
 
 impl Post {
@@ -96,12 +114,80 @@ impl Post {
             published: true,
             lang: None,
             slug,
+            uuid: String::new(),
+            series: None,
+            sticky: 0,
+            aliases: Vec::new(),
             photos: Vec::new(),
             link: None,
             extra: HashMap::new(),
+            spilled: None,
+        }
+    }
+
+    /// Move the rendered content to the file `path` and drop the raw
+    /// markdown, to keep memory low on large sites. `full_content` reads the
+    /// content back. A cover image found in the content is kept in `cover`.
+    pub fn spill(&mut self, path: &Path) -> std::io::Result<()> {
+        if let Some(cover) = self.cover() {
+            self.extra
+                .entry("cover".to_string())
+                .or_insert(serde_yaml::Value::String(cover));
+        }
+        fs::write(path, &self.content)?;
+        self.content = String::new();
+        self.raw = String::new();
+        self.more = None;
+        self.spilled = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Rendered HTML content, read back from disk if it was spilled
+    pub fn full_content(&self) -> std::io::Result<Cow<'_, str>> {
+        match &self.spilled {
+            Some(path) => fs::read_to_string(path).map(Cow::Owned),
+            None => Ok(Cow::Borrowed(&self.content)),
         }
     }
 
+    /// Key shared by translations of the same post: the `translation_key`
+    /// front-matter field, falling back to the slug
+    pub fn translation_key(&self) -> &str {
+        self.extra
+            .get("translation_key")
+            .and_then(|v| v.as_str())
+            .unwrap_or(&self.slug)
+    }
+
+    /// Terms of a custom taxonomy from front matter (a string or a list of strings)
+    pub fn taxonomy_terms(&self, name: &str) -> Vec<String> {
+        match self.extra.get(name) {
+            Some(serde_yaml::Value::String(s)) if !s.trim().is_empty() => vec![s.clone()],
+            Some(serde_yaml::Value::Sequence(seq)) => seq
+                .iter()
+                .filter_map(|v| v.as_str())
+                .filter(|s| !s.trim().is_empty())
+                .map(String::from)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Cover image: the `cover` front-matter field, the first of Hexo's
+    /// `photos`, or the first image in the content
+    pub fn cover(&self) -> Option<String> {
+        if let Some(cover) = self.extra.get("cover").and_then(|v| v.as_str()) {
+            return Some(cover.to_string());
+        }
+        if let Some(photo) = self.taxonomy_terms("photos").into_iter().next() {
+            return Some(photo);
+        }
+        let img = self.content.find("<img")?;
+        let src = img + self.content[img..].find("src=\"")? + 5;
+        let len = self.content[src..].find('"')?;
+        Some(self.content[src..src + len].to_string())
+    }
+
     /// Get the previous post in a list
     pub fn prev<'a>(&self, posts: &'a [Post]) -> Option<&'a Post> {
         let pos = posts.iter().position(|p| p.source == self.source)?;
@@ -162,6 +248,9 @@ pub struct Page {
     /// Page language
     pub lang: Option<String>,
 
+    /// Old URL paths that redirect to this page
+    pub aliases: Vec<String>,
+
     /// Custom front-matter fields
     #[serde(flatten)]
     pub extra: HashMap<String, serde_yaml::Value>,
@@ -183,6 +272,7 @@ impl Page {
             permalink: String::new(),
             comments: true,
             lang: None,
+            aliases: Vec::new(),
             extra: HashMap::new(),
         }
     }
@@ -0,0 +1,139 @@
+//! Concatenate CSS/JS assets into bundles, optionally minifying the CSS
+
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+use crate::config::BundleConfig;
+
+/// Write the configured bundles from files already copied to `public_dir`
+pub fn write_bundles(config: &BundleConfig, public_dir: &Path) -> Result<()> {
+    for (bundle, inputs) in &config.files {
+        let is_js = bundle.ends_with(".js");
+        let mut parts = Vec::new();
+        for input in inputs {
+            let path = public_dir.join(input.trim_start_matches('/'));
+            match fs::read_to_string(&path) {
+                Ok(content) => parts.push(content),
+                Err(e) => tracing::warn!("Bundle {}: skipping {:?}: {}", bundle, path, e),
+            }
+        }
+
+        // A `;` between scripts keeps one file's last statement from running
+        // into the next
+        let mut content = parts.join(if is_js { ";\n" } else { "\n" });
+        // Scripts are only concatenated: telling comments apart from strings
+        // and regexes needs a JS parser
+        if config.minify && !is_js {
+            content = minify_css(&content);
+        }
+
+        let dest = public_dir.join(bundle.trim_start_matches('/'));
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        super::write_if_changed(&dest, content)?;
+        tracing::info!("Bundled {} files into {}", inputs.len(), bundle);
+    }
+    Ok(())
+}
+
+/// Remove comments and whitespace that CSS doesn't need
+fn minify_css(css: &str) -> String {
+    let mut result = String::with_capacity(css.len());
+    let mut chars = css.char_indices().peekable();
+    let mut quote = None;
+    let mut pending_space = false;
+
+    while let Some((i, c)) = chars.next() {
+        if let Some(q) = quote {
+            result.push(c);
+            if c == '\\' {
+                result.extend(chars.next().map(|(_, c)| c));
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '/' if chars.peek().is_some_and(|&(_, c)| c == '*') => {
+                chars.next();
+                let mut prev = ' ';
+                for (_, c) in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            c if c.is_whitespace() => pending_space = true,
+            _ => {
+                let is_punct = |c: char| matches!(c, '{' | '}' | ';' | ':' | ',' | '>');
+                let last = result.chars().last();
+                // A space before `:` is only insignificant in declarations:
+                // in selectors `a :hover` means `a *:hover`
+                let drops_space = is_punct(c) && (c != ':' || in_declaration(&css[i + 1..]));
+                if pending_space && !drops_space && !last.is_some_and(is_punct) && last.is_some() {
+                    result.push(' ');
+                }
+                pending_space = false;
+                if c == '}' && result.ends_with(';') {
+                    result.pop();
+                }
+                if c == '"' || c == '\'' {
+                    quote = Some(c);
+                }
+                result.push(c);
+            }
+        }
+    }
+    result
+}
+
+/// Whether the text after a `:` is a declaration value, which ends at a `;`
+/// or `}`, rather than the rest of a selector, which ends at a `{`
+fn in_declaration(rest: &str) -> bool {
+    rest.find(['{', ';', '}'])
+        .is_some_and(|i| rest.as_bytes()[i] != b'{')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_minify_css() {
+        let css =
+            "/* header */\n.header a:hover,\n.nav > li {\n  color: red;\n  content: \"a  b\";\n}\n";
+        assert_eq!(
+            minify_css(css),
+            ".header a:hover,.nav>li{color:red;content:\"a  b\"}"
+        );
+        assert_eq!(
+            minify_css("a :hover , b ::before {\n  color : red ;\n}\n@media (min-width: 1px) { p { margin : 0 } }"),
+            "a :hover,b ::before{color:red}@media (min-width:1px){p{margin:0}}"
+        );
+    }
+
+    #[test]
+    fn test_write_bundles() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("js")).unwrap();
+        fs::write(dir.path().join("js/a.js"), "var a = 1\n\n").unwrap();
+        fs::write(dir.path().join("js/b.js"), "  var b = 2;\n").unwrap();
+
+        let config = BundleConfig {
+            minify: true,
+            files: BTreeMap::from([(
+                "js/bundle.js".to_string(),
+                vec!["js/a.js".to_string(), "/js/b.js".to_string()],
+            )]),
+        };
+        write_bundles(&config, dir.path()).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("js/bundle.js")).unwrap(),
+            "var a = 1\n\n;\n  var b = 2;\n"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Build manifest: every generated file with its content hash and origin
+//!
+//! Deploy tooling can diff the manifests of two builds to upload only the
+//! files whose hash changed, or check a deployment against one.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::cache::{hash_file, AssetRecord};
+
+/// What produced a file in `public/`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Origin {
+    /// `post`, `page`, `asset`, `index`, `feed`, ...
+    pub kind: &'static str,
+    /// The source file, relative to the site directory, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl Origin {
+    /// A file built from the whole site rather than one source
+    pub fn generated(kind: &'static str) -> Self {
+        Self { kind, source: None }
+    }
+
+    pub fn source(kind: &'static str, source: impl Into<String>) -> Self {
+        Self {
+            kind,
+            source: Some(source.into()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    path: &'a str,
+    /// SHA-256 of the content
+    sha256: String,
+    size: u64,
+    #[serde(flatten)]
+    origin: &'a Origin,
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    version: u32,
+    files: Vec<Entry<'a>>,
+}
+
+/// Origins of copied assets, by output path
+pub fn asset_origins(
+    assets: &BTreeMap<String, AssetRecord>,
+    base_dir: &Path,
+) -> BTreeMap<String, Origin> {
+    assets
+        .iter()
+        .map(|(key, record)| {
+            let source = record
+                .source
+                .strip_prefix(base_dir)
+                .unwrap_or(&record.source);
+            let source = source.to_string_lossy().replace('\\', "/");
+            (key.clone(), Origin::source("asset", source))
+        })
+        .collect()
+}
+
+/// Hash the files in `origins` (paths relative to `public_dir`) and write
+/// the manifest to `path`. Files that no longer exist are left out.
+/// Returns how many files are listed.
+pub fn write_manifest(
+    path: &Path,
+    public_dir: &Path,
+    origins: &BTreeMap<String, Origin>,
+) -> Result<usize> {
+    let files = origins
+        .par_iter()
+        .filter_map(|(key, origin)| {
+            let file = public_dir.join(key);
+            let size = fs::metadata(&file).ok()?.len();
+            Some(hash_file(&file).map(|sha256| Entry {
+                path: key,
+                sha256,
+                size,
+                origin,
+            }))
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let count = files.len();
+    let manifest = Manifest { version: 1, files };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    super::write_if_changed(path, serde_json::to_string_pretty(&manifest)? + "\n")?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let public = dir.path().join("public");
+        fs::create_dir_all(public.join("img")).unwrap();
+        fs::write(public.join("index.html"), "abc").unwrap();
+        fs::write(public.join("img/a.png"), [0u8, 0xff]).unwrap();
+        let origins = BTreeMap::from([
+            ("index.html".to_string(), Origin::generated("index")),
+            (
+                "img/a.png".to_string(),
+                Origin::source("asset", "source/img/a.png"),
+            ),
+            ("gone.html".to_string(), Origin::generated("page")),
+        ]);
+
+        let path = dir.path().join("manifest.json");
+        assert_eq!(write_manifest(&path, &public, &origins).unwrap(), 2);
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let files = manifest["files"].as_array().unwrap();
+        assert_eq!(files[0]["path"], "img/a.png");
+        assert_eq!(files[0]["source"], "source/img/a.png");
+        assert_eq!(files[0]["size"], 2);
+        assert_eq!(files[1]["kind"], "index");
+        assert!(files[1].get("source").is_none());
+        assert_eq!(
+            files[1]["sha256"],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -1,96 +1,548 @@
 //! Generator module - generates static HTML files using built-in Tera templates
 
+mod bundle;
+mod manifest;
+mod output;
+mod pipeline;
+mod redirect;
+
 use anyhow::Result;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
+use rayon::prelude::*;
 use tera::Context;
 use walkdir::WalkDir;
 
-use crate::content::{Page, Post};
-use crate::helpers::toc;
+use crate::cache::{CacheDb, ChangeSet};
+use crate::config::merge_yaml;
+use crate::content::{highlight_stylesheet, load_site_data, Page, Post};
+use crate::helpers::{
+    format_size, is_external, link_rel, reading_time, strip_html, toc, word_count, WordCount,
+};
 use crate::templates::{
-    AboutData, ArchiveYearData, ConfigData, MenuItem, NavPost, PaginationData, PostData, SiteData,
-    TagData, TemplateRenderer, ThemeData,
+    AboutData, ArchiveYearData, ConfigData, LinkData, MenuItem, NavPost, PaginationData, PostData,
+    SeriesData, SeriesPost, SiteData, TagData, TemplateError, TemplateRenderer, TemplateTiming,
+    ThemeData, TranslationData,
 };
-use crate::theme::ThemeLoader;
+use crate::theme::{SassCompiler, ThemeLoader};
 use crate::Hexo;
+pub use manifest::Origin;
+pub(crate) use output::same_content;
+pub use output::{write_if_changed, write_streamed, AssetCopier, CopyStats, IoLimit, OutputLog};
+pub use pipeline::Stage;
+use pipeline::{Build, Prepared};
+use redirect::{parse_redirects_file, redirect_page, redirects_file, Redirect};
 
 /// Static site generator using Tera templates
 pub struct Generator {
     hexo: Hexo,
     renderer: TemplateRenderer,
     theme_loader: ThemeLoader,
+    /// Whether templates read the content of `site.posts`/`site.pages`
+    /// items, which otherwise is left out of every page's context
+    site_post_content: bool,
+    site_page_content: bool,
+    /// Threads rendering posts and pages, `None` for rayon's global pool
+    pool: Option<rayon::ThreadPool>,
+    io: IoLimit,
+    /// Stages `generate` runs, in order
+    stages: Vec<Stage>,
+    stage_timings: Mutex<Vec<(Stage, Duration)>>,
+    /// Files rendered so far, to prune ones earlier builds left behind
+    outputs: OutputLog,
+    /// When the build started reading its sources, stored as the cache's
+    /// `last_build`: files saved during the build are newer than it
+    started: chrono::DateTime<chrono::Utc>,
+    /// css/highlight.css for "classed" highlight output with a known theme
+    highlight_css: Option<String>,
 }
 
 impl Generator {
     /// Create a new generator
     pub fn new(hexo: &Hexo) -> Result<Self> {
-        let renderer = TemplateRenderer::new()?;
-        let theme_loader = ThemeLoader::load(&hexo.theme_dir)?;
+        let mut theme_loader = match hexo.config.builtin_theme() {
+            Some(name) => ThemeLoader::builtin(name)?,
+            None => ThemeLoader::load(&hexo.theme_dir)?,
+        };
+        theme_loader.apply_overrides(&hexo.config.theme_config);
+        let mut renderer = TemplateRenderer::new()?;
+        renderer.configure(&hexo.config);
+        renderer.extend(&hexo.templates);
+        let theme_templates = theme_loader.templates()?;
+        if !theme_templates.is_empty() {
+            tracing::info!("Loaded {} theme templates", theme_templates.len());
+        }
+        renderer.add_templates(theme_templates)?;
+        renderer.set_languages(theme_loader.languages()?, hexo.config.default_language());
+        let site_post_content = renderer.reads_content(&["site.posts", "recent_posts"]);
+        let site_page_content = renderer.reads_content(&["site.pages"]);
+        let concurrency = &hexo.config.concurrency;
+        let pool = match concurrency.jobs {
+            0 => None,
+            jobs => Some(rayon::ThreadPoolBuilder::new().num_threads(jobs).build()?),
+        };
 
         Ok(Self {
             hexo: hexo.clone(),
             renderer,
             theme_loader,
+            site_post_content,
+            site_page_content,
+            pool,
+            io: IoLimit::new(concurrency.io_limit),
+            stages: Stage::ALL.to_vec(),
+            stage_timings: Mutex::default(),
+            outputs: OutputLog::new(&hexo.public_dir),
+            started: chrono::Utc::now(),
+            highlight_css: highlight_stylesheet(&hexo.config.highlight),
         })
     }
 
+    /// Set when the build started, if content was loaded before the
+    /// generator was created
+    pub fn set_started(&mut self, started: chrono::DateTime<chrono::Utc>) {
+        self.started = started;
+    }
+
+    /// Keep generating when a template fails, collecting the errors for
+    /// `take_errors` (used by `theme check`)
+    pub fn collect_errors(&mut self) {
+        self.renderer.collect_errors();
+    }
+
+    /// Template errors collected since `collect_errors`
+    pub fn take_errors(&self) -> Vec<TemplateError> {
+        self.renderer.take_errors()
+    }
+
+    /// The template renderer with the theme's templates loaded
+    pub fn renderer(&self) -> &TemplateRenderer {
+        &self.renderer
+    }
+
+    /// Record per-template render times during generation
+    pub fn enable_profiling(&self) {
+        self.renderer.enable_profiling();
+    }
+
+    /// Render times recorded since profiling was enabled, slowest first
+    pub fn template_timings(&self) -> Vec<(String, TemplateTiming)> {
+        self.renderer.timings()
+    }
+
+    /// Run only `stages`, in the given order, on `generate`
+    pub fn set_stages(&mut self, stages: Vec<Stage>) {
+        self.stages = stages;
+    }
+
+    /// Leave `stage` out of `generate`
+    pub fn skip_stage(&mut self, stage: Stage) {
+        self.stages.retain(|s| *s != stage);
+    }
+
+    /// How long each stage of the last `generate` took, in the order they ran
+    pub fn stage_timings(&self) -> Vec<(Stage, Duration)> {
+        self.stage_timings.lock().unwrap().clone()
+    }
+
     /// Generate the entire site
     pub fn generate(&self, posts: &[Post], pages: &[Page]) -> Result<()> {
         // Ensure public directory exists
         fs::create_dir_all(&self.hexo.public_dir)?;
 
-        // Copy theme assets
-        self.theme_loader.copy_source(&self.hexo.public_dir)?;
+        let mut build = Build::new(posts, pages, CacheDb::load(&self.hexo.base_dir));
+        for &stage in &self.stages {
+            let start = Instant::now();
+            self.run_stage(stage, &mut build)?;
+            let elapsed = start.elapsed();
+            tracing::debug!("Stage {} took {:.2}s", stage, elapsed.as_secs_f64());
+            build.timings.push((stage, elapsed));
+        }
 
-        // Copy source assets (images, etc.)
-        self.copy_source_assets()?;
+        let mut cache = build.cache;
+        // Files from skipped stages are still current, so only prune after
+        // a build that ran every stage
+        let mut outputs = self.outputs.written();
+        if Stage::ALL.iter().all(|stage| self.stages.contains(stage)) {
+            let assets = cache.assets();
+            let removed = self
+                .outputs
+                .prune(cache.outputs(), |key| assets.contains_key(key))?;
+            if removed > 0 {
+                tracing::info!("Removed {} stale files", removed);
+            }
+        } else {
+            outputs.extend(cache.outputs().iter().cloned());
+        }
+        cache.set_outputs(outputs);
+
+        let manifest = &self.hexo.config.manifest;
+        if manifest.enable {
+            let mut origins = manifest::asset_origins(cache.assets(), &self.hexo.base_dir);
+            origins.extend(self.outputs.origins());
+            let path = self.hexo.base_dir.join(&manifest.path);
+            let files =
+                self.in_pool(|| manifest::write_manifest(&path, &self.hexo.public_dir, &origins))?;
+            tracing::info!("Wrote manifest of {} files to {:?}", files, path);
+        }
+
+        cache.set_template_usage(self.renderer.template_usage());
+        cache.set_last_build(self.started);
+        cache.save()?;
+        *self.stage_timings.lock().unwrap() = build.timings;
+
+        Ok(())
+    }
+
+    fn run_stage(&self, stage: Stage, build: &mut Build) -> Result<()> {
+        if stage == Stage::Assets {
+            return self.copy_assets(&mut build.cache);
+        }
+        if stage == Stage::Load {
+            let (posts, site, config, theme) = self.prepare(build.posts, build.pages)?;
+            build.set_prepared(Prepared {
+                posts,
+                site,
+                config,
+                theme,
+            });
+            return Ok(());
+        }
 
+        let pages = build.pages;
+        let data = build.prepared(stage)?;
+        let (posts, site, config, theme) = (&data.posts, &data.site, &data.config, &data.theme);
+        match stage {
+            Stage::Lists => {
+                for (prefix, lang_posts) in self.language_trees(posts) {
+                    self.generate_index_pages(&lang_posts, &prefix, site, config, theme)?;
+                }
+                self.generate_list_pages(posts, site, config, theme)?;
+            }
+            Stage::Feeds => {
+                for (prefix, lang_posts) in self.language_trees(posts) {
+                    self.generate_atom_feed(&lang_posts, &prefix)?;
+                }
+            }
+            Stage::Posts => {
+                self.in_pool(|| self.generate_post_pages(posts, site, config, theme, None))?;
+            }
+            Stage::Pages => {
+                self.in_pool(|| self.generate_page_pages(pages, site, config, theme))?;
+            }
+            Stage::Extras => {
+                self.generate_search_index(posts)?;
+                // Redirect pages for old URLs
+                let partial = !Stage::ALL.iter().all(|stage| self.stages.contains(stage));
+                self.generate_redirects(posts, pages, partial)?;
+                if let Some(css) = &self.highlight_css {
+                    let css_path = self.hexo.public_dir.join("css/highlight.css");
+                    if let Some(parent) = css_path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    self.write_output(&css_path, Origin::generated("highlight"), css)?;
+                }
+            }
+            Stage::Assets | Stage::Load => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Copy theme assets, then source assets (images, etc.) over them,
+    /// skipping files that haven't changed since the last build, and write
+    /// bundles of the copied assets
+    fn copy_assets(&self, cache: &mut CacheDb) -> Result<()> {
+        let sass = SassCompiler::new(&self.hexo.config.sass, &self.hexo.base_dir);
+        let mut assets = AssetCopier::new(&self.hexo.public_dir)
+            .with_io_limit(self.hexo.config.concurrency.io_limit);
+        self.theme_loader.copy_source(&sass, &mut assets)?;
+        self.copy_source_assets(&sass, &mut assets)?;
+        // Compiled stylesheets and embedded theme assets
+        for key in assets.claimed() {
+            let path = self.hexo.public_dir.join(key);
+            self.outputs.record(&path, Origin::generated("asset"));
+        }
+        let stats = self.in_pool(|| assets.finish(cache))?;
+        // Saved now so a failed render doesn't recopy everything next time
+        cache.save()?;
+        tracing::info!(
+            "Copied {} assets, {} ({} unchanged, {} removed)",
+            stats.copied,
+            format_size(stats.bytes),
+            stats.skipped,
+            stats.removed
+        );
+
+        bundle::write_bundles(&self.hexo.config.bundle, &self.hexo.public_dir)?;
+        for bundle in self.hexo.config.bundle.files.keys() {
+            let path = self.hexo.public_dir.join(bundle.trim_start_matches('/'));
+            self.outputs.record(&path, Origin::generated("bundle"));
+        }
+        Ok(())
+    }
+
+    /// Re-render only the pages that use the changed templates (or templates
+    /// that extend/include them), reusing already loaded posts and pages.
+    /// Feeds, search index and assets don't depend on templates and are kept.
+    pub fn generate_for_templates(
+        &self,
+        posts: &[Post],
+        pages: &[Page],
+        changed: &[String],
+    ) -> Result<()> {
+        // Partials pages were seen rendering by earlier builds count too
+        let mut cache = CacheDb::load(&self.hexo.base_dir);
+        self.renderer
+            .add_template_usage(cache.template_usage().clone());
+        let affected = self.renderer.dependents(changed);
+        let (sorted_posts, site_data, config_data, theme_data) = self.prepare(posts, pages)?;
+        let uses = |template: &str| affected.contains(template);
+        let mut rendered = Vec::new();
+
+        if uses("index.html") {
+            for (prefix, lang_posts) in self.language_trees(&sorted_posts) {
+                self.generate_index_pages(
+                    &lang_posts,
+                    &prefix,
+                    &site_data,
+                    &config_data,
+                    &theme_data,
+                )?;
+            }
+            rendered.push("index pages");
+        }
+        if sorted_posts.iter().any(|p| uses(&self.post_template(p))) {
+            self.in_pool(|| {
+                self.generate_post_pages(&sorted_posts, &site_data, &config_data, &theme_data, None)
+            })?;
+            rendered.push("posts");
+        }
+        let affected_pages: Vec<Page> = pages
+            .iter()
+            .filter(|p| uses(&self.page_template(p)))
+            .cloned()
+            .collect();
+        if !affected_pages.is_empty() {
+            self.in_pool(|| {
+                self.generate_page_pages(&affected_pages, &site_data, &config_data, &theme_data)
+            })?;
+            rendered.push("pages");
+        }
+        let trees = self.language_trees(&sorted_posts);
+        if uses("archive.html") {
+            for (prefix, lang_posts) in &trees {
+                self.generate_archive_page(
+                    lang_posts,
+                    prefix,
+                    &site_data,
+                    &config_data,
+                    &theme_data,
+                )?;
+            }
+            rendered.push("archive");
+        }
+        if uses("tags.html") || uses("tag_single.html") {
+            for (prefix, lang_posts) in &trees {
+                self.generate_tag_pages(lang_posts, prefix, &site_data, &config_data, &theme_data)?;
+                self.generate_series_pages(
+                    lang_posts,
+                    prefix,
+                    &site_data,
+                    &config_data,
+                    &theme_data,
+                )?;
+                self.generate_taxonomy_pages(
+                    lang_posts,
+                    prefix,
+                    &site_data,
+                    &config_data,
+                    &theme_data,
+                )?;
+            }
+            rendered.push("tags");
+        }
+        cache.set_template_usage(self.renderer.template_usage());
+        cache.save()?;
+
+        tracing::info!(
+            "Re-rendered {}",
+            if rendered.is_empty() {
+                "nothing".to_string()
+            } else {
+                rendered.join(", ")
+            }
+        );
+        Ok(())
+    }
+
+    /// Re-render the pages of changed posts and pages, plus the lists, feeds
+    /// and indexes that show posts when a post changed. Everything else is
+    /// re-rendered too if `changes` reaches other pages. Assets are kept.
+    pub fn generate_for_changes(
+        &self,
+        posts: &[Post],
+        pages: &[Page],
+        changes: &ChangeSet,
+    ) -> Result<()> {
+        for path in &changes.removed {
+            let file = self
+                .hexo
+                .public_dir
+                .join(path.trim_matches('/'))
+                .join("index.html");
+            if file.is_file() {
+                fs::remove_file(&file)?;
+            }
+        }
+
+        let (sorted_posts, site_data, config_data, theme_data) = self.prepare(posts, pages)?;
+        // Templates reading site.posts/site.pages content show every change
+        let all = changes.all
+            || (self.site_post_content && !changes.posts.is_empty())
+            || (self.site_page_content && !changes.pages.is_empty());
+        let mut rendered = Vec::new();
+
+        if all || !changes.posts.is_empty() {
+            let only = (!all).then_some(&changes.posts);
+            self.in_pool(|| {
+                self.generate_post_pages(&sorted_posts, &site_data, &config_data, &theme_data, only)
+            })?;
+            rendered.push(if all {
+                "posts".to_string()
+            } else {
+                format!("{} posts", changes.posts.len())
+            });
+        }
+        let changed_pages: Vec<Page> = pages
+            .iter()
+            .filter(|p| all || changes.pages.contains(&p.source))
+            .cloned()
+            .collect();
+        if !changed_pages.is_empty() {
+            self.in_pool(|| {
+                self.generate_page_pages(&changed_pages, &site_data, &config_data, &theme_data)
+            })?;
+            rendered.push(format!("{} pages", changed_pages.len()));
+        }
+        if all || !changes.posts.is_empty() {
+            let mut feeds = 0;
+            for (prefix, lang_posts) in self.language_trees(&sorted_posts) {
+                self.generate_index_pages(
+                    &lang_posts,
+                    &prefix,
+                    &site_data,
+                    &config_data,
+                    &theme_data,
+                )?;
+                // Edits to posts too old to be in the feed don't change it
+                let entries = self.feed_entries(&lang_posts);
+                if all || entries.iter().any(|p| changes.posts.contains(&p.source)) {
+                    self.generate_atom_feed(&lang_posts, &prefix)?;
+                    feeds += 1;
+                }
+            }
+            self.generate_list_pages(&sorted_posts, &site_data, &config_data, &theme_data)?;
+            self.generate_search_index(&sorted_posts)?;
+            rendered.push(format!("lists, {} feeds and search index", feeds));
+        }
+        // Cheap, and aliases can change with any post or page
+        self.generate_redirects(&sorted_posts, pages, true)?;
+
+        tracing::info!(
+            "Re-rendered {}",
+            if rendered.is_empty() {
+                "nothing".to_string()
+            } else {
+                rendered.join(", ")
+            }
+        );
+        Ok(())
+    }
+
+    /// Sort posts and build the data shared by every template
+    fn prepare(
+        &self,
+        posts: &[Post],
+        pages: &[Page],
+    ) -> Result<(Vec<Post>, SiteData, ConfigData, ThemeData)> {
         // Sort posts by date (newest first)
         let mut sorted_posts: Vec<_> = posts.to_vec();
         sorted_posts.sort_by(|a, b| b.date.cmp(&a.date));
 
-        // Build site data
-        let site_data = self.build_site_data(&sorted_posts, pages);
-
-        // Build config data
-        let config_data = self.build_config_data();
+        // Load data files (source/_data)
+        let data = load_site_data(&self.hexo.source_dir)?;
 
         // Build theme data
-        let theme_data = self.build_theme_data();
+        let theme_data = self.build_theme_data(&data);
 
-        // Generate index pages (with pagination)
-        self.generate_index_pages(&sorted_posts, &site_data, &config_data, &theme_data)?;
+        // Build site data
+        let site_data = self.build_site_data(&sorted_posts, pages, data)?;
 
-        // Generate post pages
-        self.generate_post_pages(&sorted_posts, &site_data, &config_data, &theme_data)?;
+        // Build config data
+        let config_data = self.build_config_data();
 
-        // Generate standalone pages
-        self.generate_page_pages(pages, &site_data, &config_data, &theme_data)?;
+        self.renderer.set_build_time(self.build_time(&sorted_posts));
 
-        // Generate archive page
-        self.generate_archive_page(&sorted_posts, &site_data, &config_data, &theme_data)?;
+        self.renderer
+            .start_build(self.create_base_context(&site_data, &config_data, &theme_data));
 
-        // Generate tag pages
-        self.generate_tag_pages(&sorted_posts, &site_data, &config_data, &theme_data)?;
+        Ok((sorted_posts, site_data, config_data, theme_data))
+    }
 
-        // Generate RSS feed
-        self.generate_atom_feed(&sorted_posts)?;
+    /// "Now" for this build: the clock, unless the build is deterministic
+    fn build_time(&self, posts: &[Post]) -> chrono::DateTime<chrono::Utc> {
+        if !self.hexo.config.deterministic {
+            return chrono::Utc::now();
+        }
+        let epoch = std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|secs| secs.trim().parse().ok())
+            .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0));
+        // Not `updated`, which defaults to the file's modification time
+        epoch
+            .or_else(|| posts.iter().map(|p| p.date.to_utc()).max())
+            .unwrap_or_default()
+    }
 
-        // Generate search index
-        self.generate_search_index(&sorted_posts)?;
+    /// Template for a post: the theme's layout/<layout>.html (e.g. post.html)
+    /// if there is one, otherwise page.html
+    fn post_template(&self, post: &Post) -> String {
+        let layout_template = format!("{}.html", post.layout);
+        if post.layout != "layout" && self.renderer.has_template(&layout_template) {
+            layout_template
+        } else {
+            "page.html".to_string()
+        }
+    }
 
-        Ok(())
+    /// Template for a standalone page, picked by its layout
+    fn page_template(&self, page: &Page) -> String {
+        let layout_template = format!("{}.html", page.layout);
+        match page.layout.as_str() {
+            "about" | "links" | "project" | "search" | "home" | "tags" => layout_template,
+            // Custom layouts provided by the theme (layout/<name>.html)
+            "layout" | "index" => "page.html".to_string(),
+            _ if self.renderer.has_template(&layout_template) => layout_template,
+            _ => "page.html".to_string(),
+        }
     }
 
     /// Build site data for templates
-    fn build_site_data(&self, posts: &[Post], pages: &[Page]) -> SiteData {
-        let mut tags: HashMap<String, usize> = HashMap::new();
-        let mut categories: HashMap<String, usize> = HashMap::new();
+    fn build_site_data(
+        &self,
+        posts: &[Post],
+        pages: &[Page],
+        data: BTreeMap<String, serde_json::Value>,
+    ) -> Result<SiteData> {
+        let mut tags: BTreeMap<String, usize> = BTreeMap::new();
+        let mut categories: BTreeMap<String, usize> = BTreeMap::new();
         let mut total_word_count = 0;
 
-        let post_data: Vec<PostData> = posts
+        let post_data = posts
             .iter()
             .map(|p| {
                 for tag in &p.tags {
@@ -100,22 +552,41 @@ impl Generator {
                     *categories.entry(cat.clone()).or_insert(0) += 1;
                 }
 
-                let word_count = count_words(&p.content);
-                total_word_count += word_count;
+                let content = p.full_content()?;
+                let count = WordCount::of_html(&content);
+                total_word_count += count.total();
 
-                PostData {
+                Ok(PostData {
+                    uuid: p.uuid.clone(),
                     title: p.title.clone(),
                     date: p.date.format("%Y-%m-%d").to_string(),
                     path: format!("/{}", p.path.trim_start_matches('/')),
                     permalink: p.permalink.clone(),
                     tags: p.tags.clone(),
                     categories: p.categories.clone(),
-                    content: p.content.clone(),
+                    content: if self.site_post_content {
+                        content.into_owned()
+                    } else {
+                        String::new()
+                    },
                     excerpt: p.excerpt.clone(),
-                    word_count,
-                }
+                    word_count: count.total(),
+                    reading_time: count.reading_minutes(),
+                    is_sticky: p.sticky > 0,
+                    cover: p.cover(),
+                })
             })
-            .collect();
+            .collect::<Result<Vec<PostData>>>()?;
+
+        let mut taxonomies: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+        for name in self.hexo.config.taxonomies.keys() {
+            let counts = taxonomies.entry(name.clone()).or_default();
+            for post in posts {
+                for term in post.taxonomy_terms(name) {
+                    *counts.entry(term).or_insert(0) += 1;
+                }
+            }
+        }
 
         let page_data = pages
             .iter()
@@ -124,18 +595,24 @@ impl Generator {
                 date: p.date.format("%Y-%m-%d").to_string(),
                 path: format!("/{}", p.path.trim_start_matches('/')),
                 permalink: p.permalink.clone(),
-                content: p.content.clone(),
+                content: if self.site_page_content {
+                    p.content.clone()
+                } else {
+                    String::new()
+                },
                 layout: p.layout.clone(),
             })
             .collect();
 
-        SiteData {
+        Ok(SiteData {
             posts: post_data,
             pages: page_data,
             tags,
             categories,
             word_count: total_word_count,
-        }
+            taxonomies,
+            data,
+        })
     }
 
     /// Build config data for templates
@@ -166,11 +643,12 @@ impl Generator {
                 .as_ref()
                 .map(|k| k.join(", "))
                 .unwrap_or_default(),
+            highlight_css: self.highlight_css.is_some(),
         }
     }
 
     /// Build theme data for templates
-    fn build_theme_data(&self) -> ThemeData {
+    fn build_theme_data(&self, data: &BTreeMap<String, serde_json::Value>) -> ThemeData {
         let theme_config = self.theme_loader.config();
 
         // Parse menu items
@@ -228,6 +706,16 @@ impl Generator {
                 twitter_username: String::new(),
             });
 
+        // Blogroll links from the theme config and source/_data/links.yml
+        let links_rel = theme_config
+            .get("links_rel")
+            .and_then(|v| v.as_str())
+            .unwrap_or("nofollow");
+        let mut links = parse_links(theme_config.get("links"), links_rel);
+        if let Some(data_links) = data.get("links").and_then(|v| serde_yaml::to_value(v).ok()) {
+            links.extend(parse_links(Some(&data_links), links_rel));
+        }
+
         ThemeData {
             description: theme_config
                 .get("description")
@@ -268,6 +756,7 @@ impl Generator {
                 .and_then(|v| v.as_str())
                 .unwrap_or("")
                 .to_string(),
+            links,
         }
     }
 
@@ -283,57 +772,112 @@ impl Generator {
         context.insert("config", config_data);
         context.insert("theme", theme_data);
         // Always use Beijing time (UTC+8) for "最近更新"
-        let beijing_now =
-            chrono::Utc::now().with_timezone(&chrono::FixedOffset::east_opt(8 * 3600).unwrap());
+        let beijing_now = self
+            .renderer
+            .build_time()
+            .with_timezone(&chrono::FixedOffset::east_opt(8 * 3600).unwrap());
         context.insert("current_year", &beijing_now.format("%Y").to_string());
         context.insert("now_formatted", &format_datetime_chinese(&beijing_now));
         context
     }
 
-    /// Generate index pages with pagination
+    /// Posts grouped into per-language output trees as (URL prefix, posts).
+    /// Single-language sites have one tree at the root with every post.
+    fn language_trees(&self, posts: &[Post]) -> Vec<(String, Vec<Post>)> {
+        let config = &self.hexo.config;
+        if config.languages.is_empty() {
+            return vec![(String::new(), posts.to_vec())];
+        }
+        config
+            .languages
+            .iter()
+            .map(|lang| {
+                let lang_posts = posts
+                    .iter()
+                    .filter(|p| p.lang.as_deref() == Some(lang.as_str()))
+                    .cloned()
+                    .collect();
+                (config.language_prefix(lang), lang_posts)
+            })
+            .collect()
+    }
+
+    /// Other-language versions of each post, keyed by translation key
+    fn build_translations(&self, posts: &[Post]) -> HashMap<String, Vec<TranslationData>> {
+        let mut translations: HashMap<String, Vec<TranslationData>> = HashMap::new();
+        if self.hexo.config.languages.is_empty() {
+            return translations;
+        }
+        for post in posts {
+            translations
+                .entry(post.translation_key().to_string())
+                .or_default()
+                .push(TranslationData {
+                    lang: post.lang.clone().unwrap_or_default(),
+                    title: post.title.clone(),
+                    path: format!("/{}", post.path.trim_start_matches('/')),
+                });
+        }
+        translations
+    }
+
+    /// Generate index pages with pagination under a language prefix ("" or "zh/")
     fn generate_index_pages(
         &self,
         posts: &[Post],
+        prefix: &str,
         site_data: &SiteData,
         config_data: &ConfigData,
         theme_data: &ThemeData,
     ) -> Result<()> {
+        // Pinned posts come first (highest weight first), the rest stay newest first
+        let mut posts = posts.to_vec();
+        posts.sort_by_key(|p| std::cmp::Reverse((p.sticky.max(0), p.date)));
+        let posts = posts.as_slice();
+
         let per_page = self.hexo.config.per_page;
         let total_pages = posts.len().div_ceil(per_page);
 
         for page_num in 1..=total_pages {
             let start = (page_num - 1) * per_page;
             let end = (start + per_page).min(posts.len());
-            let page_posts: Vec<PostData> = posts[start..end]
+            let page_posts = posts[start..end]
                 .iter()
-                .map(|p| PostData {
-                    title: p.title.clone(),
-                    date: p.date.format("%Y-%m-%d").to_string(),
-                    path: format!("/{}", p.path.trim_start_matches('/')),
-                    permalink: p.permalink.clone(),
-                    tags: p.tags.clone(),
-                    categories: p.categories.clone(),
-                    content: p.content.clone(),
-                    excerpt: p.excerpt.clone(),
-                    word_count: count_words(&p.content),
+                .map(|p| {
+                    let content = p.full_content()?.into_owned();
+                    Ok(PostData {
+                        uuid: p.uuid.clone(),
+                        title: p.title.clone(),
+                        date: p.date.format("%Y-%m-%d").to_string(),
+                        path: format!("/{}", p.path.trim_start_matches('/')),
+                        permalink: p.permalink.clone(),
+                        tags: p.tags.clone(),
+                        categories: p.categories.clone(),
+                        word_count: word_count(&content),
+                        reading_time: reading_time(&content),
+                        content,
+                        excerpt: p.excerpt.clone(),
+                        is_sticky: p.sticky > 0,
+                        cover: p.cover(),
+                    })
                 })
-                .collect();
+                .collect::<Result<Vec<PostData>>>()?;
 
             let pagination = PaginationData {
                 per_page,
                 total: total_pages,
                 current: page_num,
                 current_url: if page_num == 1 {
-                    "/".to_string()
+                    format!("/{}", prefix)
                 } else {
-                    format!("/page/{}/", page_num)
+                    format!("/{}page/{}/", prefix, page_num)
                 },
                 prev: page_num.saturating_sub(1),
                 prev_link: if page_num > 1 {
                     if page_num == 2 {
-                        "/".to_string()
+                        format!("/{}", prefix)
                     } else {
-                        format!("/page/{}/", page_num - 1)
+                        format!("/{}page/{}/", prefix, page_num - 1)
                     }
                 } else {
                     String::new()
@@ -344,7 +888,7 @@ impl Generator {
                     0
                 },
                 next_link: if page_num < total_pages {
-                    format!("/page/{}/", page_num + 1)
+                    format!("/{}page/{}/", prefix, page_num + 1)
                 } else {
                     String::new()
                 },
@@ -359,17 +903,17 @@ impl Generator {
             let html = self.renderer.render("index.html", &context)?;
 
             let output_path = if page_num == 1 {
-                self.hexo.public_dir.join("index.html")
+                self.hexo.public_dir.join(prefix).join("index.html")
             } else {
                 self.hexo
                     .public_dir
-                    .join(format!("page/{}/index.html", page_num))
+                    .join(format!("{}page/{}/index.html", prefix, page_num))
             };
 
             if let Some(parent) = output_path.parent() {
                 fs::create_dir_all(parent)?;
             }
-            fs::write(&output_path, html)?;
+            self.write_output(&output_path, Origin::generated("index"), html)?;
             tracing::debug!("Generated: {:?}", output_path);
         }
 
@@ -383,47 +927,120 @@ impl Generator {
         site_data: &SiteData,
         config_data: &ConfigData,
         theme_data: &ThemeData,
+        only: Option<&BTreeSet<String>>,
     ) -> Result<()> {
-        let all_posts: Vec<_> = posts.to_vec();
-
-        for (i, post) in posts.iter().enumerate() {
-            // Compute prev/next navigation
-            let prev_post = if i + 1 < all_posts.len() {
-                Some(NavPost {
-                    title: all_posts[i + 1].title.clone(),
-                    path: format!("/{}", all_posts[i + 1].path.trim_start_matches('/')),
-                })
-            } else {
-                None
-            };
+        let all_posts = posts;
+        let series = group_series(posts);
+        let translations = self.build_translations(posts);
+
+        // Posts render independently, so spread them over a thread pool
+        posts.par_iter().enumerate().try_for_each(|(i, post)| {
+            if only.is_some_and(|only| !only.contains(&post.source)) {
+                return Ok(());
+            }
 
-            let next_post = if i > 0 {
-                Some(NavPost {
-                    title: all_posts[i - 1].title.clone(),
-                    path: format!("/{}", all_posts[i - 1].path.trim_start_matches('/')),
-                })
-            } else {
-                None
+            // Compute prev/next navigation among posts in the same language
+            let nav = |other: &Post| NavPost {
+                title: other.title.clone(),
+                path: format!("/{}", other.path.trim_start_matches('/')),
             };
+            let prev_post = all_posts[i + 1..]
+                .iter()
+                .find(|p| p.lang == post.lang)
+                .map(nav);
+            let next_post = all_posts[..i]
+                .iter()
+                .rev()
+                .find(|p| p.lang == post.lang)
+                .map(nav);
+
+            let content = post.full_content()?;
 
             // Generate table of contents
-            let toc_html = toc(&post.content, 3);
+            let toc_html = toc(&content, 3);
             // Check if TOC has actual content (not just empty <ol class="toc"></ol>)
-            let has_toc = toc_html.contains("toc-item");
+            // and the post hasn't opted out with `toc: false`
+            let has_toc = toc_html.contains("toc-item")
+                && post.extra.get("toc").and_then(|v| v.as_bool()) != Some(false);
 
             let mut context = self.create_base_context(site_data, config_data, theme_data);
             context.insert("page_title", &post.title);
             context.insert("page_date", &post.date.format("%Y-%m-%d").to_string());
-            context.insert("page_content", &post.content);
+            context.insert("page_content", &content);
             context.insert("page_tags", &post.tags);
             context.insert("page_categories", &post.categories);
             context.insert("page_banner", &"");
             context.insert("page_mathjax", &false);
             context.insert("current_path", &post.path);
             // Only show catalog if theme enables it AND there's actual TOC content
-            context.insert("show_catalog", &(theme_data.catalog && has_toc));
+            let catalog = post
+                .extra
+                .get("theme")
+                .and_then(|t| t.get("catalog"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(theme_data.catalog);
+            context.insert("show_catalog", &(catalog && has_toc));
             context.insert("is_special_page", &false);
             context.insert("toc", &toc_html);
+            context.insert("page_comments", &post.comments);
+            override_theme(&mut context, theme_data, &post.extra);
+            let count = WordCount::of_html(&content);
+            context.insert("page_word_count", &count.total());
+            context.insert("page_reading_time", &count.reading_minutes());
+            context.insert(
+                "page",
+                &page_vars(
+                    serde_json::json!({
+                        "title": post.title,
+                        "date": post.date.format("%Y-%m-%d").to_string(),
+                        "path": format!("/{}", post.path.trim_start_matches('/')),
+                        "permalink": post.permalink,
+                        "tags": post.tags,
+                        "categories": post.categories,
+                        "layout": post.layout,
+                        "lang": post.lang,
+                        "date_iso": post.date.to_rfc3339(),
+                        "updated": post.updated.map(|d| d.to_rfc3339()),
+                        "description": post_description(post, &content),
+                        "cover": post.cover(),
+                        "word_count": count.total(),
+                        "reading_time": count.reading_minutes(),
+                    }),
+                    &post.extra,
+                ),
+            );
+
+            if let Some(name) = &post.series {
+                if let Some(series_posts) = series.get(name) {
+                    // Each language has its own run of the series
+                    let series_posts: Vec<&Post> = series_posts
+                        .iter()
+                        .copied()
+                        .filter(|p| p.lang == post.lang)
+                        .collect();
+                    let prefix = self
+                        .hexo
+                        .config
+                        .language_prefix(post.lang.as_deref().unwrap_or_default());
+                    context.insert(
+                        "page_series",
+                        &self.build_series_data(name, &series_posts, Some(post), &prefix),
+                    );
+                }
+            }
+
+            if let Some(lang) = &post.lang {
+                context.insert("page_lang", lang);
+            }
+            if let Some(versions) = translations.get(post.translation_key()) {
+                let others: Vec<&TranslationData> = versions
+                    .iter()
+                    .filter(|t| t.lang != post.lang.clone().unwrap_or_default())
+                    .collect();
+                if !others.is_empty() {
+                    context.insert("page_translations", &others);
+                }
+            }
 
             if let Some(ref prev) = prev_post {
                 context.insert("prev_post", prev);
@@ -432,7 +1049,7 @@ impl Generator {
                 context.insert("next_post", next);
             }
 
-            let html = self.renderer.render("page.html", &context)?;
+            let html = self.renderer.render(&self.post_template(post), &context)?;
 
             // Strip leading slash from path to avoid creating absolute paths
             let clean_path = post.path.trim_start_matches('/');
@@ -441,12 +1058,18 @@ impl Generator {
                 fs::create_dir_all(parent)
                     .map_err(|e| anyhow::anyhow!("Failed to create dir {:?}: {}", parent, e))?;
             }
-            fs::write(&output_path, &html)
+            self.io
+                .run(|| {
+                    self.write_output(
+                        &output_path,
+                        self.source_origin("post", &post.source),
+                        &html,
+                    )
+                })
                 .map_err(|e| anyhow::anyhow!("Failed to write {:?}: {}", output_path, e))?;
             tracing::debug!("Generated post: {:?}", output_path);
-        }
-
-        Ok(())
+            Ok(())
+        })
     }
 
     /// Generate standalone pages
@@ -457,16 +1080,8 @@ impl Generator {
         config_data: &ConfigData,
         theme_data: &ThemeData,
     ) -> Result<()> {
-        for page in pages {
-            let template_name = match page.layout.as_str() {
-                "about" => "about.html",
-                "links" => "links.html",
-                "project" => "project.html",
-                "search" => "search.html",
-                "home" => "home.html",
-                "tags" => "tags.html",
-                _ => "page.html",
-            };
+        pages.par_iter().try_for_each(|page| {
+            let template_name = self.page_template(page);
 
             let mut context = self.create_base_context(site_data, config_data, theme_data);
             context.insert("page_title", &page.title);
@@ -478,6 +1093,22 @@ impl Generator {
             context.insert("current_path", &page.path);
             context.insert("show_catalog", &false);
             context.insert("is_special_page", &true);
+            context.insert("page_comments", &page.comments);
+            override_theme(&mut context, theme_data, &page.extra);
+            context.insert(
+                "page",
+                &page_vars(
+                    serde_json::json!({
+                        "title": page.title,
+                        "date": page.date.format("%Y-%m-%d").to_string(),
+                        "path": format!("/{}", page.path.trim_start_matches('/')),
+                        "permalink": page.permalink,
+                        "layout": page.layout,
+                        "lang": page.lang,
+                    }),
+                    &page.extra,
+                ),
+            );
 
             // Special handling for tags page - provide all_tags data
             if page.layout == "tags" {
@@ -485,7 +1116,7 @@ impl Generator {
                 context.insert("all_tags", &all_tags);
             }
 
-            let html = self.renderer.render(template_name, &context)?;
+            let html = self.renderer.render(&template_name, &context)?;
 
             // Strip leading slash from path to avoid creating absolute paths
             let clean_path = page.path.trim_start_matches('/');
@@ -493,11 +1124,37 @@ impl Generator {
             if let Some(parent) = output_path.parent() {
                 fs::create_dir_all(parent)?;
             }
-            fs::write(&output_path, html)?;
+            self.io.run(|| {
+                self.write_output(&output_path, self.source_origin("page", &page.source), html)
+            })?;
             tracing::debug!("Generated page: {:?}", output_path);
-        }
+            Ok(())
+        })
+    }
 
-        Ok(())
+    /// Write a rendered file, recording it as this build's output
+    fn write_output(
+        &self,
+        path: &Path,
+        origin: Origin,
+        contents: impl AsRef<[u8]>,
+    ) -> std::io::Result<bool> {
+        self.outputs.record(path, origin);
+        write_if_changed(path, contents)
+    }
+
+    /// Origin of a file rendered from `source` (relative to `source/`)
+    fn source_origin(&self, kind: &'static str, source: &str) -> Origin {
+        Origin::source(kind, format!("{}/{}", self.hexo.config.source_dir, source))
+    }
+
+    /// Run `op` in the configured thread pool, so its parallel iterators use
+    /// that many threads
+    fn in_pool<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        match &self.pool {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
     }
 
     /// Build all tags data for the tags listing page
@@ -512,6 +1169,7 @@ impl Generator {
                     continue;
                 }
                 tags_map.entry(tag.clone()).or_default().push(PostData {
+                    uuid: post.uuid.clone(),
                     title: post.title.clone(),
                     date: post.date.clone(),
                     path: post.path.clone(),
@@ -521,6 +1179,9 @@ impl Generator {
                     content: String::new(), // Don't need content for listing
                     excerpt: None,
                     word_count: 0,
+                    reading_time: 0,
+                    is_sticky: post.is_sticky,
+                    cover: post.cover.clone(),
                 });
             }
         }
@@ -537,10 +1198,28 @@ impl Generator {
         all_tags
     }
 
-    /// Generate archive page
+    /// Archive, tag, series and taxonomy pages of each language tree
+    fn generate_list_pages(
+        &self,
+        posts: &[Post],
+        site_data: &SiteData,
+        config_data: &ConfigData,
+        theme_data: &ThemeData,
+    ) -> Result<()> {
+        for (prefix, lang_posts) in self.language_trees(posts) {
+            self.generate_archive_page(&lang_posts, &prefix, site_data, config_data, theme_data)?;
+            self.generate_tag_pages(&lang_posts, &prefix, site_data, config_data, theme_data)?;
+            self.generate_series_pages(&lang_posts, &prefix, site_data, config_data, theme_data)?;
+            self.generate_taxonomy_pages(&lang_posts, &prefix, site_data, config_data, theme_data)?;
+        }
+        Ok(())
+    }
+
+    /// Generate the archive page under a language prefix ("" or "zh/")
     fn generate_archive_page(
         &self,
         posts: &[Post],
+        prefix: &str,
         site_data: &SiteData,
         config_data: &ConfigData,
         theme_data: &ThemeData,
@@ -550,17 +1229,7 @@ impl Generator {
 
         for post in posts {
             let year = post.date.year();
-            years_map.entry(year).or_default().push(PostData {
-                title: post.title.clone(),
-                date: post.date.format("%Y-%m-%d").to_string(),
-                path: format!("/{}", post.path.trim_start_matches('/')),
-                permalink: post.permalink.clone(),
-                tags: post.tags.clone(),
-                categories: post.categories.clone(),
-                content: String::new(), // Don't need full content for archive
-                excerpt: None,
-                word_count: 0,
-            });
+            years_map.entry(year).or_default().push(post_summary(post));
         }
 
         // Convert to sorted vector (newest first)
@@ -572,7 +1241,7 @@ impl Generator {
 
         let mut context = self.create_base_context(site_data, config_data, theme_data);
         context.insert("archive_years", &archive_years);
-        context.insert("current_path", "archives/");
+        context.insert("current_path", &format!("{}archives/", prefix));
         context.insert("is_home", &false);
 
         let html = self.renderer.render("archive.html", &context)?;
@@ -580,21 +1249,23 @@ impl Generator {
         let output_path = self
             .hexo
             .public_dir
+            .join(prefix)
             .join(&self.hexo.config.archive_dir)
             .join("index.html");
         if let Some(parent) = output_path.parent() {
             fs::create_dir_all(parent)?;
         }
-        fs::write(&output_path, html)?;
+        self.write_output(&output_path, Origin::generated("archive"), html)?;
         tracing::info!("Generated archive page");
 
         Ok(())
     }
 
-    /// Generate tag pages
+    /// Generate tag pages under a language prefix
     fn generate_tag_pages(
         &self,
         posts: &[Post],
+        prefix: &str,
         site_data: &SiteData,
         config_data: &ConfigData,
         theme_data: &ThemeData,
@@ -608,17 +1279,10 @@ impl Generator {
                 if tag.trim().is_empty() {
                     continue;
                 }
-                tags_map.entry(tag.clone()).or_default().push(PostData {
-                    title: post.title.clone(),
-                    date: post.date.format("%Y-%m-%d").to_string(),
-                    path: format!("/{}", post.path.trim_start_matches('/')),
-                    permalink: post.permalink.clone(),
-                    tags: post.tags.clone(),
-                    categories: post.categories.clone(),
-                    content: String::new(),
-                    excerpt: None,
-                    word_count: 0,
-                });
+                tags_map
+                    .entry(tag.clone())
+                    .or_default()
+                    .push(post_summary(post));
             }
         }
 
@@ -639,7 +1303,7 @@ impl Generator {
             let mut context = self.create_base_context(site_data, config_data, theme_data);
             context.insert("tag_name", tag);
             context.insert("tag_posts", tag_posts);
-            context.insert("current_path", &format!("tags/{}/", tag_slug));
+            context.insert("current_path", &format!("{}tags/{}/", prefix, tag_slug));
             context.insert("is_home", &false);
 
             let html = self.renderer.render("tag_single.html", &context)?;
@@ -647,125 +1311,417 @@ impl Generator {
             let output_path = self
                 .hexo
                 .public_dir
+                .join(prefix)
                 .join(&self.hexo.config.tag_dir)
                 .join(&tag_slug)
                 .join("index.html");
             if let Some(parent) = output_path.parent() {
                 fs::create_dir_all(parent)?;
             }
-            fs::write(&output_path, html)?;
+            self.write_output(&output_path, Origin::generated("tag"), html)?;
         }
 
         tracing::info!("Generated {} tag pages", tags_map.len());
         Ok(())
     }
 
-    /// Generate Atom RSS feed
-    fn generate_atom_feed(&self, posts: &[Post]) -> Result<()> {
-        let mut feed = String::new();
-        feed.push_str(r#"<?xml version="1.0" encoding="utf-8"?>"#);
-        feed.push('\n');
-        feed.push_str(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#);
-        feed.push('\n');
-        feed.push_str(&format!(
-            "  <title>{}</title>\n",
-            escape_xml(&self.hexo.config.title)
-        ));
-        feed.push_str(&format!(
-            "  <link href=\"{}/atom.xml\" rel=\"self\"/>\n",
-            self.hexo.config.url
-        ));
-        feed.push_str(&format!("  <link href=\"{}/\"/>\n", self.hexo.config.url));
-        feed.push_str(&format!(
-            "  <updated>{}</updated>\n",
-            chrono::Utc::now().to_rfc3339()
-        ));
-        feed.push_str(&format!("  <id>{}/</id>\n", self.hexo.config.url));
-        feed.push_str(&format!(
-            "  <author><name>{}</name></author>\n",
-            escape_xml(&self.hexo.config.author)
-        ));
-
-        // Include recent posts (limit to 20)
-        for post in posts.iter().take(20) {
-            feed.push_str("  <entry>\n");
-            feed.push_str(&format!("    <title>{}</title>\n", escape_xml(&post.title)));
-            feed.push_str(&format!(
-                "    <link href=\"{}{}\"/>\n",
-                self.hexo.config.url.trim_end_matches('/'),
-                if post.path.starts_with('/') {
-                    post.path.clone()
-                } else {
-                    format!("/{}", post.path)
+    /// Build the series navigation data for the language tree at `prefix`,
+    /// marking `current` if given
+    fn build_series_data(
+        &self,
+        name: &str,
+        posts: &[&Post],
+        current: Option<&Post>,
+        prefix: &str,
+    ) -> SeriesData {
+        let series_posts: Vec<SeriesPost> = posts
+            .iter()
+            .map(|p| SeriesPost {
+                title: p.title.clone(),
+                path: format!("/{}", p.path.trim_start_matches('/')),
+                date: p.date.format("%Y-%m-%d").to_string(),
+                is_current: current.is_some_and(|c| c.source == p.source),
+            })
+            .collect();
+        let position = series_posts
+            .iter()
+            .position(|p| p.is_current)
+            .map(|i| i + 1)
+            .unwrap_or(0);
+
+        SeriesData {
+            name: name.to_string(),
+            path: format!(
+                "{}{}{}/{}/",
+                self.hexo.config.root,
+                prefix,
+                self.hexo.config.series_dir.trim_matches('/'),
+                slug::slugify(name)
+            ),
+            position,
+            total: series_posts.len(),
+            posts: series_posts,
+        }
+    }
+
+    /// Generate a landing page for each series under a language prefix
+    fn generate_series_pages(
+        &self,
+        posts: &[Post],
+        prefix: &str,
+        site_data: &SiteData,
+        config_data: &ConfigData,
+        theme_data: &ThemeData,
+    ) -> Result<()> {
+        let series = group_series(posts);
+
+        for (name, series_posts) in &series {
+            let series_slug = slug::slugify(name);
+            if series_slug.is_empty() {
+                continue;
+            }
+            let dir = self.hexo.config.series_dir.trim_matches('/');
+
+            // Listings show newest first like tag pages; page_series keeps reading order
+            let tag_posts: Vec<PostData> =
+                series_posts.iter().rev().map(|p| post_summary(p)).collect();
+
+            let mut context = self.create_base_context(site_data, config_data, theme_data);
+            context.insert("tag_name", name);
+            context.insert("tag_posts", &tag_posts);
+            context.insert("taxonomy", "series");
+            context.insert(
+                "page_series",
+                &self.build_series_data(name, series_posts, None, prefix),
+            );
+            context.insert(
+                "current_path",
+                &format!("{}{}/{}/", prefix, dir, series_slug),
+            );
+            context.insert("is_home", &false);
+
+            let html = self.renderer.render("tag_single.html", &context)?;
+
+            let output_path = self
+                .hexo
+                .public_dir
+                .join(prefix)
+                .join(dir)
+                .join(&series_slug)
+                .join("index.html");
+            if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            self.write_output(&output_path, Origin::generated("series"), html)?;
+        }
+
+        if !series.is_empty() {
+            tracing::info!("Generated {} series pages", series.len());
+        }
+        Ok(())
+    }
+
+    /// Generate pages for custom taxonomies under a language prefix: one page
+    /// per term plus an index of terms
+    fn generate_taxonomy_pages(
+        &self,
+        posts: &[Post],
+        prefix: &str,
+        site_data: &SiteData,
+        config_data: &ConfigData,
+        theme_data: &ThemeData,
+    ) -> Result<()> {
+        for (name, dir) in &self.hexo.config.taxonomies {
+            let mut terms: BTreeMap<String, Vec<PostData>> = BTreeMap::new();
+            for post in posts {
+                for term in post.taxonomy_terms(name) {
+                    terms.entry(term).or_default().push(post_summary(post));
                 }
-            ));
-            feed.push_str(&format!(
-                "    <id>{}{}</id>\n",
-                self.hexo.config.url.trim_end_matches('/'),
-                if post.path.starts_with('/') {
-                    post.path.clone()
-                } else {
-                    format!("/{}", post.path)
+            }
+
+            let dir = dir.trim_matches('/');
+            for (term, term_posts) in &terms {
+                let term_slug = slug::slugify(term);
+                if term_slug.is_empty() {
+                    continue;
                 }
-            ));
-            feed.push_str(&format!(
-                "    <published>{}</published>\n",
-                post.date.to_rfc3339()
-            ));
-            feed.push_str(&format!(
-                "    <updated>{}</updated>\n",
-                post.updated.unwrap_or(post.date).to_rfc3339()
-            ));
-            // Convert relative URLs in content to absolute URLs
-            let content = post.excerpt.as_ref().unwrap_or(&post.content);
-            let base_url = self.hexo.config.url.trim_end_matches('/');
-            let content_with_full_urls = convert_relative_urls_to_absolute(content, base_url);
-            // Strip invalid XML control characters
-            let clean_content = strip_invalid_xml_chars(&content_with_full_urls);
-            feed.push_str(&format!(
-                "    <content type=\"html\"><![CDATA[{}]]></content>\n",
-                clean_content
-            ));
-            feed.push_str("  </entry>\n");
-        }
-
-        feed.push_str("</feed>\n");
-
-        let output_path = self.hexo.public_dir.join("atom.xml");
-        fs::write(&output_path, feed)?;
-        tracing::info!("Generated atom.xml");
+
+                let mut context = self.create_base_context(site_data, config_data, theme_data);
+                context.insert("tag_name", term);
+                context.insert("tag_posts", term_posts);
+                context.insert("taxonomy", name);
+                context.insert("current_path", &format!("{}{}/{}/", prefix, dir, term_slug));
+                context.insert("is_home", &false);
+
+                let html = self.renderer.render("tag_single.html", &context)?;
+
+                let output_path = self
+                    .hexo
+                    .public_dir
+                    .join(prefix)
+                    .join(dir)
+                    .join(&term_slug)
+                    .join("index.html");
+                if let Some(parent) = output_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                self.write_output(&output_path, Origin::generated("taxonomy"), html)?;
+            }
+
+            // Index of all terms
+            let all_terms: Vec<TagData> = terms
+                .into_iter()
+                .map(|(name, posts)| TagData { name, posts })
+                .collect();
+
+            let mut context = self.create_base_context(site_data, config_data, theme_data);
+            context.insert("all_tags", &all_terms);
+            context.insert("taxonomy", name);
+            context.insert("current_path", &format!("{}{}/", prefix, dir));
+            context.insert("is_special_page", &true);
+
+            let html = self.renderer.render("tags.html", &context)?;
+
+            let output_path = self
+                .hexo
+                .public_dir
+                .join(prefix)
+                .join(dir)
+                .join("index.html");
+            if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            self.write_output(&output_path, Origin::generated("taxonomy"), html)?;
+
+            tracing::info!("Generated {} {} pages", all_terms.len(), name);
+        }
+
+        Ok(())
+    }
+
+    /// The newest posts, as many as the feed lists
+    fn feed_entries<'p>(&self, posts: &'p [Post]) -> &'p [Post] {
+        match self.hexo.config.feed.limit {
+            0 => posts,
+            limit => &posts[..limit.min(posts.len())],
+        }
+    }
+
+    /// Generate Atom RSS feed, writing entries as they are built so that
+    /// the whole feed is never held in memory
+    fn generate_atom_feed(&self, posts: &[Post], prefix: &str) -> Result<()> {
+        let config = &self.hexo.config;
+        let entries = self.feed_entries(posts);
+        let base_url = config.url.trim_end_matches('/');
+
+        let output_path = self.hexo.public_dir.join(prefix).join("atom.xml");
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.outputs.record(&output_path, Origin::generated("feed"));
+        write_streamed(&output_path, |feed| {
+            writeln!(feed, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+            writeln!(feed, r#"<feed xmlns="http://www.w3.org/2005/Atom">"#)?;
+            writeln!(feed, "  <title>{}</title>", escape_xml(&config.title))?;
+            writeln!(
+                feed,
+                "  <link href=\"{}/{}atom.xml\" rel=\"self\"/>",
+                config.url, prefix
+            )?;
+            writeln!(feed, "  <link href=\"{}/{}\"/>", config.url, prefix)?;
+            // The newest entry's update time, so an unchanged feed stays unchanged
+            let updated = entries
+                .iter()
+                .map(|p| p.updated.unwrap_or(p.date))
+                .max()
+                .map(|d| d.to_rfc3339())
+                .unwrap_or_else(|| self.renderer.build_time().to_rfc3339());
+            writeln!(feed, "  <updated>{}</updated>", updated)?;
+            writeln!(feed, "  <id>{}/{}</id>", config.url, prefix)?;
+            writeln!(
+                feed,
+                "  <author><name>{}</name></author>",
+                escape_xml(&config.author)
+            )?;
+
+            for post in entries {
+                writeln!(feed, "  <entry>")?;
+                writeln!(feed, "    <title>{}</title>", escape_xml(&post.title))?;
+                writeln!(
+                    feed,
+                    "    <link href=\"{}/{}\"/>",
+                    base_url,
+                    post.path.trim_start_matches('/')
+                )?;
+                writeln!(feed, "    <id>urn:uuid:{}</id>", post.uuid)?;
+                writeln!(
+                    feed,
+                    "    <published>{}</published>",
+                    post.date.to_rfc3339()
+                )?;
+                writeln!(
+                    feed,
+                    "    <updated>{}</updated>",
+                    post.updated.unwrap_or(post.date).to_rfc3339()
+                )?;
+                // Convert relative URLs in content to absolute URLs
+                let full_content;
+                let content = match &post.excerpt {
+                    Some(excerpt) => excerpt.as_str(),
+                    None => {
+                        full_content = post.full_content()?;
+                        &full_content
+                    }
+                };
+                let content_with_full_urls = convert_relative_urls_to_absolute(content, base_url);
+                // Strip invalid XML control characters
+                let clean_content = strip_invalid_xml_chars(&content_with_full_urls);
+                writeln!(
+                    feed,
+                    "    <content type=\"html\"><![CDATA[{}]]></content>",
+                    clean_content
+                )?;
+                writeln!(feed, "  </entry>")?;
+            }
+            writeln!(feed, "</feed>")
+        })?;
+        tracing::info!("Generated {}atom.xml", prefix);
 
         Ok(())
     }
 
-    /// Generate search index (JSON)
+    /// Generate search index (JSON), an entry at a time so that the text of
+    /// every post is never held in memory at once
     fn generate_search_index(&self, posts: &[Post]) -> Result<()> {
-        let search_data: Vec<serde_json::Value> = posts
-            .iter()
-            .map(|p| {
-                serde_json::json!({
+        let output_path = self.hexo.public_dir.join("search.json");
+        self.outputs
+            .record(&output_path, Origin::generated("search"));
+        write_streamed(&output_path, |out| {
+            if posts.is_empty() {
+                return write!(out, "[]");
+            }
+            // Same layout as serde_json's pretty printer for the whole array
+            write!(out, "[")?;
+            for (i, p) in posts.iter().enumerate() {
+                let entry = serde_json::json!({
                     "title": p.title,
                     "url": format!("/{}", p.path.trim_start_matches('/')),
-                    "content": strip_html(&p.content),
+                    "content": strip_html(&p.full_content()?),
                     "date": p.date.format("%Y-%m-%d").to_string(),
-                })
-            })
+                });
+                let entry = serde_json::to_string_pretty(&entry)?;
+                let separator = if i == 0 { "" } else { "," };
+                write!(out, "{}\n  {}", separator, entry.replace('\n', "\n  "))?;
+            }
+            write!(out, "\n]")
+        })?;
+        tracing::info!("Generated search.json");
+
+        Ok(())
+    }
+
+    /// Generate meta-refresh pages and a `_redirects` file for `alias`/`redirect_from`.
+    /// `partial` builds don't write everything, so the files of the last full
+    /// build are protected from redirects too.
+    fn generate_redirects(&self, posts: &[Post], pages: &[Page], partial: bool) -> Result<()> {
+        let targets = posts
+            .iter()
+            .map(|p| (&p.aliases, &p.path))
+            .chain(pages.iter().map(|p| (&p.aliases, &p.path)));
+
+        let mut redirects = Vec::new();
+        for (aliases, path) in targets {
+            let to = format!("/{}", path.trim_start_matches('/'));
+            redirects.extend(aliases.iter().map(|alias| Redirect::new(alias, &to)));
+        }
+        if redirects.is_empty() {
+            return Ok(());
+        }
+
+        redirects.retain(|redirect| {
+            let safe = redirect.is_safe();
+            if !safe {
+                tracing::warn!(
+                    "Redirect {} points outside the public directory, skipping",
+                    redirect.from
+                );
+            }
+            safe
+        });
+
+        // Old URLs are usually still on disk from earlier builds, so only
+        // refuse to replace files that are generated in this build
+        let mut taken: BTreeSet<String> = self
+            .outputs
+            .origins()
+            .into_iter()
+            .filter(|(_, origin)| origin.kind != "redirect")
+            .map(|(key, _)| key)
             .collect();
+        taken.extend(
+            posts
+                .iter()
+                .map(|p| &p.path)
+                .chain(pages.iter().map(|p| &p.path))
+                .map(|path| Redirect::new(path, "").output_key()),
+        );
+        if partial {
+            // Lists, feeds and assets this build didn't write are still the
+            // last full build's, apart from its own redirects
+            let previous = fs::read_to_string(self.hexo.public_dir.join("_redirects"))
+                .map(|contents| parse_redirects_file(&contents))
+                .unwrap_or_default();
+            let previous: BTreeSet<String> = previous.iter().map(Redirect::output_key).collect();
+            let cache = CacheDb::load(&self.hexo.base_dir);
+            taken.extend(
+                cache
+                    .outputs()
+                    .iter()
+                    .chain(cache.assets().keys())
+                    .filter(|key| !previous.contains(*key))
+                    .cloned(),
+            );
+        }
+        redirects.retain(|redirect| {
+            let clash = taken.contains(&redirect.output_key());
+            if clash {
+                tracing::warn!(
+                    "Redirect {} would overwrite generated content, skipping",
+                    redirect.from
+                );
+            }
+            !clash
+        });
 
-        let output_path = self.hexo.public_dir.join("search.json");
-        let json = serde_json::to_string_pretty(&search_data)?;
-        fs::write(&output_path, json)?;
-        tracing::info!("Generated search.json");
+        for redirect in &redirects {
+            let output_path = redirect.output_path(&self.hexo.public_dir);
+            if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            self.write_output(
+                &output_path,
+                Origin::generated("redirect"),
+                redirect_page(&redirect.to),
+            )?;
+        }
+
+        self.write_output(
+            &self.hexo.public_dir.join("_redirects"),
+            Origin::generated("redirect"),
+            redirects_file(&redirects),
+        )?;
+        tracing::info!("Generated {} redirects", redirects.len());
 
         Ok(())
     }
 
     /// Copy source assets (images, etc.) to public directory
-    fn copy_source_assets(&self) -> Result<()> {
+    fn copy_source_assets(&self, sass: &SassCompiler, assets: &mut AssetCopier) -> Result<()> {
         let source_dir = &self.hexo.source_dir;
 
         for entry in WalkDir::new(source_dir)
             .follow_links(true)
+            .sort_by_file_name()
             .into_iter()
             .filter_map(|e| e.ok())
         {
@@ -779,22 +1735,31 @@ impl Generator {
                     continue;
                 }
 
-                // Skip files in _posts directory
-                if path
-                    .components()
-                    .any(|c| c.as_os_str() == "_posts" || c.as_os_str() == "_drafts")
-                {
+                // Skip files in _posts, _drafts and _data directories
+                if path.components().any(|c| {
+                    c.as_os_str() == "_posts"
+                        || c.as_os_str() == "_drafts"
+                        || c.as_os_str() == "_data"
+                }) {
                     continue;
                 }
 
                 let relative = path.strip_prefix(source_dir)?;
-                let dest = self.hexo.public_dir.join(relative);
-
-                if let Some(parent) = dest.parent() {
-                    fs::create_dir_all(parent)?;
+                if SassCompiler::handles(path) {
+                    if SassCompiler::is_partial(path) {
+                        continue;
+                    }
+                    let css_relative = relative.with_extension("css");
+                    let dest = self.hexo.public_dir.join(&css_relative);
+                    if let Some(parent) = dest.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    write_if_changed(&dest, sass.compile(path)?)?;
+                    assets.claim(&css_relative);
+                    continue;
                 }
 
-                fs::copy(path, &dest)?;
+                assets.copy(path, relative);
             }
         }
 
@@ -802,46 +1767,117 @@ impl Generator {
     }
 }
 
-/// Count words in HTML content (strips tags first)
-fn count_words(html: &str) -> usize {
-    let text = strip_html(html);
-    // Count Chinese characters and English words
-    let mut count = 0;
-    let mut in_word = false;
-
-    for c in text.chars() {
-        if c.is_ascii_alphanumeric() {
-            if !in_word {
-                in_word = true;
-                count += 1;
-            }
-        } else if c > '\u{4E00}' && c < '\u{9FFF}' {
-            // Chinese characters
-            count += 1;
-            in_word = false;
-        } else {
-            in_word = false;
-        }
-    }
+/// Parse blogroll link entries (a list of mappings with name/url/description/avatar/rel)
+fn parse_links(value: Option<&serde_yaml::Value>, default_rel: &str) -> Vec<LinkData> {
+    let Some(serde_yaml::Value::Sequence(entries)) = value else {
+        return Vec::new();
+    };
+
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let map = entry.as_mapping()?;
+            let get = |key: &str| {
+                map.get(key)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string()
+            };
+            let url = get("url");
+            if url.is_empty() {
+                return None;
+            }
+            let rel = link_rel(map.get("rel"), default_rel, is_external(&url));
+            Some(LinkData {
+                name: get("name"),
+                description: get("description"),
+                avatar: get("avatar"),
+                url,
+                rel,
+            })
+        })
+        .collect()
+}
 
-    count
+/// Replace `theme` in the context with a copy that has the front matter's
+/// `theme:` mapping merged in (e.g. `theme: { catalog: false }` for one post)
+fn override_theme(
+    context: &mut Context,
+    theme_data: &ThemeData,
+    extra: &HashMap<String, serde_yaml::Value>,
+) {
+    let Some(overrides) = extra.get("theme").filter(|v| v.is_mapping()) else {
+        return;
+    };
+    let (Ok(mut theme), Ok(overrides)) = (
+        serde_yaml::to_value(theme_data),
+        serde_yaml::to_value(overrides),
+    ) else {
+        return;
+    };
+    merge_yaml(&mut theme, overrides);
+    context.insert("theme", &theme);
 }
 
-/// Strip HTML tags from content
-fn strip_html(html: &str) -> String {
-    let mut result = String::new();
-    let mut in_tag = false;
+/// `page.*` template variables: the standard fields plus any custom front
+/// matter (`cover`, `subtitle`, ...). Standard fields win on name clashes.
+fn page_vars(
+    standard: serde_json::Value,
+    extra: &HashMap<String, serde_yaml::Value>,
+) -> serde_json::Map<String, serde_json::Value> {
+    // Sorted, as front matter is read into a HashMap
+    let mut vars: serde_json::Map<String, serde_json::Value> = extra
+        .iter()
+        .filter_map(|(key, value)| Some((key.clone(), serde_json::to_value(value).ok()?)))
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .collect();
+    if let serde_json::Value::Object(standard) = standard {
+        vars.extend(standard);
+    }
+    vars
+}
 
-    for c in html.chars() {
-        match c {
-            '<' => in_tag = true,
-            '>' => in_tag = false,
-            _ if !in_tag => result.push(c),
-            _ => {}
+/// Front matter `description`, or the text of the excerpt (or whole post
+/// `content`)
+fn post_description(post: &Post, content: &str) -> String {
+    match post.extra.get("description").and_then(|v| v.as_str()) {
+        Some(description) => description.to_string(),
+        None => strip_html(post.excerpt.as_deref().unwrap_or(content)),
+    }
+}
+
+/// Group posts by series name, each in reading order (oldest first)
+fn group_series(posts: &[Post]) -> BTreeMap<String, Vec<&Post>> {
+    let mut series: BTreeMap<String, Vec<&Post>> = BTreeMap::new();
+    for post in posts {
+        if let Some(name) = &post.series {
+            series.entry(name.clone()).or_default().push(post);
         }
     }
+    for series_posts in series.values_mut() {
+        series_posts.sort_by_key(|p| p.date);
+    }
+    series
+}
 
-    result
+/// Post data for listings (archive, tag and taxonomy pages) without the content
+fn post_summary(post: &Post) -> PostData {
+    PostData {
+        uuid: post.uuid.clone(),
+        title: post.title.clone(),
+        date: post.date.format("%Y-%m-%d").to_string(),
+        path: format!("/{}", post.path.trim_start_matches('/')),
+        permalink: post.permalink.clone(),
+        tags: post.tags.clone(),
+        categories: post.categories.clone(),
+        content: String::new(), // Don't need full content for listings
+        excerpt: None,
+        word_count: 0,
+        reading_time: 0,
+        is_sticky: post.sticky > 0,
+        cover: post.cover(),
+    }
 }
 
 /// Escape XML special characters
@@ -0,0 +1,490 @@
+//! Writing output files
+
+use anyhow::Result;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+
+use super::manifest::Origin;
+use crate::cache::{AssetRecord, CacheDb};
+
+/// Write `contents` to `path` unless the file already holds exactly these
+/// bytes, so unchanged output keeps its mtime for rsync, deploy tools and
+/// browser caches. Returns whether the file was written.
+pub fn write_if_changed(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<bool> {
+    let (path, contents) = (path.as_ref(), contents.as_ref());
+    let unchanged = fs::metadata(path).is_ok_and(|m| m.len() == contents.len() as u64)
+        && fs::read(path).is_ok_and(|existing| existing == contents);
+    if unchanged {
+        return Ok(false);
+    }
+    fs::write(path, contents)?;
+    Ok(true)
+}
+
+/// Like `write_if_changed`, for output produced piece by piece: `write`
+/// writes to a buffered temporary file next to `path`, which replaces `path`
+/// unless the content is the same. Returns whether `path` was written.
+pub fn write_streamed(
+    path: impl AsRef<Path>,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<bool> {
+    let path = path.as_ref();
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+
+    let result = File::create(&tmp).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        writer.flush()
+    });
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    if same_content(&tmp, path) {
+        fs::remove_file(&tmp)?;
+        return Ok(false);
+    }
+    fs::rename(&tmp, path)?;
+    Ok(true)
+}
+
+/// Files written to `public/` during a build and where they came from, so
+/// files written by an earlier build and not by this one can be removed
+pub struct OutputLog {
+    public_dir: PathBuf,
+    written: Mutex<BTreeMap<String, Origin>>,
+}
+
+impl OutputLog {
+    pub fn new(public_dir: &Path) -> Self {
+        Self {
+            public_dir: public_dir.to_path_buf(),
+            written: Mutex::default(),
+        }
+    }
+
+    /// Note that `path` was written (or found unchanged) by this build
+    pub fn record(&self, path: &Path, origin: Origin) {
+        if let Ok(relative) = path.strip_prefix(&self.public_dir) {
+            self.written
+                .lock()
+                .unwrap()
+                .insert(output_key(relative), origin);
+        }
+    }
+
+    /// Output paths recorded so far, relative to `public/`
+    pub fn written(&self) -> BTreeSet<String> {
+        self.written.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Output paths recorded so far with where they came from
+    pub fn origins(&self) -> BTreeMap<String, Origin> {
+        self.written.lock().unwrap().clone()
+    }
+
+    /// Remove the files in `previous` that this build didn't write, along
+    /// with directories left empty. Files for which `is_asset` is true were
+    /// copied by this build, say a page replaced by a static file of the
+    /// same path, and are kept. Returns how many files were removed.
+    pub fn prune(
+        &self,
+        previous: &BTreeSet<String>,
+        is_asset: impl Fn(&str) -> bool,
+    ) -> io::Result<usize> {
+        let written = self.written.lock().unwrap();
+        let mut removed = 0;
+        for key in previous
+            .iter()
+            .filter(|key| !written.contains_key(*key) && !is_asset(key))
+        {
+            let path = self.public_dir.join(key);
+            if !path.is_file() {
+                continue;
+            }
+            fs::remove_file(&path)?;
+            tracing::debug!("Removed stale output: {:?}", path);
+            removed += 1;
+            let mut dir = path.parent();
+            while let Some(parent) = dir.filter(|d| *d != self.public_dir) {
+                if fs::remove_dir(parent).is_err() {
+                    break;
+                }
+                dir = parent.parent();
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Caps how many threads do file IO at the same time
+pub struct IoLimit {
+    /// 0 for no limit
+    limit: usize,
+    active: Mutex<usize>,
+    done: Condvar,
+}
+
+impl IoLimit {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            active: Mutex::new(0),
+            done: Condvar::new(),
+        }
+    }
+
+    /// Run `io` once fewer than `limit` other threads are inside `run`
+    pub fn run<T>(&self, io: impl FnOnce() -> T) -> T {
+        if self.limit == 0 {
+            return io();
+        }
+        let mut active = self.active.lock().unwrap();
+        while *active >= self.limit {
+            active = self.done.wait(active).unwrap();
+        }
+        *active += 1;
+        drop(active);
+
+        let _slot = Slot(self);
+        io()
+    }
+}
+
+/// Gives a slot of an `IoLimit` back when dropped, even if the I/O panics
+struct Slot<'a>(&'a IoLimit);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        *self.0.active.lock().unwrap() -= 1;
+        self.0.done.notify_one();
+    }
+}
+
+/// Copies static assets into `public/`, skipping files whose source hasn't
+/// changed since the last build and removing ones whose source is gone.
+///
+/// Copies are planned with `copy` and done by `finish`, so when a child theme
+/// or the site overrides a file, only the winning source is copied.
+pub struct AssetCopier {
+    public_dir: PathBuf,
+    /// Source of each output, by path relative to `public/`
+    planned: BTreeMap<String, PathBuf>,
+    /// Outputs written directly, such as compiled stylesheets
+    claimed: BTreeSet<String>,
+    /// Planned outputs that were written directly earlier in this build,
+    /// so their content can't be trusted to match the cache
+    overwritten: BTreeSet<String>,
+    io: IoLimit,
+}
+
+/// What `AssetCopier::finish` did
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CopyStats {
+    pub copied: usize,
+    pub skipped: usize,
+    pub removed: usize,
+    /// Bytes copied
+    pub bytes: u64,
+}
+
+/// Assets checked between progress messages
+const PROGRESS_INTERVAL: usize = 1000;
+
+impl AssetCopier {
+    pub fn new(public_dir: &Path) -> Self {
+        Self {
+            public_dir: public_dir.to_path_buf(),
+            planned: BTreeMap::new(),
+            claimed: BTreeSet::new(),
+            overwritten: BTreeSet::new(),
+            io: IoLimit::new(0),
+        }
+    }
+
+    /// Copy at most `limit` files at the same time, 0 for no limit
+    pub fn with_io_limit(mut self, limit: usize) -> Self {
+        self.io = IoLimit::new(limit);
+        self
+    }
+
+    pub fn public_dir(&self) -> &Path {
+        &self.public_dir
+    }
+
+    /// Plan copying `source` to `relative` in `public/`, replacing any
+    /// earlier plan for the same output
+    pub fn copy(&mut self, source: &Path, relative: &Path) {
+        let key = output_key(relative);
+        if self.claimed.remove(&key) {
+            self.overwritten.insert(key.clone());
+        }
+        self.planned.insert(key, source.to_path_buf());
+    }
+
+    /// Note that `relative` was written by other means, cancelling any
+    /// planned copy to it
+    pub fn claim(&mut self, relative: &Path) {
+        let key = output_key(relative);
+        self.planned.remove(&key);
+        self.claimed.insert(key);
+    }
+
+    /// Outputs written by other means, see `claim`
+    pub fn claimed(&self) -> &BTreeSet<String> {
+        &self.claimed
+    }
+
+    /// Copy the planned assets whose source changed since the cached build,
+    /// remove assets the cached build copied that no longer have a source,
+    /// and record this build's assets in the cache. Copies run in parallel
+    /// (on the current rayon pool).
+    pub fn finish(self, cache: &mut CacheDb) -> Result<CopyStats> {
+        let total = self.planned.len();
+        let done = AtomicUsize::new(0);
+        let cached = cache.assets();
+
+        // (record, bytes copied) of each asset, None if it was unchanged
+        let results = self
+            .planned
+            .par_iter()
+            .map(|(key, source)| {
+                let record = AssetRecord::of(source)?;
+                let dest = self.public_dir.join(key);
+                let dest_size = fs::metadata(&dest).ok().map(|m| m.len());
+                let unchanged = dest_size == Some(record.size)
+                    && ((cached.get(key) == Some(&record) && !self.overwritten.contains(key))
+                        || same_content(source, &dest));
+                let copied = if unchanged {
+                    None
+                } else {
+                    if let Some(parent) = dest.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    let bytes = self.io.run(|| fs::copy(source, &dest)).map_err(|e| {
+                        anyhow::anyhow!("Failed to copy {:?} to {:?}: {}", source, dest, e)
+                    })?;
+                    tracing::debug!("Copied: {:?} -> {:?}", source, dest);
+                    Some(bytes)
+                };
+
+                let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                if done.is_multiple_of(PROGRESS_INTERVAL) {
+                    tracing::info!("Checked {}/{} assets", done, total);
+                }
+                Ok((key.clone(), record, copied))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut stats = CopyStats::default();
+        let mut records = BTreeMap::new();
+        for (key, record, copied) in results {
+            match copied {
+                Some(bytes) => {
+                    stats.copied += 1;
+                    stats.bytes += bytes;
+                }
+                None => stats.skipped += 1,
+            }
+            records.insert(key, record);
+        }
+
+        for key in cache.assets().keys() {
+            if records.contains_key(key) || self.claimed.contains(key) {
+                continue;
+            }
+            let dest = self.public_dir.join(key);
+            if dest.is_file() {
+                fs::remove_file(&dest)?;
+                tracing::debug!("Removed stale asset: {:?}", dest);
+                stats.removed += 1;
+            }
+        }
+
+        cache.set_assets(records);
+        Ok(stats)
+    }
+}
+
+/// Cache key of an output path: relative to `public/`, with `/` separators
+fn output_key(relative: &Path) -> String {
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+/// Whether two files have the same bytes, compared a chunk at a time
+pub(crate) fn same_content(a: &Path, b: &Path) -> bool {
+    let (Ok(a), Ok(b)) = (File::open(a), File::open(b)) else {
+        return false;
+    };
+    let same_len = match (a.metadata(), b.metadata()) {
+        (Ok(x), Ok(y)) => x.len() == y.len(),
+        _ => false,
+    };
+    if !same_len {
+        return false;
+    }
+
+    let (mut a, mut b) = (io::BufReader::new(a), io::BufReader::new(b));
+    let (mut chunk_a, mut chunk_b) = ([0u8; 8192], [0u8; 8192]);
+    loop {
+        let n = match a.read(&mut chunk_a) {
+            Ok(0) => return true,
+            Ok(n) => n,
+            Err(_) => return false,
+        };
+        if b.read_exact(&mut chunk_b[..n]).is_err() || chunk_a[..n] != chunk_b[..n] {
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_log_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let public = dir.path();
+        for key in ["index.html", "tags/old/index.html", "tags/new/index.html"] {
+            fs::create_dir_all(public.join(key).parent().unwrap()).unwrap();
+            fs::write(public.join(key), key).unwrap();
+        }
+        let previous = BTreeSet::from(["index.html".to_string(), "tags/old/index.html".into()]);
+
+        let log = OutputLog::new(public);
+        log.record(&public.join("index.html"), Origin::generated("index"));
+        log.record(
+            &public.join("tags/new/index.html"),
+            Origin::generated("tag"),
+        );
+        assert_eq!(log.prune(&previous, |_| false).unwrap(), 1);
+        assert!(public.join("index.html").exists());
+        assert!(public.join("tags/new/index.html").exists());
+        assert!(!public.join("tags/old").exists());
+        assert_eq!(log.written().len(), 2);
+    }
+
+    #[test]
+    fn test_output_log_prune_keeps_assets() {
+        // source/about.md was replaced by a static source/about/index.html
+        let dir = tempfile::tempdir().unwrap();
+        let public = dir.path();
+        fs::create_dir_all(public.join("about")).unwrap();
+        fs::write(public.join("about/index.html"), "static").unwrap();
+        let previous = BTreeSet::from(["about/index.html".to_string()]);
+        let assets = BTreeSet::from(["about/index.html".to_string()]);
+
+        let log = OutputLog::new(public);
+        assert_eq!(log.prune(&previous, |key| assets.contains(key)).unwrap(), 0);
+        assert!(public.join("about/index.html").exists());
+    }
+
+    #[test]
+    fn test_write_if_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.html");
+
+        assert!(write_if_changed(&path, "<p>hi</p>").unwrap());
+        let written = fs::metadata(&path).unwrap().modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+
+        assert!(!write_if_changed(&path, "<p>hi</p>").unwrap());
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), written);
+
+        assert!(write_if_changed(&path, "<p>bye</p>").unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "<p>bye</p>");
+    }
+
+    #[test]
+    fn test_write_streamed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("atom.xml");
+        let feed = |w: &mut dyn Write| {
+            for i in 0..3 {
+                writeln!(w, "<entry>{}</entry>", i)?;
+            }
+            Ok(())
+        };
+
+        assert!(write_streamed(&path, feed).unwrap());
+        assert!(!write_streamed(&path, feed).unwrap());
+        assert!(write_streamed(&path, |w| w.write_all(b"<feed/>")).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "<feed/>");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_io_limit() {
+        let limit = IoLimit::new(2);
+        let (active, most) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    limit.run(|| {
+                        let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                        most.fetch_max(now, Ordering::SeqCst);
+                        std::thread::sleep(std::time::Duration::from_millis(10));
+                        active.fetch_sub(1, Ordering::SeqCst);
+                    })
+                });
+            }
+        });
+        assert!(most.into_inner() <= 2);
+
+        // A panicking write doesn't keep its slot
+        for _ in 0..2 {
+            let panicked = std::panic::catch_unwind(|| limit.run(|| panic!("write failed")));
+            assert!(panicked.is_err());
+        }
+        assert_eq!(limit.run(|| 1), 1);
+    }
+
+    #[test]
+    fn test_asset_copier() {
+        let site = tempfile::tempdir().unwrap();
+        let public = site.path().join("public");
+        let source = site.path().join("source");
+        fs::create_dir_all(source.join("img")).unwrap();
+        fs::write(source.join("img/a.png"), "a").unwrap();
+        fs::write(source.join("img/b.png"), "b").unwrap();
+        fs::write(source.join("theme.css"), "theme").unwrap();
+        fs::write(source.join("site.css"), "site").unwrap();
+
+        let build = |cache: &mut CacheDb, files: &[&str]| {
+            let mut copier = AssetCopier::new(&public);
+            copier.copy(&source.join("theme.css"), Path::new("style.css"));
+            copier.copy(&source.join("site.css"), Path::new("style.css"));
+            for file in files {
+                copier.copy(&source.join(file), Path::new(file));
+            }
+            copier.finish(cache).unwrap()
+        };
+
+        let mut cache = CacheDb::load(site.path());
+        let stats = build(&mut cache, &["img/a.png", "img/b.png"]);
+        assert_eq!((stats.copied, stats.skipped, stats.removed), (3, 0, 0));
+        assert!(stats.bytes > 0);
+        assert_eq!(
+            fs::read_to_string(public.join("style.css")).unwrap(),
+            "site"
+        );
+
+        let stats = build(&mut cache, &["img/a.png", "img/b.png"]);
+        assert_eq!((stats.copied, stats.skipped, stats.removed), (0, 3, 0));
+
+        fs::write(source.join("img/a.png"), "A2").unwrap();
+        fs::remove_file(source.join("img/b.png")).unwrap();
+        let stats = build(&mut cache, &["img/a.png"]);
+        assert_eq!((stats.copied, stats.skipped, stats.removed), (1, 1, 1));
+        assert_eq!(fs::read_to_string(public.join("img/a.png")).unwrap(), "A2");
+        assert!(!public.join("img/b.png").exists());
+    }
+}
@@ -0,0 +1,132 @@
+//! Named stages of a full build
+
+use anyhow::{bail, Result};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::cache::CacheDb;
+use crate::content::{Page, Post};
+use crate::templates::{ConfigData, SiteData, ThemeData};
+
+/// A step of `Generator::generate`. Stages run in the order they are given
+/// and share a `Build`, so they can be skipped or re-ordered as long as
+/// `Load` runs before the stages that render pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// Copy theme and source assets and write bundles
+    Assets,
+    /// Sort posts and build the data shared by every template
+    Load,
+    /// Index, archive, tag, series and taxonomy pages
+    Lists,
+    /// Atom feeds
+    Feeds,
+    /// Post pages
+    Posts,
+    /// Standalone pages
+    Pages,
+    /// Search index, redirects and the highlight stylesheet
+    Extras,
+}
+
+impl Stage {
+    /// Every stage, in the order a full build runs them
+    pub const ALL: [Stage; 7] = [
+        Stage::Assets,
+        Stage::Load,
+        Stage::Lists,
+        Stage::Feeds,
+        Stage::Posts,
+        Stage::Pages,
+        Stage::Extras,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Assets => "assets",
+            Stage::Load => "load",
+            Stage::Lists => "lists",
+            Stage::Feeds => "feeds",
+            Stage::Posts => "posts",
+            Stage::Pages => "pages",
+            Stage::Extras => "extras",
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Stage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Stage::ALL.into_iter().find(|stage| stage.name() == s) {
+            Some(stage) => Ok(stage),
+            None => bail!(
+                "Unknown build stage '{}', expected one of: {}",
+                s,
+                Stage::ALL.map(Stage::name).join(", ")
+            ),
+        }
+    }
+}
+
+/// Template data built by the `Load` stage
+pub(super) struct Prepared {
+    pub posts: Vec<Post>,
+    pub site: SiteData,
+    pub config: ConfigData,
+    pub theme: ThemeData,
+}
+
+/// State shared by the stages of one build
+pub(super) struct Build<'a> {
+    pub posts: &'a [Post],
+    pub pages: &'a [Page],
+    pub cache: CacheDb,
+    prepared: Option<Prepared>,
+    /// How long each stage that ran took
+    pub timings: Vec<(Stage, Duration)>,
+}
+
+impl<'a> Build<'a> {
+    pub fn new(posts: &'a [Post], pages: &'a [Page], cache: CacheDb) -> Self {
+        Self {
+            posts,
+            pages,
+            cache,
+            prepared: None,
+            timings: Vec::new(),
+        }
+    }
+
+    pub fn set_prepared(&mut self, prepared: Prepared) {
+        self.prepared = Some(prepared);
+    }
+
+    /// The data of the `Load` stage, which `stage` needs
+    pub fn prepared(&self, stage: Stage) -> Result<&Prepared> {
+        match &self.prepared {
+            Some(prepared) => Ok(prepared),
+            None => bail!("The {} stage needs the load stage to run first", stage),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_names() {
+        for stage in Stage::ALL {
+            assert_eq!(stage.name().parse::<Stage>().unwrap(), stage);
+        }
+        assert!("render".parse::<Stage>().is_err());
+    }
+}
@@ -0,0 +1,134 @@
+//! Redirect pages for `alias`/`redirect_from` front matter
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::helpers::html_escape;
+
+/// A redirect from an old URL path to a new one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirect {
+    pub from: String,
+    pub to: String,
+}
+
+impl Redirect {
+    /// Normalize `from` into a root-relative path (`old/post` -> `/old/post/`)
+    pub fn new(from: &str, to: &str) -> Self {
+        let from = from.trim().trim_start_matches('/');
+        let from = if from.is_empty() || from.ends_with('/') || has_extension(from) {
+            format!("/{}", from)
+        } else {
+            format!("/{}/", from)
+        };
+        Self {
+            from,
+            to: to.to_string(),
+        }
+    }
+
+    /// Whether the redirect page stays inside the public directory: no `..`
+    /// and nothing that is still absolute once the leading `/` is trimmed
+    pub fn is_safe(&self) -> bool {
+        let from = self.from.trim_start_matches('/');
+        !from.split(['/', '\\']).any(|segment| segment == "..")
+            && Path::new(from)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    }
+
+    /// File the redirect page is written to, relative to the public
+    /// directory, with `/` separators
+    pub fn output_key(&self) -> String {
+        let from = self.from.trim_start_matches('/');
+        if has_extension(from) {
+            from.to_string()
+        } else {
+            format!("{}index.html", from)
+        }
+    }
+
+    /// File the redirect page is written to
+    pub fn output_path(&self, public_dir: &Path) -> PathBuf {
+        public_dir.join(self.output_key())
+    }
+}
+
+/// Render a meta-refresh page pointing at `to`
+pub fn redirect_page(to: &str) -> String {
+    let to = html_escape(to);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Redirecting…</title>\n<link rel=\"canonical\" href=\"{to}\">\n<meta name=\"robots\" content=\"noindex\">\n<meta http-equiv=\"refresh\" content=\"0; url={to}\">\n</head>\n<body>\n<p>Redirecting to <a href=\"{to}\">{to}</a>…</p>\n</body>\n</html>\n"
+    )
+}
+
+/// Paths redirected by a `_redirects` file
+pub fn parse_redirects_file(contents: &str) -> Vec<Redirect> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            Some(Redirect::new(parts.next()?, parts.next()?))
+        })
+        .collect()
+}
+
+/// Render a Netlify/Cloudflare Pages `_redirects` file
+pub fn redirects_file(redirects: &[Redirect]) -> String {
+    redirects
+        .iter()
+        .map(|r| format!("{} {} 301\n", r.from, r.to))
+        .collect()
+}
+
+fn has_extension(path: &str) -> bool {
+    path.rsplit('/')
+        .next()
+        .is_some_and(|file| file.contains('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redirect_paths() {
+        let public = Path::new("/public");
+        let dir = Redirect::new("old/post", "/2024/01/01/post/");
+        assert_eq!(dir.from, "/old/post/");
+        assert_eq!(
+            dir.output_path(public),
+            PathBuf::from("/public/old/post/index.html")
+        );
+
+        let file = Redirect::new("/old/post.html", "/2024/01/01/post/");
+        assert_eq!(file.from, "/old/post.html");
+        assert_eq!(
+            file.output_path(public),
+            PathBuf::from("/public/old/post.html")
+        );
+
+        let contents = redirects_file(&[dir.clone(), file.clone()]);
+        assert_eq!(
+            contents,
+            "/old/post/ /2024/01/01/post/ 301\n/old/post.html /2024/01/01/post/ 301\n"
+        );
+        assert_eq!(parse_redirects_file(&contents), vec![dir, file]);
+        assert_eq!(Redirect::new("/", "/a/").output_key(), "index.html");
+    }
+
+    #[test]
+    fn test_redirect_is_safe() {
+        assert!(Redirect::new("old/post", "/").is_safe());
+        assert!(Redirect::new("./old.html", "/").is_safe());
+        assert!(!Redirect::new("../../x", "/").is_safe());
+        assert!(!Redirect::new("old/../../x.html", "/").is_safe());
+        assert!(!Redirect::new("old\\..\\x", "/").is_safe());
+    }
+
+    #[test]
+    fn test_redirect_page() {
+        let html = redirect_page("/new/");
+        assert!(html.contains(r#"<meta http-equiv="refresh" content="0; url=/new/">"#));
+        assert!(html.contains(r#"<link rel="canonical" href="/new/">"#));
+    }
+}
@@ -0,0 +1,249 @@
+//! `js()` and `css()` helpers: `<script>` and stylesheet `<link>` tags
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use super::{html_escape, is_external, url_for};
+use crate::config::SiteConfig;
+
+/// The kind of tag an asset helper emits
+#[derive(Clone, Copy)]
+enum Kind {
+    Script,
+    Stylesheet,
+}
+
+impl Kind {
+    /// Argument naming the asset path (besides `path`)
+    fn key(self) -> &'static str {
+        match self {
+            Kind::Script => "src",
+            Kind::Stylesheet => "href",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Kind::Script => ".js",
+            Kind::Stylesheet => ".css",
+        }
+    }
+
+    fn tag(self, url: &str, attrs: &[(String, Value)]) -> String {
+        let attrs: String = attrs.iter().filter_map(|(k, v)| attribute(k, v)).collect();
+        match self {
+            Kind::Script => format!("<script src=\"{}\"{}></script>", html_escape(url), attrs),
+            Kind::Stylesheet => format!(
+                "<link rel=\"stylesheet\" href=\"{}\"{}>",
+                html_escape(url),
+                attrs
+            ),
+        }
+    }
+}
+
+/// `<script>` tags, like Hexo's `js` helper: `js(path="js/app")`,
+/// `js(path=["a.js", "b.js"])` or `js(src="app.js", async=true, defer=true)`.
+/// Other arguments given with `path` apply to every tag. List items may also
+/// be objects with a `src` and attributes of their own.
+pub fn js(args: &HashMap<String, Value>, config: &SiteConfig) -> Result<String, String> {
+    tags(Kind::Script, args, config)
+}
+
+/// Stylesheet `<link>` tags, like Hexo's `css` helper: `css(path="style")`,
+/// `css(path=["a.css", "b.css"])` or `css(href="print.css", media="print")`.
+/// Other arguments given with `path` apply to every tag. List items may also
+/// be objects with an `href` and attributes of their own.
+pub fn css(args: &HashMap<String, Value>, config: &SiteConfig) -> Result<String, String> {
+    tags(Kind::Stylesheet, args, config)
+}
+
+fn tags(kind: Kind, args: &HashMap<String, Value>, config: &SiteConfig) -> Result<String, String> {
+    let name = match kind {
+        Kind::Script => "js",
+        Kind::Stylesheet => "css",
+    };
+
+    let assets: Vec<(String, Vec<(String, Value)>)> = match args.get("path") {
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| asset(kind, item))
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("{}: `path` items must be strings or objects", name))?,
+        Some(item) => vec![asset(kind, item)
+            .ok_or_else(|| format!("{}: `path` must be a string, list or object", name))?],
+        None => {
+            let path = args
+                .get(kind.key())
+                .and_then(Value::as_str)
+                .ok_or_else(|| format!("{}: missing `path` or `{}` argument", name, kind.key()))?;
+            vec![(path.to_string(), sorted_attrs(args, &[kind.key()]))]
+        }
+    };
+    let shared = match args.contains_key("path") {
+        true => sorted_attrs(args, &["path", kind.key()]),
+        false => Vec::new(),
+    };
+
+    // Files bundled together are replaced by their bundle, emitted once
+    let mut seen = Vec::new();
+    let mut html = Vec::new();
+    for (path, attrs) in assets {
+        let path = with_extension(&path, kind.extension());
+        let path = bundle_for(&path, config).unwrap_or(path);
+        if seen.contains(&path) {
+            continue;
+        }
+        // An item's own attributes win over the shared ones
+        let mut all: Vec<(String, Value)> = shared
+            .iter()
+            .filter(|(k, _)| !attrs.iter().any(|(own, _)| own == k))
+            .cloned()
+            .collect();
+        all.extend(attrs);
+        html.push(kind.tag(&url_for(&path, config, None), &all));
+        seen.push(path);
+    }
+    Ok(html.join("\n"))
+}
+
+/// Path and attributes of one list item
+fn asset(kind: Kind, item: &Value) -> Option<(String, Vec<(String, Value)>)> {
+    match item {
+        Value::String(path) => Some((path.clone(), Vec::new())),
+        Value::Object(map) => {
+            let path = map.get(kind.key()).and_then(Value::as_str)?;
+            let attrs = map
+                .iter()
+                .filter(|(k, _)| k.as_str() != kind.key())
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            Some((path.to_string(), attrs))
+        }
+        _ => None,
+    }
+}
+
+/// Every argument except the `skip` ones, sorted for stable output
+fn sorted_attrs(args: &HashMap<String, Value>, skip: &[&str]) -> Vec<(String, Value)> {
+    let mut attrs: Vec<(String, Value)> = args
+        .iter()
+        .filter(|(k, _)| !skip.contains(&k.as_str()))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    attrs.sort_by(|a, b| a.0.cmp(&b.0));
+    attrs
+}
+
+/// ` name="value"`, a bare ` name` for `true`, nothing for `false`/null
+fn attribute(name: &str, value: &Value) -> Option<String> {
+    match value {
+        Value::Bool(true) => Some(format!(" {}", name)),
+        Value::Bool(false) | Value::Null => None,
+        Value::String(s) => Some(format!(" {}=\"{}\"", name, html_escape(s))),
+        other => Some(format!(" {}=\"{}\"", name, html_escape(&other.to_string()))),
+    }
+}
+
+/// Add the extension unless the path has it or a query string
+fn with_extension(path: &str, extension: &str) -> String {
+    if path.ends_with(extension) || path.contains('?') || is_external(path) {
+        path.to_string()
+    } else {
+        format!("{}{}", path, extension)
+    }
+}
+
+/// The configured bundle `path` is concatenated into, if any
+fn bundle_for(path: &str, config: &SiteConfig) -> Option<String> {
+    let path = path.trim_start_matches('/');
+    config
+        .bundle
+        .files
+        .iter()
+        .find(|(_, inputs)| inputs.iter().any(|i| i.trim_start_matches('/') == path))
+        .map(|(bundle, _)| bundle.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn args(pairs: &[(&str, Value)]) -> HashMap<String, Value> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_js_css() {
+        let mut config = SiteConfig {
+            root: "/".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            js(
+                &args(&[
+                    ("src", json!("js/app")),
+                    ("async", json!(true)),
+                    ("defer", json!(false))
+                ]),
+                &config
+            )
+            .unwrap(),
+            r#"<script src="/js/app.js" async></script>"#
+        );
+        assert_eq!(
+            css(
+                &args(&[("href", json!("print.css")), ("media", json!("print"))]),
+                &config
+            )
+            .unwrap(),
+            r#"<link rel="stylesheet" href="/print.css" media="print">"#
+        );
+        assert_eq!(
+            js(
+                &args(&[(
+                    "path",
+                    json!(["https://cdn.example.com/x.js", {"src": "a.js", "defer": true}])
+                )]),
+                &config
+            )
+            .unwrap(),
+            "<script src=\"https://cdn.example.com/x.js\"></script>\n<script src=\"/a.js\" defer></script>"
+        );
+
+        assert_eq!(
+            js(
+                &args(&[("path", json!("x.js")), ("defer", json!(true))]),
+                &config
+            )
+            .unwrap(),
+            r#"<script src="/x.js" defer></script>"#
+        );
+        assert_eq!(
+            css(
+                &args(&[
+                    ("path", json!(["a", {"href": "b", "media": "screen"}])),
+                    ("media", json!("print"))
+                ]),
+                &config
+            )
+            .unwrap(),
+            "<link rel=\"stylesheet\" href=\"/a.css\" media=\"print\">\n<link rel=\"stylesheet\" href=\"/b.css\" media=\"screen\">"
+        );
+
+        config.bundle.files.insert(
+            "js/all.js".to_string(),
+            vec!["js/a.js".to_string(), "js/b.js".to_string()],
+        );
+        assert_eq!(
+            js(&args(&[("path", json!(["js/a", "/js/b.js"]))]), &config).unwrap(),
+            r#"<script src="/js/all.js"></script>"#
+        );
+        assert!(js(&HashMap::new(), &config).is_err());
+    }
+}
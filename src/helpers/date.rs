@@ -0,0 +1,71 @@
+//! Date helpers
+
+use chrono::{DateTime, TimeZone};
+
+/// How long ago (or how far ahead) `date` is from `now` in words, with
+/// moment.js `fromNow()` thresholds and rounding (Hexo `relative_date`
+/// helper): "a few seconds ago", "5 minutes ago", "in 2 days", "a year ago"
+pub fn relative_date<A: TimeZone, B: TimeZone>(date: &DateTime<A>, now: &DateTime<B>) -> String {
+    let seconds = now.timestamp() - date.timestamp();
+    let past = seconds >= 0;
+    let seconds = seconds.unsigned_abs() as f64;
+
+    let minutes = (seconds / 60.0).round();
+    let hours = (seconds / 3600.0).round();
+    let days = (seconds / 86400.0).round();
+    let months = (seconds / 86400.0 / 30.4375).round();
+    let years = (seconds / 86400.0 / 365.25).round();
+
+    let amount = if seconds < 45.0 {
+        "a few seconds".to_string()
+    } else if seconds < 90.0 {
+        "a minute".to_string()
+    } else if minutes < 45.0 {
+        format!("{} minutes", minutes)
+    } else if minutes < 90.0 {
+        "an hour".to_string()
+    } else if hours < 22.0 {
+        format!("{} hours", hours)
+    } else if hours < 36.0 {
+        "a day".to_string()
+    } else if days < 26.0 {
+        format!("{} days", days)
+    } else if days < 45.0 {
+        "a month".to_string()
+    } else if days < 320.0 {
+        format!("{} months", months)
+    } else if days < 548.0 {
+        "a year".to_string()
+    } else {
+        format!("{} years", years)
+    };
+
+    if past {
+        format!("{} ago", amount)
+    } else {
+        format!("in {}", amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn test_relative_date() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let ago = |d: Duration| relative_date(&(now - d), &now);
+
+        assert_eq!(ago(Duration::seconds(10)), "a few seconds ago");
+        assert_eq!(ago(Duration::seconds(70)), "a minute ago");
+        assert_eq!(ago(Duration::minutes(20)), "20 minutes ago");
+        assert_eq!(ago(Duration::hours(5)), "5 hours ago");
+        assert_eq!(ago(Duration::hours(30)), "a day ago");
+        assert_eq!(ago(Duration::days(3)), "3 days ago");
+        assert_eq!(ago(Duration::days(100)), "3 months ago");
+        assert_eq!(ago(Duration::days(400)), "a year ago");
+        assert_eq!(ago(Duration::days(1000)), "3 years ago");
+        assert_eq!(ago(-Duration::days(2)), "in 2 days");
+    }
+}
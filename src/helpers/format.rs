@@ -0,0 +1,130 @@
+//! String formatting helpers
+
+/// Words kept lowercase by [`titlecase`] unless they start or end the title
+const SMALL_WORDS: &[&str] = &[
+    "a", "an", "and", "as", "at", "but", "by", "en", "for", "if", "in", "nor", "of", "on", "or",
+    "per", "the", "to", "v", "via", "vs",
+];
+
+/// Format a number with a thousands `delimiter` and decimal `separator`,
+/// rounded to `precision` digits if given (Hexo `number_format` helper)
+pub fn number_format(
+    number: f64,
+    precision: Option<usize>,
+    delimiter: &str,
+    separator: &str,
+) -> String {
+    let formatted = match precision {
+        Some(precision) => format!("{:.*}", precision, number),
+        None => number.to_string(),
+    };
+    let (sign, unsigned) = match formatted.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", formatted.as_str()),
+    };
+    let (integer, fraction) = match unsigned.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (unsigned, None),
+    };
+
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push_str(delimiter);
+        }
+        grouped.push(digit);
+    }
+    match fraction {
+        Some(fraction) => format!("{}{}{}{}", sign, grouped, separator, fraction),
+        None => format!("{}{}", sign, grouped),
+    }
+}
+
+/// Title case: capitalize words except short conjunctions, articles and
+/// prepositions in the middle (Hexo `titlecase` helper). Words that already
+/// contain capitals, like "iPhone" or "URLs", are kept as written.
+pub fn titlecase(text: &str) -> String {
+    let words: Vec<&str> = text.split(' ').collect();
+    let last = words.len().saturating_sub(1);
+    words
+        .iter()
+        .enumerate()
+        .map(|(i, word)| {
+            let lower = word.to_lowercase();
+            if word.chars().skip(1).any(char::is_uppercase) {
+                word.to_string()
+            } else if i != 0 && i != last && SMALL_WORDS.contains(&lower.as_str()) {
+                lower
+            } else {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Wrap text into lines of at most `width` characters, breaking at spaces
+/// (Hexo `word_wrap` helper). Words longer than `width` get a line of their own.
+pub fn word_wrap(text: &str, width: usize) -> String {
+    text.lines()
+        .map(|line| {
+            let mut lines: Vec<String> = Vec::new();
+            let mut current = String::new();
+            for word in line.split_whitespace() {
+                let len = current.chars().count();
+                if len > 0 && len + 1 + word.chars().count() > width {
+                    lines.push(std::mem::take(&mut current));
+                }
+                if !current.is_empty() {
+                    current.push(' ');
+                }
+                current.push_str(word);
+            }
+            lines.push(current);
+            lines.join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A byte count for people, e.g. "1.5 MB"
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(
+            number_format(1234567.891, Some(2), ",", "."),
+            "1,234,567.89"
+        );
+        assert_eq!(number_format(-1234.0, None, ".", ","), "-1.234");
+        assert_eq!(number_format(12.5, None, ",", "."), "12.5");
+
+        assert_eq!(
+            titlecase("the lord of the rings and iPhone URLs"),
+            "The Lord of the Rings and iPhone URLs"
+        );
+        assert_eq!(titlecase("what to"), "What To");
+
+        assert_eq!(
+            word_wrap("Once upon a time in a galaxy", 11),
+            "Once upon a\ntime in a\ngalaxy"
+        );
+
+        assert_eq!(format_size(10), "10 B");
+        assert_eq!(format_size(3 * 1_048_576 / 2), "1.5 MB");
+    }
+}
@@ -0,0 +1,216 @@
+//! Link helpers
+
+use super::html_escape;
+use crate::config::SiteConfig;
+
+/// `rel` tokens that may be set on generated links
+const ALLOWED_REL: &[&str] = &[
+    "nofollow",
+    "sponsored",
+    "ugc",
+    "noopener",
+    "noreferrer",
+    "external",
+    "me",
+];
+
+/// Build a `rel` attribute value from an entry's `rel` setting (a string or a
+/// list of strings), falling back to `default` when the entry has none.
+/// Unknown tokens are dropped and external links always get `noopener`.
+pub fn link_rel(rel: Option<&serde_yaml::Value>, default: &str, external: bool) -> String {
+    let tokens: Vec<String> = match rel {
+        Some(serde_yaml::Value::String(s)) => s.split_whitespace().map(String::from).collect(),
+        Some(serde_yaml::Value::Sequence(seq)) => seq
+            .iter()
+            .filter_map(|v| v.as_str())
+            .flat_map(|s| s.split_whitespace())
+            .map(String::from)
+            .collect(),
+        _ => default.split_whitespace().map(String::from).collect(),
+    };
+
+    let mut result: Vec<String> = Vec::new();
+    for token in tokens {
+        let token = token.to_lowercase();
+        if ALLOWED_REL.contains(&token.as_str()) && !result.contains(&token) {
+            result.push(token);
+        }
+    }
+    if external && !result.iter().any(|t| t == "noopener") {
+        result.push("noopener".to_string());
+    }
+
+    result.join(" ")
+}
+
+/// Render an anchor tag (Hexo `link_to` helper). The text is escaped.
+pub fn link_to(url: &str, text: &str, rel: &str) -> String {
+    let external = is_external(url);
+    let target = if external { " target=\"_blank\"" } else { "" };
+    let rel_attr = if rel.is_empty() {
+        String::new()
+    } else {
+        format!(" rel=\"{}\"", html_escape(rel))
+    };
+    format!(
+        "<a href=\"{}\"{}{}>{}</a>",
+        html_escape(url),
+        target,
+        rel_attr,
+        html_escape(text)
+    )
+}
+
+/// Whether a URL points to another site
+pub fn is_external(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://") || url.starts_with("//")
+}
+
+/// URL of a site path under the configured root (Hexo `url_for` helper).
+/// `index.html` and `.html` endings are dropped as `pretty_urls` asks. With
+/// `relative_link`, the URL is relative to `from`, the current page's path.
+pub fn url_for(path: &str, config: &SiteConfig, from: Option<&str>) -> String {
+    if is_external(path) || path.starts_with('#') || path.contains(':') {
+        return path.to_string();
+    }
+    let url = pretty_url(&site_path(path, config), config);
+    match from {
+        Some(from) if config.relative_link => relative_url(&site_path(from, config), &url),
+        _ => url,
+    }
+}
+
+/// Absolute URL of a site path (Hexo `full_url_for` helper)
+pub fn full_url_for(path: &str, config: &SiteConfig) -> String {
+    if is_external(path) {
+        return path.to_string();
+    }
+    format!(
+        "{}{}",
+        config.url.trim_end_matches('/'),
+        pretty_url(&site_path(path, config), config)
+    )
+}
+
+/// `<link rel="canonical">` for a site path, or the given full URL
+pub fn canonical(path: &str, config: &SiteConfig) -> String {
+    format!(
+        r#"<link rel="canonical" href="{}">"#,
+        html_escape(&full_url_for(path, config))
+    )
+}
+
+/// Path from the page at `from` to `to`, e.g. `../../css/style.css`
+pub fn relative_url(from: &str, to: &str) -> String {
+    let from: Vec<&str> = from.split('/').collect();
+    let to: Vec<&str> = to.split('/').collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<&str> = vec![".."; from.len().saturating_sub(common + 1)];
+    parts.extend(&to[common..]);
+    let url = parts.join("/");
+    if url.is_empty() {
+        "./".to_string()
+    } else {
+        url
+    }
+}
+
+/// `path` under the site root, with a leading slash
+fn site_path(path: &str, config: &SiteConfig) -> String {
+    let root = format!("/{}/", config.root.trim_matches('/')).replace("//", "/");
+    if path.starts_with(&root) {
+        path.to_string()
+    } else {
+        format!("{}{}", root, path.trim_start_matches('/'))
+    }
+}
+
+/// Drop `index.html`/`.html` endings as configured by `pretty_urls`
+fn pretty_url(url: &str, config: &SiteConfig) -> String {
+    let mut url = url;
+    if !config.pretty_urls.trailing_index {
+        url = url.strip_suffix("index.html").unwrap_or(url);
+    }
+    if !config.pretty_urls.trailing_html {
+        url = url.strip_suffix(".html").unwrap_or(url);
+    }
+    url.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_rel() {
+        let entry = serde_yaml::Value::String("sponsored bogus".to_string());
+        assert_eq!(
+            link_rel(Some(&entry), "nofollow", true),
+            "sponsored noopener"
+        );
+        assert_eq!(
+            link_rel(None, "nofollow ugc", true),
+            "nofollow ugc noopener"
+        );
+        assert_eq!(link_rel(None, "", false), "");
+    }
+
+    #[test]
+    fn test_link_to() {
+        assert_eq!(
+            link_to("https://example.com", "Example", "nofollow noopener"),
+            r#"<a href="https://example.com" target="_blank" rel="nofollow noopener">Example</a>"#
+        );
+        assert_eq!(
+            link_to("/about/", "About", ""),
+            r#"<a href="/about/">About</a>"#
+        );
+        assert_eq!(
+            link_to("/q/", "<b>Q&A</b>", ""),
+            r#"<a href="/q/">&lt;b&gt;Q&amp;A&lt;/b&gt;</a>"#
+        );
+    }
+
+    #[test]
+    fn test_url_for() {
+        let mut config = SiteConfig {
+            url: "https://example.com".to_string(),
+            root: "/blog/".to_string(),
+            ..Default::default()
+        };
+        config.pretty_urls.trailing_index = false;
+        config.pretty_urls.trailing_html = false;
+
+        assert_eq!(
+            url_for("css/style.css", &config, None),
+            "/blog/css/style.css"
+        );
+        assert_eq!(
+            url_for("/blog/about/index.html", &config, None),
+            "/blog/about/"
+        );
+        assert_eq!(
+            url_for("notes/today.html", &config, None),
+            "/blog/notes/today"
+        );
+        assert_eq!(
+            url_for("https://x.org/a.js", &config, None),
+            "https://x.org/a.js"
+        );
+        assert_eq!(
+            full_url_for("2024/hello/", &config),
+            "https://example.com/blog/2024/hello/"
+        );
+        assert_eq!(
+            canonical("/", &config),
+            r#"<link rel="canonical" href="https://example.com/blog/">"#
+        );
+
+        config.relative_link = true;
+        assert_eq!(
+            url_for("css/style.css", &config, Some("2024/hello/")),
+            "../../css/style.css"
+        );
+        assert_eq!(url_for("2024/hello/", &config, Some("2024/hello/")), "./");
+    }
+}
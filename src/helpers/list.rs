@@ -1,44 +1,48 @@
-//! Table of contents generator
+//! Table of contents and post list helpers
 
-/// Table of contents generator
-pub fn toc(content: &str, max_depth: usize) -> String {
-    // Collect all headings first
-    let mut headings: Vec<(usize, String, String)> = Vec::new(); // (level, id, text)
-
-    let mut i = 0;
-    let chars: Vec<char> = content.chars().collect();
-
-    while i < chars.len() {
-        // Look for <h1>, <h2>, etc.
-        if chars[i] == '<' && i + 3 < chars.len() && chars[i + 1] == 'h' {
-            if let Some(level) = chars[i + 2].to_digit(10) {
-                let level = level as usize;
-                if level <= max_depth {
-                    // Find the closing >
-                    if let Some(start) = chars[i..].iter().position(|&c| c == '>') {
-                        let start = i + start + 1;
-                        // Find </h{level}>
-                        let end_tag = format!("</h{}>", level);
-                        let end_chars: Vec<char> = end_tag.chars().collect();
+use serde_json::Value;
 
-                        if let Some(end) = find_sequence(&chars[start..], &end_chars) {
-                            let heading: String = chars[start..start + end].iter().collect();
-                            let heading = strip_tags(&heading);
-                            let id = generate_heading_id(&heading);
-                            headings.push((level, id, heading));
+/// Options of the `toc()` helper
+#[derive(Debug, Clone)]
+pub struct TocOptions {
+    /// Class of the list; items, links and numbers use it as a prefix
+    pub class: String,
+    /// Prefix entries with their number, e.g. "1.2."
+    pub list_number: bool,
+    pub min_depth: usize,
+    pub max_depth: usize,
+}
 
-                            i = start + end + end_chars.len();
-                            continue;
-                        }
-                    }
-                }
-            }
+impl Default for TocOptions {
+    fn default() -> Self {
+        Self {
+            class: "toc".to_string(),
+            list_number: true,
+            min_depth: 1,
+            max_depth: 6,
         }
-        i += 1;
     }
+}
+
+/// Table of contents of headings up to `max_depth`, without numbers
+pub fn toc(content: &str, max_depth: usize) -> String {
+    toc_with(
+        content,
+        &TocOptions {
+            list_number: false,
+            max_depth,
+            ..Default::default()
+        },
+    )
+}
+
+/// Table of contents generator (Hexo `toc` helper)
+pub fn toc_with(content: &str, options: &TocOptions) -> String {
+    let class = &options.class;
+    let headings = headings(content, options.min_depth, options.max_depth);
 
     if headings.is_empty() {
-        return r#"<ol class="toc"></ol>"#.to_string();
+        return format!(r#"<ol class="{}"></ol>"#, class);
     }
 
     // Find the minimum level to use as base
@@ -47,8 +51,10 @@ pub fn toc(content: &str, max_depth: usize) -> String {
     // Build properly nested TOC
     let mut html = String::new();
     let mut current_level = min_level;
+    // Entry number at each level below the base, for list numbers
+    let mut numbers: Vec<usize> = vec![0];
 
-    html.push_str(r#"<ol class="toc">"#);
+    html.push_str(&format!(r#"<ol class="{}">"#, class));
 
     for (idx, (level, id, text)) in headings.iter().enumerate() {
         let level = *level;
@@ -57,21 +63,32 @@ pub fn toc(content: &str, max_depth: usize) -> String {
         while current_level > level {
             html.push_str("</ol></li>");
             current_level -= 1;
+            numbers.pop();
         }
 
         // Open child lists for levels going down
         while current_level < level {
             // Don't close the previous li, add child ol inside it
-            html.push_str("<ol>");
+            html.push_str(&format!(r#"<ol class="{}-child">"#, class));
             current_level += 1;
+            numbers.push(0);
+        }
+        if let Some(last) = numbers.last_mut() {
+            *last += 1;
         }
 
         // Check if next heading is a child (deeper level)
         let has_children = idx + 1 < headings.len() && headings[idx + 1].0 > level;
 
+        let number = if options.list_number {
+            let number: String = numbers.iter().map(|n| format!("{}.", n)).collect();
+            format!(r#"<span class="{}-number">{}</span> "#, class, number)
+        } else {
+            String::new()
+        };
         html.push_str(&format!(
-            "<li class=\"toc-item toc-level-{}\"><a class=\"toc-link\" href=\"#{}\"><span class=\"toc-text\">{}</span></a>",
-            level, id, text
+            "<li class=\"{class}-item {class}-level-{}\"><a class=\"{class}-link\" href=\"#{}\">{}<span class=\"{class}-text\">{}</span></a>",
+            level, id, number, text
         ));
 
         // Only close li if no children follow
@@ -90,6 +107,156 @@ pub fn toc(content: &str, max_depth: usize) -> String {
     html
 }
 
+/// Headings between `min_depth` and `max_depth` as (level, id, text). The
+/// id is the heading's `id` attribute, or generated from its text.
+fn headings(content: &str, min_depth: usize, max_depth: usize) -> Vec<(usize, String, String)> {
+    let mut headings = Vec::new();
+
+    let mut i = 0;
+    let chars: Vec<char> = content.chars().collect();
+
+    while i < chars.len() {
+        // Look for <h1>, <h2>, etc.
+        if chars[i] == '<' && i + 3 < chars.len() && chars[i + 1] == 'h' {
+            if let Some(level) = chars[i + 2].to_digit(10) {
+                let level = level as usize;
+                if (min_depth..=max_depth).contains(&level) {
+                    // Find the closing >
+                    if let Some(start) = chars[i..].iter().position(|&c| c == '>') {
+                        let open_tag: String = chars[i..i + start].iter().collect();
+                        let start = i + start + 1;
+                        // Find </h{level}>
+                        let end_tag = format!("</h{}>", level);
+                        let end_chars: Vec<char> = end_tag.chars().collect();
+
+                        if let Some(end) = find_sequence(&chars[start..], &end_chars) {
+                            let heading: String = chars[start..start + end].iter().collect();
+                            let heading = strip_tags(&heading);
+                            let id = id_attribute(&open_tag)
+                                .unwrap_or_else(|| generate_heading_id(&heading));
+                            headings.push((level, id, heading));
+
+                            i = start + end + end_chars.len();
+                            continue;
+                        }
+                    }
+                }
+            }
+        }
+        i += 1;
+    }
+    headings
+}
+
+/// Value of the `id` attribute in an opening tag
+fn id_attribute(tag: &str) -> Option<String> {
+    let start = tag.find(" id=\"")? + 5;
+    let len = tag[start..].find('"')?;
+    Some(tag[start..start + len].to_string())
+}
+
+/// Options of the `list_posts()` helper
+#[derive(Debug, Clone)]
+pub struct ListPostsOptions {
+    /// Number of posts to list, 0 for all
+    pub amount: usize,
+    /// Post field to sort by
+    pub orderby: String,
+    /// 1 for ascending, -1 for descending
+    pub order: i64,
+    /// `list` for a `<ul>`, anything else for links joined by `separator`
+    pub style: String,
+    pub separator: String,
+    /// Class prefix of the list, items and links
+    pub class: String,
+}
+
+impl Default for ListPostsOptions {
+    fn default() -> Self {
+        Self {
+            amount: 6,
+            orderby: "date".to_string(),
+            order: -1,
+            style: "list".to_string(),
+            separator: ", ".to_string(),
+            class: "post".to_string(),
+        }
+    }
+}
+
+/// Links to posts (Hexo `list_posts` helper)
+pub fn list_posts(posts: &[Value], options: &ListPostsOptions) -> String {
+    let mut posts: Vec<&Value> = posts.iter().collect();
+    posts.sort_by(|a, b| {
+        let ordering = compare_field(a, b, &options.orderby);
+        if options.order < 0 {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    if options.amount > 0 {
+        posts.truncate(options.amount);
+    }
+
+    let class = &options.class;
+    let links = posts.iter().map(|post| {
+        format!(
+            r#"<a class="{}-list-link" href="{}">{}</a>"#,
+            class,
+            post["path"].as_str().unwrap_or_default(),
+            post["title"].as_str().unwrap_or_default()
+        )
+    });
+    if options.style != "list" {
+        return links.collect::<Vec<_>>().join(&options.separator);
+    }
+
+    let items: String = links
+        .map(|link| format!(r#"<li class="{}-list-item">{}</li>"#, class, link))
+        .collect();
+    format!(r#"<ul class="{}-list">{}</ul>"#, class, items)
+}
+
+/// The `amount` newest posts
+pub fn recent_posts(posts: &[Value], amount: usize) -> Vec<Value> {
+    let mut posts = posts.to_vec();
+    posts.sort_by(|a, b| compare_field(b, a, "date"));
+    posts.truncate(amount);
+    posts
+}
+
+/// Number of posts, or of posts published in `year` (and `month`)
+pub fn archive_count(posts: &[Value], year: Option<i32>, month: Option<u32>) -> usize {
+    let prefix = match (year, month) {
+        (Some(year), Some(month)) => format!("{:04}-{:02}-", year, month),
+        (Some(year), None) => format!("{:04}-", year),
+        _ => String::new(),
+    };
+    posts
+        .iter()
+        .filter(|post| {
+            post["date"]
+                .as_str()
+                .is_some_and(|date| date.starts_with(&prefix))
+        })
+        .count()
+}
+
+/// Order two posts by a string or number field
+fn compare_field(a: &Value, b: &Value, field: &str) -> std::cmp::Ordering {
+    match (&a[field], &b[field]) {
+        (Value::Number(x), Value::Number(y)) => x
+            .as_f64()
+            .partial_cmp(&y.as_f64())
+            .unwrap_or(std::cmp::Ordering::Equal),
+        (x, y) => x
+            .as_str()
+            .unwrap_or_default()
+            .cmp(y.as_str().unwrap_or_default()),
+    }
+}
+
 fn find_sequence(haystack: &[char], needle: &[char]) -> Option<usize> {
     'outer: for i in 0..haystack.len() {
         if i + needle.len() > haystack.len() {
@@ -142,3 +309,65 @@ fn generate_heading_id(text: &str) -> String {
         .collect::<Vec<_>>()
         .join("-")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toc_options() {
+        let content =
+            r#"<h1>Title</h1><h2 id="intro">Intro</h2><h3>Setup Steps</h3><h2>Usage</h2>"#;
+        let options = TocOptions {
+            class: "nav".to_string(),
+            min_depth: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            toc_with(content, &options),
+            concat!(
+                r##"<ol class="nav"><li class="nav-item nav-level-2"><a class="nav-link" href="#intro"><span class="nav-number">1.</span> <span class="nav-text">Intro</span></a>"##,
+                r##"<ol class="nav-child"><li class="nav-item nav-level-3"><a class="nav-link" href="#Setup-Steps"><span class="nav-number">1.1.</span> <span class="nav-text">Setup Steps</span></a></li></ol></li>"##,
+                r##"<li class="nav-item nav-level-2"><a class="nav-link" href="#Usage"><span class="nav-number">2.</span> <span class="nav-text">Usage</span></a></li></ol>"##,
+            )
+        );
+        assert!(!toc(content, 2).contains("Setup"));
+    }
+
+    #[test]
+    fn test_post_lists() {
+        let posts = vec![
+            serde_json::json!({ "title": "B", "path": "/b/", "date": "2024-03-01" }),
+            serde_json::json!({ "title": "A", "path": "/a/", "date": "2023-12-31" }),
+            serde_json::json!({ "title": "C", "path": "/c/", "date": "2024-03-15" }),
+        ];
+        let options = ListPostsOptions {
+            amount: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            list_posts(&posts, &options),
+            concat!(
+                r#"<ul class="post-list"><li class="post-list-item"><a class="post-list-link" href="/c/">C</a></li>"#,
+                r#"<li class="post-list-item"><a class="post-list-link" href="/b/">B</a></li></ul>"#
+            )
+        );
+        let options = ListPostsOptions {
+            orderby: "title".to_string(),
+            order: 1,
+            style: "none".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            list_posts(&posts, &options),
+            r#"<a class="post-list-link" href="/a/">A</a>, <a class="post-list-link" href="/b/">B</a>, <a class="post-list-link" href="/c/">C</a>"#
+        );
+
+        let recent = recent_posts(&posts, 1);
+        assert_eq!(recent[0]["title"], "C");
+        assert_eq!(archive_count(&posts, None, None), 3);
+        assert_eq!(archive_count(&posts, Some(2024), None), 2);
+        assert_eq!(archive_count(&posts, Some(2024), Some(3)), 2);
+        assert_eq!(archive_count(&posts, Some(2023), Some(1)), 0);
+    }
+}
@@ -1,7 +1,24 @@
 //! Helper functions for templates
 //!
-//! Currently provides the `toc()` function for generating table of contents.
+//! Provides the `toc()` function for generating table of contents, link and
+//! asset tag helpers, Open Graph meta tags, relative dates, text formatting and word counting used by the Tera templates.
 
+mod asset;
+mod date;
+mod escape;
+mod format;
+mod link;
 mod list;
+mod open_graph;
+mod wordcount;
 
-pub use list::toc;
+pub use asset::{css, js};
+pub use date::relative_date;
+pub use escape::{html_escape, url_encode};
+pub use format::{format_size, number_format, titlecase, word_wrap};
+pub use link::{canonical, full_url_for, is_external, link_rel, link_to, relative_url, url_for};
+pub use list::{
+    archive_count, list_posts, recent_posts, toc, toc_with, ListPostsOptions, TocOptions,
+};
+pub use open_graph::open_graph;
+pub use wordcount::{reading_time, strip_html, word_count, WordCount};
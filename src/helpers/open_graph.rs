@@ -0,0 +1,264 @@
+//! Open Graph and Twitter card meta tags, like Hexo's `open_graph` helper
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use super::{full_url_for, html_escape, is_external, strip_html};
+use crate::config::SiteConfig;
+
+/// Characters kept from the page text for `og:description`
+const DESCRIPTION_LENGTH: usize = 200;
+
+/// Meta tags for a page. `args` holds the template arguments: `page` (the
+/// page variables) plus options overriding what is derived from it: `title`,
+/// `type`, `url`, `image`, `site_name`, `description`, `locale`, `date`,
+/// `updated`, `author`, `tags`, `twitter_card`, `twitter_id`, `twitter_site`,
+/// `twitter_image`, `fb_admins` and `fb_app_id`.
+pub fn open_graph(args: &HashMap<String, Value>, config: &SiteConfig) -> String {
+    let empty = serde_json::Map::new();
+    let page = args
+        .get("page")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+    let option = |key: &str| {
+        args.get(key)
+            .and_then(Value::as_str)
+            .filter(|s| !s.is_empty())
+    };
+    let page_str = |key: &str| {
+        page.get(key)
+            .and_then(Value::as_str)
+            .filter(|s| !s.is_empty())
+    };
+
+    // Only posts have a full publication date
+    let is_article = page.contains_key("date_iso");
+    let og_type = option("type").unwrap_or(if is_article { "article" } else { "website" });
+    let title = option("title")
+        .or_else(|| page_str("title"))
+        .unwrap_or(&config.title);
+    let url = option("url")
+        .map(String::from)
+        .or_else(|| page_str("permalink").map(String::from))
+        .or_else(|| page_str("path").map(|p| full_url_for(p, config)))
+        .unwrap_or_else(|| full_url_for("/", config));
+    let site_name = option("site_name").unwrap_or(&config.title);
+    let description = option("description")
+        .or_else(|| page_str("description"))
+        .map(|d| truncate(&strip_html(d), DESCRIPTION_LENGTH))
+        .unwrap_or_else(|| config.description.clone());
+    let locale = option("locale")
+        .or_else(|| page_str("lang"))
+        .unwrap_or(&config.language)
+        .replace('-', "_");
+
+    let images: Vec<String> = match args.get("image") {
+        Some(image) => strings(image),
+        None => page
+            .get("photos")
+            .map(strings)
+            .filter(|photos| !photos.is_empty())
+            .or_else(|| page.get("cover").map(strings))
+            .unwrap_or_default(),
+    }
+    .iter()
+    .map(|image| image_url(image, &url, config))
+    .collect();
+
+    let keywords: Vec<String> = match args.get("tags").or_else(|| page.get("tags")) {
+        Some(tags) => strings(tags),
+        None => config.keywords.clone().unwrap_or_default(),
+    };
+
+    let mut tags = Vec::new();
+    let mut meta = |attr: &str, key: &str, content: &str| {
+        if !content.is_empty() {
+            tags.push(format!(
+                "<meta {}=\"{}\" content=\"{}\">",
+                attr,
+                key,
+                html_escape(content)
+            ));
+        }
+    };
+
+    meta("name", "description", &description);
+    meta("name", "keywords", &keywords.join(","));
+    meta("property", "og:type", og_type);
+    meta("property", "og:title", title);
+    meta("property", "og:url", &url);
+    meta("property", "og:site_name", site_name);
+    meta("property", "og:description", &description);
+    meta("property", "og:locale", &locale);
+    for image in &images {
+        meta("property", "og:image", image);
+    }
+    if is_article {
+        meta(
+            "property",
+            "article:published_time",
+            option("date")
+                .or_else(|| page_str("date_iso"))
+                .unwrap_or(""),
+        );
+        meta(
+            "property",
+            "article:modified_time",
+            option("updated")
+                .or_else(|| page_str("updated"))
+                .unwrap_or(""),
+        );
+        meta(
+            "property",
+            "article:author",
+            option("author").unwrap_or(&config.author),
+        );
+        for tag in &keywords {
+            meta("property", "article:tag", tag);
+        }
+    }
+
+    meta(
+        "name",
+        "twitter:card",
+        option("twitter_card").unwrap_or("summary"),
+    );
+    let twitter_image = option("twitter_image")
+        .map(|image| image_url(image, &url, config))
+        .or_else(|| images.first().cloned())
+        .unwrap_or_default();
+    meta("name", "twitter:image", &twitter_image);
+    meta("name", "twitter:title", title);
+    meta("name", "twitter:description", &description);
+    if let Some(id) = option("twitter_id") {
+        meta("name", "twitter:creator", &twitter_handle(id));
+    }
+    if let Some(site) = option("twitter_site") {
+        meta("name", "twitter:site", &twitter_handle(site));
+    }
+    if let Some(admins) = option("fb_admins") {
+        meta("property", "fb:admins", admins);
+    }
+    if let Some(app_id) = option("fb_app_id") {
+        meta("property", "fb:app_id", app_id);
+    }
+
+    tags.join("\n")
+}
+
+/// Full URL of an image. Relative paths, like a cover next to the post's
+/// `index.md`, are resolved against the page URL rather than the site root.
+fn image_url(image: &str, page_url: &str, config: &SiteConfig) -> String {
+    if is_external(image) || image.starts_with('/') || image.contains(':') {
+        return full_url_for(image, config);
+    }
+    let dir = match page_url.rfind('/') {
+        Some(end) => &page_url[..=end],
+        None => page_url,
+    };
+    format!("{}{}", dir, image.trim_start_matches("./"))
+}
+
+/// A string or list of strings
+fn strings(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) if !s.is_empty() => vec![s.clone()],
+        Value::Array(items) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Twitter handles are written with a leading `@`
+fn twitter_handle(id: &str) -> String {
+    if id.starts_with('@') {
+        id.to_string()
+    } else {
+        format!("@{}", id)
+    }
+}
+
+/// Collapse whitespace and cut to `max` characters
+fn truncate(text: &str, max: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}...", text[..end].trim_end()),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_open_graph() {
+        let config = SiteConfig {
+            title: "Blog".to_string(),
+            url: "https://example.com".to_string(),
+            language: "en-US".to_string(),
+            author: "Ann".to_string(),
+            ..Default::default()
+        };
+        let args: HashMap<String, Value> = [
+            (
+                "page".to_string(),
+                json!({
+                    "title": "Hello \"World\"",
+                    "date_iso": "2024-01-02T10:00:00+08:00",
+                    "permalink": "https://example.com/2024/hello/",
+                    "description": "<p>First   post</p>",
+                    "cover": "/images/cover.png",
+                    "tags": ["rust", "hexo"],
+                }),
+            ),
+            ("twitter_id".to_string(), json!("ann")),
+            ("twitter_card".to_string(), json!("summary_large_image")),
+        ]
+        .into_iter()
+        .collect();
+
+        let html = open_graph(&args, &config);
+        assert!(html.contains(r#"<meta property="og:type" content="article">"#));
+        assert!(html.contains(r#"<meta property="og:title" content="Hello &quot;World&quot;">"#));
+        assert!(html.contains(r#"<meta property="og:description" content="First post">"#));
+        assert!(html.contains(r#"<meta property="og:locale" content="en_US">"#));
+        assert!(html.contains(
+            r#"<meta property="og:image" content="https://example.com/images/cover.png">"#
+        ));
+        assert!(html.contains(
+            r#"<meta property="article:published_time" content="2024-01-02T10:00:00+08:00">"#
+        ));
+        assert!(html.contains(r#"<meta property="article:tag" content="hexo">"#));
+        assert!(html.contains(r#"<meta name="twitter:card" content="summary_large_image">"#));
+        assert!(html.contains(r#"<meta name="twitter:creator" content="@ann">"#));
+        assert!(!html.contains("article:modified_time"));
+
+        let args: HashMap<String, Value> = [(
+            "page".to_string(),
+            json!({
+                "permalink": "https://example.com/2024/hello/",
+                "cover": "./cover.png",
+            }),
+        )]
+        .into_iter()
+        .collect();
+        let html = open_graph(&args, &config);
+        assert!(html.contains(
+            r#"<meta property="og:image" content="https://example.com/2024/hello/cover.png">"#
+        ));
+        assert!(html.contains(
+            r#"<meta name="twitter:image" content="https://example.com/2024/hello/cover.png">"#
+        ));
+
+        let html = open_graph(&HashMap::new(), &config);
+        assert!(html.contains(r#"<meta property="og:type" content="website">"#));
+        assert!(html.contains(r#"<meta property="og:url" content="https://example.com/">"#));
+    }
+}
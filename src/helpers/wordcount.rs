@@ -0,0 +1,174 @@
+//! Word counting for mixed CJK and Latin text
+
+/// Reading speed for CJK text, in characters per minute
+const CJK_PER_MINUTE: usize = 300;
+
+/// Reading speed for Latin text, in words per minute
+const LATIN_PER_MINUTE: usize = 160;
+
+/// Word count of a piece of content, split by script
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WordCount {
+    /// CJK characters (each one counts as a word)
+    pub cjk: usize,
+    /// Latin (and other space-separated) words
+    pub latin: usize,
+}
+
+impl WordCount {
+    /// Count the words of rendered HTML
+    pub fn of_html(html: &str) -> Self {
+        Self::of_text(&strip_html(html))
+    }
+
+    /// Count the words of plain text
+    pub fn of_text(text: &str) -> Self {
+        let mut count = Self::default();
+        let mut in_word = false;
+
+        for c in text.chars() {
+            if is_cjk(c) {
+                count.cjk += 1;
+                in_word = false;
+            } else if c.is_alphanumeric() {
+                if !in_word {
+                    count.latin += 1;
+                    in_word = true;
+                }
+            } else if !(in_word && matches!(c, '\'' | '’' | '-' | '_')) {
+                // Apostrophes and hyphens inside a word don't split it
+                in_word = false;
+            }
+        }
+
+        count
+    }
+
+    pub fn total(&self) -> usize {
+        self.cjk + self.latin
+    }
+
+    /// Estimated reading time in minutes (at least 1)
+    pub fn reading_minutes(&self) -> usize {
+        let minutes =
+            self.cjk as f64 / CJK_PER_MINUTE as f64 + self.latin as f64 / LATIN_PER_MINUTE as f64;
+        (minutes.ceil() as usize).max(1)
+    }
+}
+
+/// Count the words of rendered HTML (CJK characters plus Latin words)
+pub fn word_count(html: &str) -> usize {
+    WordCount::of_html(html).total()
+}
+
+/// Estimated reading time of rendered HTML, in minutes
+pub fn reading_time(html: &str) -> usize {
+    WordCount::of_html(html).reading_minutes()
+}
+
+/// Strip tags, `<script>`/`<style>` bodies and entities from HTML
+pub fn strip_html(html: &str) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '<' => {
+                let skip_to = if starts_with_ci(rest, "<script") {
+                    find_ci(rest, "</script>")
+                } else if starts_with_ci(rest, "<style") {
+                    find_ci(rest, "</style>")
+                } else {
+                    None
+                };
+                let end = skip_to.or_else(|| rest.find('>').map(|i| i + 1));
+                rest = &rest[end.unwrap_or(rest.len())..];
+                // Tags separate words (`<p>a</p><p>b</p>` is two words)
+                result.push(' ');
+            }
+            '&' => match rest.find(';').filter(|&i| i <= 10) {
+                Some(end) => {
+                    result.push_str(decode_entity(&rest[1..end]));
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    result.push('&');
+                    rest = &rest[1..];
+                }
+            },
+            _ => {
+                result.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    result
+}
+
+/// Whether `s` starts with the ASCII `prefix`, ignoring case
+fn starts_with_ci(s: &str, prefix: &str) -> bool {
+    s.get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+/// Byte offset just past the (ASCII case-insensitive) ASCII `needle`
+fn find_ci(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+        .map(|i| i + needle.len())
+}
+
+fn decode_entity(entity: &str) -> &'static str {
+    match entity {
+        "amp" => "&",
+        "lt" => "<",
+        "gt" => ">",
+        "quot" => "\"",
+        "apos" | "#39" => "'",
+        _ => " ",
+    }
+}
+
+/// CJK ideographs, kana and hangul count as one word per character
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'     // Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}'   // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}'   // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}'   // Hangul Syllables
+        | '\u{F900}'..='\u{FAFF}'   // CJK Compatibility Ideographs
+        | '\u{20000}'..='\u{2EBEF}' // CJK Extensions B-F
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_cjk_and_latin() {
+        let count = WordCount::of_html("<p>用 Rust 写的静态博客 generator, it's fast&nbsp;!</p>");
+        assert_eq!(count.cjk, 7);
+        assert_eq!(count.latin, 4);
+        assert_eq!(word_count("<p>one</p><p>two</p>"), 2);
+    }
+
+    #[test]
+    fn test_strip_html_skips_scripts() {
+        let text = strip_html("a<script>var x = 1;</script>b<STYLE>p{}</STYLE>c &amp; d");
+        assert_eq!(WordCount::of_text(&text).latin, 4);
+        // Multi-byte text right after `<`, mixed-case tags
+        assert_eq!(strip_html("x <中文> y").split_whitespace().count(), 2);
+        assert_eq!(strip_html("a<Script src=x>1</sCRIPT>b"), "a b");
+    }
+
+    #[test]
+    fn test_reading_time() {
+        assert_eq!(reading_time(""), 1);
+        assert_eq!(reading_time(&"word ".repeat(320)), 2);
+        assert_eq!(reading_time(&"字".repeat(600)), 2);
+    }
+}
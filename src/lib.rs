@@ -3,6 +3,7 @@
 //! This crate provides a Rust implementation of a static site generator
 //! that uses Tera templates with an embedded vexo theme for rendering.
 
+pub mod cache;
 pub mod commands;
 pub mod config;
 pub mod content;
@@ -28,6 +29,8 @@ pub struct Hexo {
     pub public_dir: std::path::PathBuf,
     /// Theme directory
     pub theme_dir: std::path::PathBuf,
+    /// Custom template filters, functions and testers
+    pub templates: templates::TemplateExtensions,
 }
 
 impl Hexo {
@@ -36,11 +39,12 @@ impl Hexo {
         let base_dir = base_dir.as_ref().to_path_buf();
         let config_path = base_dir.join("_config.yml");
 
-        let config = if config_path.exists() {
+        let mut config = if config_path.exists() {
             config::SiteConfig::load(&config_path)?
         } else {
             config::SiteConfig::default()
         };
+        config.load_theme_override(&base_dir)?;
 
         let source_dir = base_dir.join(&config.source_dir);
         let public_dir = base_dir.join(&config.public_dir);
@@ -52,9 +56,25 @@ impl Hexo {
             source_dir,
             public_dir,
             theme_dir,
+            templates: templates::TemplateExtensions::default(),
         })
     }
 
+    /// Register a custom Tera filter for all templates
+    pub fn register_filter<F: tera::Filter + 'static>(&mut self, name: &str, filter: F) {
+        self.templates.register_filter(name, filter);
+    }
+
+    /// Register a custom Tera function for all templates
+    pub fn register_function<F: tera::Function + 'static>(&mut self, name: &str, function: F) {
+        self.templates.register_function(name, function);
+    }
+
+    /// Register a custom Tera tester for all templates
+    pub fn register_tester<T: tera::Test + 'static>(&mut self, name: &str, tester: T) {
+        self.templates.register_tester(name, tester);
+    }
+
     /// Initialize a new site
     pub fn init(&self) -> Result<()> {
         commands::init::run(self)
@@ -56,6 +56,24 @@ enum Commands {
         /// Deploy after generation
         #[arg(long)]
         deploy: bool,
+
+        /// Print how long each template took to render
+        #[arg(long)]
+        profile: bool,
+
+        /// Build byte-identical output from unchanged sources, using
+        /// SOURCE_DATE_EPOCH or the newest post's date as the build time
+        #[arg(long)]
+        deterministic: bool,
+
+        /// Threads to render with (overrides `concurrency.jobs`)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+
+        /// Memory hint in megabytes: when posts would need more, their
+        /// content is kept on disk during the build
+        #[arg(long, value_name = "MB")]
+        max_memory: Option<u64>,
     },
 
     /// Start a local server
@@ -76,20 +94,118 @@ enum Commands {
         /// Enable static mode (no file watching)
         #[arg(long)]
         r#static: bool,
+
+        /// Serve over HTTPS, with a self-signed certificate unless --cert
+        /// and --key are given
+        #[arg(long)]
+        https: bool,
+
+        /// PEM certificate for --https
+        #[arg(long, requires = "key")]
+        cert: Option<String>,
+
+        /// PEM private key for --https
+        #[arg(long, requires = "cert")]
+        key: Option<String>,
+
+        /// Compress responses with gzip or brotli
+        #[arg(long)]
+        compress: bool,
+
+        /// Cache headers: `no-cache` (revalidate every file) or
+        /// `production` (cache assets for `server.max_age` seconds)
+        #[arg(long, value_parser = ["no-cache", "production"])]
+        cache: Option<String>,
     },
 
     /// Clean the public folder and cache
     Clean,
 
+    /// Inspect or reset the incremental build cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+
     /// List site information
     List {
         /// Type of content to list (post, page, route, tag, category)
         #[arg(default_value = "post")]
         r#type: String,
+
+        /// List unpublished posts (`published: false`, `draft: true`, or in _drafts)
+        #[arg(long)]
+        draft: bool,
+    },
+
+    /// Manage themes
+    Theme {
+        #[command(subcommand)]
+        action: ThemeAction,
     },
 
     /// Display version information
     Version,
+
+    /// Time full and incremental builds of a synthesized site
+    #[command(hide = true)]
+    Bench {
+        /// Number of posts to synthesize
+        #[arg(long, default_value = "1000")]
+        posts: usize,
+
+        /// Directory to build the site in (defaults to a temporary one)
+        #[arg(long)]
+        dir: Option<PathBuf>,
+
+        /// Keep the site after the benchmark
+        #[arg(long)]
+        keep: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Show the cache size, entry counts and last build time
+    Status,
+
+    /// Delete the cache so the next build starts from scratch
+    Clear,
+
+    /// Check that cached assets match what is in the public folder
+    Verify,
+}
+
+#[derive(Subcommand)]
+enum ThemeAction {
+    /// Download CDN resources referenced by the theme for offline builds
+    Vendor,
+
+    /// Clone (or update) a theme repository into themes/ and enable it
+    Install {
+        /// Git URL of the theme repository
+        url: String,
+
+        /// Theme name (defaults to the repository name without a `hexo-theme-` prefix)
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Branch or tag to check out
+        #[arg(short, long)]
+        branch: Option<String>,
+    },
+
+    /// Scaffold a new theme in themes/ from the built-in templates
+    New {
+        /// Theme name
+        name: String,
+    },
+
+    /// Check whether a theme works with hexo-rs by rendering sample content
+    Check {
+        /// Theme name (defaults to the configured theme)
+        name: Option<String>,
+    },
 }
 
 #[tokio::main]
@@ -135,11 +251,22 @@ async fn main() -> Result<()> {
             hexo_rs::commands::new::create_post(&hexo, &title, &layout, path.as_deref())?;
         }
 
-        Commands::Generate { watch, deploy: _ } => {
-            let hexo = hexo_rs::Hexo::new(&base_dir)?;
+        Commands::Generate {
+            watch,
+            deploy: _,
+            profile,
+            deterministic,
+            jobs,
+            max_memory,
+        } => {
+            let mut hexo = hexo_rs::Hexo::new(&base_dir)?;
+            hexo.config.deterministic |= deterministic;
+            if let Some(jobs) = jobs {
+                hexo.config.concurrency.jobs = jobs;
+            }
             tracing::info!("Generating static files...");
 
-            hexo_rs::commands::generate::run(&hexo)?;
+            hexo_rs::commands::generate::run_with(&hexo, profile, max_memory)?;
             println!("Generated successfully!");
 
             if watch {
@@ -153,14 +280,34 @@ async fn main() -> Result<()> {
             ip,
             open,
             r#static,
+            https,
+            cert,
+            key,
+            compress,
+            cache,
         } => {
-            let hexo = hexo_rs::Hexo::new(&base_dir)?;
+            let mut hexo = hexo_rs::Hexo::new(&base_dir)?;
+            let server = &mut hexo.config.server;
+            server.https |= https || cert.is_some();
+            server.compress |= compress;
+            if let Some(cache) = cache {
+                server.cache = cache;
+            }
+            if cert.is_some() {
+                server.cert = cert;
+                server.key = key;
+            }
 
-            // Generate first
-            tracing::info!("Generating static files...");
-            hexo.generate()?;
+            // Generate first, unless the last build is still current
+            match hexo_rs::commands::generate::up_to_date(&hexo) {
+                Some(reason) => tracing::info!("Skipping generation: {}", reason),
+                None => {
+                    tracing::info!("Generating static files...");
+                    hexo.generate()?;
+                }
+            }
 
-            tracing::info!("Starting server at http://{}:{}", ip, port);
+            tracing::info!("Starting server on {}:{}", ip, port);
             hexo_rs::server::start(&hexo, &ip, port, !r#static, open).await?;
         }
 
@@ -171,9 +318,50 @@ async fn main() -> Result<()> {
             println!("Cleaned successfully!");
         }
 
-        Commands::List { r#type } => {
+        Commands::Cache { action } => {
             let hexo = hexo_rs::Hexo::new(&base_dir)?;
-            hexo_rs::commands::list::run(&hexo, &r#type)?;
+            match action {
+                CacheAction::Status => hexo_rs::commands::cache::status(&hexo)?,
+                CacheAction::Clear => {
+                    hexo_rs::commands::cache::clear(&hexo)?;
+                    println!("Cache cleared!");
+                }
+                CacheAction::Verify => {
+                    let problems = hexo_rs::commands::cache::verify(&hexo)?;
+                    if !problems.is_empty() {
+                        anyhow::bail!("Cache verify found {} problem(s)", problems.len());
+                    }
+                }
+            }
+        }
+
+        Commands::List { r#type, draft } => {
+            let hexo = hexo_rs::Hexo::new(&base_dir)?;
+            hexo_rs::commands::list::run(&hexo, &r#type, draft)?;
+        }
+
+        Commands::Theme { action } => {
+            let hexo = hexo_rs::Hexo::new(&base_dir)?;
+            match action {
+                ThemeAction::Vendor => hexo_rs::commands::theme::vendor(&hexo)?,
+                ThemeAction::Install { url, name, branch } => hexo_rs::commands::theme::install(
+                    &hexo,
+                    &url,
+                    name.as_deref(),
+                    branch.as_deref(),
+                )?,
+                ThemeAction::New { name } => hexo_rs::commands::theme::scaffold(&hexo, &name)?,
+                ThemeAction::Check { name } => {
+                    let problems = hexo_rs::commands::theme::check(&hexo, name.as_deref())?;
+                    if !problems.is_empty() {
+                        anyhow::bail!("Theme check found {} problem(s)", problems.len());
+                    }
+                }
+            }
+        }
+
+        Commands::Bench { posts, dir, keep } => {
+            hexo_rs::commands::bench::run(dir.as_deref(), posts, keep)?;
         }
 
         Commands::Version => {
@@ -0,0 +1,135 @@
+//! HTTP basic auth for previews reachable from other machines
+
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use std::sync::Arc;
+
+use crate::config::ServerConfig;
+
+/// Overrides `server.user`
+pub const USER_ENV: &str = "HEXO_SERVER_USER";
+
+/// Overrides `server.password`
+pub const PASSWORD_ENV: &str = "HEXO_SERVER_PASSWORD";
+
+/// The credentials every request must carry
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    /// Any user name is accepted if unset
+    user: Option<String>,
+    password: String,
+}
+
+impl Credentials {
+    /// Credentials from the environment or `server.user`/`server.password`,
+    /// or `None` if no password is set and auth is off
+    pub fn from_config(config: &ServerConfig) -> Option<Self> {
+        let env = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+        let password = env(PASSWORD_ENV).or_else(|| config.password.clone())?;
+        let user = env(USER_ENV).or_else(|| config.user.clone());
+        Some(Self { user, password })
+    }
+
+    /// Whether the request's `Authorization` header has these credentials
+    fn allows(&self, headers: &HeaderMap) -> bool {
+        let Some(decoded) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split_once(' '))
+            // The scheme is case-insensitive
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("basic"))
+            .map(|(_, credentials)| credentials)
+            .and_then(|v| {
+                base64::engine::general_purpose::STANDARD
+                    .decode(v.trim())
+                    .ok()
+            })
+            .and_then(|v| String::from_utf8(v).ok())
+        else {
+            return false;
+        };
+        let Some((user, password)) = decoded.split_once(':') else {
+            return false;
+        };
+        let user_ok = self
+            .user
+            .as_ref()
+            .is_none_or(|expected| same(user, expected));
+        // Both are compared either way, so timing doesn't tell which was wrong
+        same(password, &self.password) & user_ok
+    }
+}
+
+/// Middleware answering 401 to requests without the credentials
+pub async fn require(
+    State(credentials): State<Arc<Credentials>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if credentials.allows(request.headers()) {
+        return next.run(request).await;
+    }
+    (
+        StatusCode::UNAUTHORIZED,
+        [(
+            header::WWW_AUTHENTICATE,
+            "Basic realm=\"hexo-rs\", charset=\"UTF-8\"",
+        )],
+        "Unauthorized",
+    )
+        .into_response()
+}
+
+/// Compare in time that only depends on the length
+pub(super) fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn basic(credentials: &str) -> HeaderMap {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(credentials);
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Basic {}", encoded).parse().unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_allows() {
+        let credentials = Credentials {
+            user: Some("editor".to_string()),
+            password: "s3cret:pass".to_string(),
+        };
+        assert!(credentials.allows(&basic("editor:s3cret:pass")));
+        assert!(!credentials.allows(&basic("editor:s3cret")));
+        assert!(!credentials.allows(&basic("other:s3cret:pass")));
+        assert!(!credentials.allows(&HeaderMap::new()));
+        let mut lower = HeaderMap::new();
+        let encoded = base64::engine::general_purpose::STANDARD.encode("editor:s3cret:pass");
+        lower.insert(
+            header::AUTHORIZATION,
+            format!("basic {}", encoded).parse().unwrap(),
+        );
+        assert!(credentials.allows(&lower));
+
+        let token = Credentials {
+            user: None,
+            password: "token".to_string(),
+        };
+        assert!(token.allows(&basic("anyone:token")));
+        assert!(token.allows(&basic(":token")));
+        assert!(!token.allows(&basic("anyone:nope")));
+    }
+}
@@ -0,0 +1,113 @@
+//! ETag and Cache-Control headers for files served by the dev server
+
+use axum::http::{header, HeaderMap, HeaderValue};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::config::ServerConfig;
+
+/// `server.cache` mode that makes the browser check every file again
+pub const NO_CACHE: &str = "no-cache";
+
+/// `server.cache` mode with the headers a production host would send
+pub const PRODUCTION: &str = "production";
+
+/// Weak ETag of the file at `path`, from its size and modification time.
+/// `injected` marks pages served with the live reload script, which differ
+/// from the same file served without it.
+pub fn etag(path: &Path, injected: bool) -> Option<String> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!(
+        "W/\"{:x}-{:x}{}\"",
+        metadata.len(),
+        mtime.as_nanos(),
+        if injected { "-lr" } else { "" }
+    ))
+}
+
+/// Whether the request's `If-None-Match` matches `etag`, so a
+/// `304 Not Modified` will do
+pub fn not_modified(request: &HeaderMap, etag: &str) -> bool {
+    let Some(value) = request
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return false;
+    };
+    // Weak comparison: W/ prefixes are ignored
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    value
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+/// `Cache-Control` for a file. In production mode HTML is revalidated on
+/// every load and everything else is cached for `server.max_age` seconds.
+pub fn cache_control(config: &ServerConfig, is_html: bool) -> String {
+    if config.cache != PRODUCTION {
+        return NO_CACHE.to_string();
+    }
+    if is_html {
+        "public, max-age=0, must-revalidate".to_string()
+    } else {
+        format!("public, max-age={}", config.max_age)
+    }
+}
+
+/// Add the `ETag` and `Cache-Control` headers of a served file
+pub fn add_headers(
+    headers: &mut HeaderMap,
+    config: &ServerConfig,
+    etag: Option<&str>,
+    is_html: bool,
+) {
+    if let Some(value) = etag.and_then(|e| HeaderValue::from_str(e).ok()) {
+        headers.insert(header::ETAG, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&cache_control(config, is_html)) {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_not_modified() {
+        let mut headers = HeaderMap::new();
+        assert!(!not_modified(&headers, "W/\"3-1\""));
+        headers.insert(header::IF_NONE_MATCH, "\"1-1\", W/\"3-1\"".parse().unwrap());
+        assert!(not_modified(&headers, "W/\"3-1\""));
+        assert!(!not_modified(&headers, "W/\"3-2\""));
+        headers.insert(header::IF_NONE_MATCH, "*".parse().unwrap());
+        assert!(not_modified(&headers, "W/\"3-2\""));
+    }
+
+    #[test]
+    fn test_cache_control() {
+        let mut config = ServerConfig::default();
+        assert_eq!(cache_control(&config, false), "no-cache");
+        config.cache = PRODUCTION.to_string();
+        config.max_age = 600;
+        assert_eq!(cache_control(&config, false), "public, max-age=600");
+        assert_eq!(
+            cache_control(&config, true),
+            "public, max-age=0, must-revalidate"
+        );
+    }
+
+    #[test]
+    fn test_etag_changes_with_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.css");
+        std::fs::write(&path, "a").unwrap();
+        let first = etag(&path, false).unwrap();
+        assert!(first.starts_with("W/\"1-"));
+        assert_ne!(etag(&path, true).unwrap(), first);
+        std::fs::write(&path, "ab").unwrap();
+        assert_ne!(etag(&path, false).unwrap(), first);
+        assert!(etag(&dir.path().join("missing"), false).is_none());
+    }
+}
@@ -0,0 +1,179 @@
+//! Open-in-editor support for dev server error pages
+
+use anyhow::{anyhow, Result};
+use axum::http::{header, HeaderMap};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use crate::helpers::{html_escape, url_encode};
+
+/// A source location that can be opened in the editor
+#[derive(Debug, Clone)]
+pub struct SourceLocation {
+    pub file: PathBuf,
+    pub line: usize,
+}
+
+impl SourceLocation {
+    pub fn new(file: PathBuf, line: usize) -> Self {
+        Self {
+            file,
+            line: line.max(1),
+        }
+    }
+}
+
+/// Build an editor URL (e.g. `vscode://file/...`) from the configured template
+pub fn editor_url(template: &str, location: &SourceLocation) -> String {
+    template
+        .replace("{file}", &location.file.to_string_lossy())
+        .replace("{line}", &location.line.to_string())
+}
+
+/// Launch the configured editor command at the given location
+pub fn open_in_editor(command: &str, location: &SourceLocation) -> Result<()> {
+    let mut parts = command.split_whitespace().map(|part| {
+        part.replace("{file}", &location.file.to_string_lossy())
+            .replace("{line}", &location.line.to_string())
+    });
+    let program = parts
+        .next()
+        .ok_or_else(|| anyhow!("No editor command configured"))?;
+
+    std::process::Command::new(program).args(parts).spawn()?;
+    Ok(())
+}
+
+/// Find the source file that would generate a URL path, if any
+pub fn find_source_for_path(source_dir: &Path, url_path: &str) -> Option<PathBuf> {
+    let clean = url_path.trim_matches('/');
+    if clean.is_empty() {
+        return None;
+    }
+
+    // Pages: source/<path>.md or source/<path>/index.md
+    let candidates = [
+        source_dir.join(format!("{}.md", clean)),
+        source_dir.join(clean).join("index.md"),
+    ];
+    if let Some(found) = candidates.into_iter().find(|p| p.is_file()) {
+        return Some(found);
+    }
+
+    // Posts: the slug (last URL segment) is the file name in _posts
+    let slug = clean.rsplit('/').next()?;
+    let post = source_dir.join("_posts").join(format!("{}.md", slug));
+    post.is_file().then_some(post)
+}
+
+/// URL of the server endpoint that launches the editor at `location`, to be
+/// POSTed with the server's `token`
+pub fn open_url(location: &SourceLocation, token: &str) -> String {
+    format!(
+        "/__open?file={}&line={}&token={}",
+        url_encode(&location.file.to_string_lossy()),
+        location.line,
+        url_encode(token)
+    )
+}
+
+/// Whether a request to launch the editor may come from `peer`: only this
+/// machine, and only pages the server served itself. Browsers send `Origin`
+/// with cross-site POSTs, so it must name the host the request went to.
+pub fn may_open(peer: &SocketAddr, headers: &HeaderMap) -> bool {
+    if !peer.ip().is_loopback() {
+        return false;
+    }
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return true;
+    };
+    let host = headers.get(header::HOST).and_then(|h| h.to_str().ok());
+    let origin_host = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| origin.split_once("://"))
+        .map(|(_, host)| host);
+    host.is_some() && origin_host == host
+}
+
+/// Render a minimal HTML error page with an optional open-in-editor link
+pub fn error_page(
+    title: &str,
+    message: &str,
+    location: Option<&SourceLocation>,
+    editor_url_template: &str,
+    open_token: &str,
+) -> String {
+    let links = location
+        .map(|loc| {
+            let file = html_escape(&loc.file.to_string_lossy());
+            let open_href = open_url(loc, open_token);
+            format!(
+                "<p><code>{}:{}</code></p>\n<form method=\"post\" action=\"{}\"><a href=\"{}\">Open in editor</a> &middot; <button type=\"submit\">Launch editor from server</button></form>\n",
+                file,
+                loc.line,
+                html_escape(&open_href),
+                html_escape(&editor_url(editor_url_template, loc))
+            )
+        })
+        .unwrap_or_default();
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body style=\"font-family: sans-serif; padding: 2em;\">\n<h1>{}</h1>\n<pre style=\"white-space: pre-wrap;\">{}</pre>\n{}</body>\n</html>\n",
+        html_escape(title),
+        html_escape(title),
+        html_escape(message),
+        links
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editor_url() {
+        let loc = SourceLocation::new(PathBuf::from("/site/source/_posts/a.md"), 3);
+        assert_eq!(
+            editor_url("vscode://file/{file}:{line}", &loc),
+            "vscode://file//site/source/_posts/a.md:3"
+        );
+    }
+
+    #[test]
+    fn test_may_open() {
+        let local: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let remote: SocketAddr = "192.168.1.20:50000".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "localhost:4000".parse().unwrap());
+        assert!(may_open(&local, &headers));
+        assert!(!may_open(&remote, &headers));
+
+        headers.insert(header::ORIGIN, "http://localhost:4000".parse().unwrap());
+        assert!(may_open(&local, &headers));
+        headers.insert(header::ORIGIN, "https://evil.example".parse().unwrap());
+        assert!(!may_open(&local, &headers));
+        headers.insert(header::ORIGIN, "null".parse().unwrap());
+        assert!(!may_open(&local, &headers));
+    }
+
+    #[test]
+    fn test_find_source_for_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path();
+        std::fs::create_dir_all(source.join("_posts")).unwrap();
+        std::fs::create_dir_all(source.join("about")).unwrap();
+        std::fs::write(source.join("_posts/hello.md"), "").unwrap();
+        std::fs::write(source.join("about/index.md"), "").unwrap();
+
+        assert_eq!(
+            find_source_for_path(source, "/2024/01/01/hello/"),
+            Some(source.join("_posts/hello.md"))
+        );
+        assert_eq!(
+            find_source_for_path(source, "/about/"),
+            Some(source.join("about/index.md"))
+        );
+        assert_eq!(find_source_for_path(source, "/missing/"), None);
+    }
+}
@@ -1,35 +1,117 @@
 //! Development server with live reload
 
+mod auth;
+mod caching;
+mod editor;
+mod proxy;
+mod tls;
+
 use anyhow::Result;
 use axum::{
     body::Body,
     extract::{
         ws::{Message, WebSocket},
-        State, WebSocketUpgrade,
+        ConnectInfo, Query, State, WebSocketUpgrade,
     },
-    http::{Request, StatusCode},
+    http::{HeaderMap, Request, StatusCode},
+    middleware,
     response::{Html, IntoResponse, Response},
-    routing::get,
+    routing::{get, post},
     Router,
 };
 use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode};
+use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::sync::broadcast;
+use tower_http::compression::CompressionLayer;
 use tower_http::services::ServeDir;
 
+use crate::commands::generate::Rebuilder;
+use crate::config::ServerConfig;
 use crate::Hexo;
+use editor::SourceLocation;
 
-/// Live reload script injected into HTML pages
+/// Live reload script injected into HTML pages. Build errors are shown in
+/// an overlay over the page until the next successful build reloads it, and
+/// changed stylesheets are swapped in without reloading.
 const LIVE_RELOAD_SCRIPT: &str = r#"
 <script>
 (function() {
-    var ws = new WebSocket('ws://' + location.host + '/__livereload');
+    var scheme = location.protocol === 'https:' ? 'wss://' : 'ws://';
+    var ws = new WebSocket(scheme + location.host + '/__livereload');
+    function link(text, href, onclick) {
+        var a = document.createElement('a');
+        a.textContent = text;
+        a.href = href;
+        a.style.color = '#8be9fd';
+        a.style.marginRight = '1em';
+        if (onclick) { a.onclick = onclick; }
+        return a;
+    }
+    function showError(error) {
+        var overlay = document.getElementById('__hexo-error-overlay');
+        if (!overlay) {
+            overlay = document.createElement('div');
+            overlay.id = '__hexo-error-overlay';
+            overlay.style.cssText = 'position:fixed;top:0;left:0;right:0;bottom:0;z-index:2147483647;' +
+                'overflow:auto;padding:2em;box-sizing:border-box;background:rgba(24,24,27,0.95);' +
+                'color:#f4f4f5;font:14px/1.5 ui-monospace,monospace;text-align:left;';
+            document.body.appendChild(overlay);
+        }
+        overlay.textContent = '';
+        var title = document.createElement('h2');
+        title.textContent = 'Build failed';
+        title.style.cssText = 'color:#f87171;margin:0 0 1em;font:inherit;font-size:1.4em;';
+        overlay.appendChild(title);
+        if (error.file) {
+            var file = document.createElement('p');
+            file.textContent = error.file + ':' + error.line;
+            overlay.appendChild(file);
+            var links = document.createElement('p');
+            links.appendChild(link('Open in editor', error.editor_url));
+            links.appendChild(link('Launch editor from server', '#', function() {
+                fetch(error.open_url, { method: 'POST' });
+                return false;
+            }));
+            overlay.appendChild(links);
+        }
+        var message = document.createElement('pre');
+        message.textContent = error.message;
+        message.style.cssText = 'white-space:pre-wrap;margin:1em 0;';
+        overlay.appendChild(message);
+        var hint = document.createElement('p');
+        hint.textContent = 'The page reloads once the build succeeds. Press Esc to dismiss.';
+        hint.style.color = '#a1a1aa';
+        overlay.appendChild(hint);
+    }
+    function updateStylesheets() {
+        var links = document.querySelectorAll('link[rel="stylesheet"]');
+        Array.prototype.forEach.call(links, function(link) {
+            var url = new URL(link.href, location.href);
+            if (url.host !== location.host) { return; }
+            url.searchParams.set('__hexo', Date.now());
+            // Keep the old sheet until the new one loaded, so nothing flashes
+            var next = link.cloneNode();
+            next.href = url.href;
+            next.onload = next.onerror = function() { link.remove(); };
+            link.parentNode.insertBefore(next, link.nextSibling);
+        });
+    }
+    document.addEventListener('keydown', function(e) {
+        var overlay = document.getElementById('__hexo-error-overlay');
+        if (e.key === 'Escape' && overlay) { overlay.remove(); }
+    });
     ws.onmessage = function(msg) {
-        if (msg.data === 'reload') {
+        var data = JSON.parse(msg.data);
+        if (data.type === 'reload') {
             location.reload();
+        } else if (data.type === 'css-update') {
+            updateStylesheets();
+        } else if (data.type === 'error') {
+            showError(data);
         }
     };
     ws.onclose = function() {
@@ -41,35 +123,108 @@ const LIVE_RELOAD_SCRIPT: &str = r#"
 </body>
 "#;
 
+/// Message pushed to browsers over the live reload socket, as JSON tagged
+/// with its `type`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum ReloadMessage {
+    /// The site was regenerated
+    Reload,
+    /// Only stylesheets changed, so pages can keep their state
+    CssUpdate,
+    /// Regeneration failed, shown in an overlay
+    Error {
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line: Option<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        editor_url: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        open_url: Option<String>,
+    },
+}
+
+impl ReloadMessage {
+    fn error(error: &BuildError, editor_url_template: &str, open_token: &str) -> Self {
+        let location = error.location.as_ref();
+        Self::Error {
+            message: error.message.clone(),
+            file: location.map(|loc| loc.file.to_string_lossy().into_owned()),
+            line: location.map(|loc| loc.line),
+            editor_url: location.map(|loc| editor::editor_url(editor_url_template, loc)),
+            open_url: location.map(|loc| editor::open_url(loc, open_token)),
+        }
+    }
+}
+
+/// Sources of stylesheets, which can change without reloading pages
+const STYLESHEET_EXTENSIONS: &[&str] = &["css", "styl", "scss", "sass"];
+
 /// Server state
 struct ServerState {
+    base_dir: PathBuf,
+    source_dir: PathBuf,
     public_dir: PathBuf,
-    reload_tx: broadcast::Sender<()>,
+    reload_tx: broadcast::Sender<ReloadMessage>,
     live_reload: bool,
+    config: ServerConfig,
+    /// Last regeneration error, shown instead of stale pages until the next successful build
+    build_error: Mutex<Option<BuildError>>,
+    /// Whether requests carry basic auth credentials for this server
+    auth_enabled: bool,
+    /// Secret the server's own pages send to `/__open`, which other sites
+    /// can't read
+    open_token: String,
+}
+
+/// A failed regeneration and the file most likely responsible
+struct BuildError {
+    message: String,
+    location: Option<SourceLocation>,
 }
 
 /// Start the development server
 pub async fn start(hexo: &Hexo, ip: &str, port: u16, watch: bool, open: bool) -> Result<()> {
     // Create broadcast channel for live reload notifications
-    let (reload_tx, _) = broadcast::channel::<()>(16);
+    let (reload_tx, _) = broadcast::channel::<ReloadMessage>(16);
 
+    let credentials = auth::Credentials::from_config(&hexo.config.server);
     let state = Arc::new(ServerState {
+        base_dir: hexo.base_dir.clone(),
+        source_dir: hexo.source_dir.clone(),
         public_dir: hexo.public_dir.clone(),
-        reload_tx: reload_tx.clone(),
+        reload_tx,
         live_reload: watch,
+        config: hexo.config.server.clone(),
+        build_error: Mutex::new(None),
+        open_token: uuid::Uuid::new_v4().simple().to_string(),
+        auth_enabled: credentials.is_some(),
     });
 
-    // Create router with live reload endpoint
-    let app = Router::new()
+    // Create router with live reload and open-in-editor endpoints
+    let mut app = Router::new()
         .route("/__livereload", get(livereload_handler))
+        .route("/__open", post(open_handler))
         .fallback(fallback_handler)
-        .with_state(state);
+        .with_state(state.clone());
+    if let Some(credentials) = credentials {
+        app = app.layer(middleware::from_fn_with_state(
+            Arc::new(credentials),
+            auth::require,
+        ));
+    }
+    if hexo.config.server.compress {
+        app = app.layer(CompressionLayer::new());
+    }
 
     // Parse address - handle "localhost" specially
     let bind_ip = if ip == "localhost" { "127.0.0.1" } else { ip };
     let addr: SocketAddr = format!("{}:{}", bind_ip, port).parse()?;
 
-    let url = format!("http://{}:{}", ip, port);
+    let https = hexo.config.server.https;
+    let url = format!("{}://{}:{}", if https { "https" } else { "http" }, ip, port);
     println!("Server running at {}", url);
     if watch {
         println!("Live reload enabled. Watching for changes...");
@@ -90,9 +245,10 @@ pub async fn start(hexo: &Hexo, ip: &str, port: u16, watch: bool, open: bool) ->
         let config_path = hexo.base_dir.join("_config.yml");
         let hexo_clone = hexo.clone();
 
-        tokio::spawn(async move {
-            if let Err(e) =
-                watch_and_reload(source_dir, theme_dir, config_path, hexo_clone, reload_tx).await
+        // Waiting for changes and rebuilding block, so keep them off the
+        // threads serving requests
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = watch_and_reload(source_dir, theme_dir, config_path, hexo_clone, state)
             {
                 tracing::error!("File watcher error: {}", e);
             }
@@ -100,19 +256,30 @@ pub async fn start(hexo: &Hexo, ip: &str, port: u16, watch: bool, open: bool) ->
     }
 
     // Start server
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    if https {
+        let tls = tls::rustls_config(&hexo.config.server, &hexo.base_dir, ip).await?;
+        axum_server::bind_rustls(addr, tls)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .await?;
+    } else {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await?;
+    }
 
     Ok(())
 }
 
 /// Watch for file changes and trigger reload
-async fn watch_and_reload(
+fn watch_and_reload(
     source_dir: PathBuf,
     theme_dir: PathBuf,
     config_path: PathBuf,
     hexo: Hexo,
-    reload_tx: broadcast::Sender<()>,
+    state: Arc<ServerState>,
 ) -> Result<()> {
     let (tx, rx) = std::sync::mpsc::channel();
 
@@ -143,6 +310,16 @@ async fn watch_and_reload(
         tracing::debug!("Watching: {:?}", config_path);
     }
 
+    // Load the content the startup build used, so that the first edit
+    // already only re-renders the pages it affects
+    let mut rebuilder = Rebuilder::new();
+    if let Err(e) = rebuilder.load(&hexo) {
+        tracing::warn!(
+            "Failed to load content, the first rebuild will be full: {}",
+            e
+        );
+    }
+
     // Handle file change events
     loop {
         match rx.recv() {
@@ -171,14 +348,41 @@ async fn watch_and_reload(
 
                 // Regenerate site
                 println!("\n🔄 Regenerating...");
-                match hexo.generate() {
+                let changed: Vec<PathBuf> =
+                    relevant_events.iter().map(|e| e.path.clone()).collect();
+                match rebuilder.rebuild(&hexo, &changed) {
                     Ok(_) => {
                         println!("✅ Regenerated successfully!");
-                        // Notify all connected clients to reload
-                        let _ = reload_tx.send(());
+                        let had_error = state.build_error.lock().unwrap().take().is_some();
+                        // Notify all connected clients to reload, or just
+                        // swap stylesheets unless an error overlay is up
+                        let message = if !had_error && only_stylesheets(&changed) {
+                            ReloadMessage::CssUpdate
+                        } else {
+                            ReloadMessage::Reload
+                        };
+                        let _ = state.reload_tx.send(message);
                     }
                     Err(e) => {
                         println!("❌ Generation failed: {}", e);
+                        // Blame the first changed source file so the error page can link to it
+                        let location = relevant_events
+                            .iter()
+                            .find(|e| e.path.is_file() && e.path.starts_with(&source_dir))
+                            .map(|e| SourceLocation::new(e.path.clone(), 1));
+                        let error = BuildError {
+                            message: format!("{:#}", e),
+                            location,
+                        };
+                        // Shown over the current page; pages loaded later
+                        // get the error page instead
+                        let message = ReloadMessage::error(
+                            &error,
+                            &state.config.editor_url,
+                            &state.open_token,
+                        );
+                        *state.build_error.lock().unwrap() = Some(error);
+                        let _ = state.reload_tx.send(message);
                     }
                 }
             }
@@ -195,6 +399,16 @@ async fn watch_and_reload(
     Ok(())
 }
 
+/// Whether all `changed` files are stylesheets
+fn only_stylesheets(changed: &[PathBuf]) -> bool {
+    !changed.is_empty()
+        && changed.iter().all(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| STYLESHEET_EXTENSIONS.contains(&ext))
+        })
+}
+
 /// WebSocket handler for live reload
 async fn livereload_handler(
     ws: WebSocketUpgrade,
@@ -205,7 +419,10 @@ async fn livereload_handler(
 }
 
 /// Handle WebSocket connection for live reload
-async fn handle_livereload_socket(mut socket: WebSocket, mut reload_rx: broadcast::Receiver<()>) {
+async fn handle_livereload_socket(
+    mut socket: WebSocket,
+    mut reload_rx: broadcast::Receiver<ReloadMessage>,
+) {
     tracing::debug!("Live reload client connected");
 
     loop {
@@ -213,8 +430,11 @@ async fn handle_livereload_socket(mut socket: WebSocket, mut reload_rx: broadcas
             // Wait for reload signal
             result = reload_rx.recv() => {
                 match result {
-                    Ok(_) => {
-                        if socket.send(Message::Text("reload".to_string())).await.is_err() {
+                    Ok(message) => {
+                        let Ok(text) = serde_json::to_string(&message) else {
+                            continue;
+                        };
+                        if socket.send(Message::Text(text)).await.is_err() {
                             break;
                         }
                     }
@@ -240,11 +460,61 @@ async fn handle_livereload_socket(mut socket: WebSocket, mut reload_rx: broadcas
     tracing::debug!("Live reload client disconnected");
 }
 
+/// Query parameters for the open-in-editor endpoint
+#[derive(Deserialize)]
+struct OpenParams {
+    file: PathBuf,
+    line: Option<usize>,
+    token: String,
+}
+
+/// Open a source file in the configured editor
+async fn open_handler(
+    State(state): State<Arc<ServerState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(params): Query<OpenParams>,
+) -> Response {
+    // It runs a command, so only for this machine's pages from this server
+    if !editor::may_open(&peer, &headers) || !auth::same(&params.token, &state.open_token) {
+        return (StatusCode::FORBIDDEN, "Not allowed to open the editor").into_response();
+    }
+
+    // Only allow opening files that belong to the site
+    let base_dir = state
+        .base_dir
+        .canonicalize()
+        .unwrap_or(state.base_dir.clone());
+    let file = match params.file.canonicalize() {
+        Ok(file) if file.starts_with(&base_dir) => file,
+        _ => return (StatusCode::FORBIDDEN, "File is outside the site directory").into_response(),
+    };
+
+    let location = SourceLocation::new(file, params.line.unwrap_or(1));
+    match editor::open_in_editor(&state.config.editor_command, &location) {
+        Ok(()) => (StatusCode::OK, "Opened in editor").into_response(),
+        Err(e) => {
+            tracing::warn!("Failed to open editor: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to open editor: {}", e),
+            )
+                .into_response()
+        }
+    }
+}
+
 /// Fallback handler that serves files and injects live reload script
 async fn fallback_handler(
     State(state): State<Arc<ServerState>>,
     request: Request<Body>,
 ) -> Response {
+    // Requests for backends the site talks to
+    let path_and_query = request.uri().path_and_query().map_or("/", |p| p.as_str());
+    if let Some(url) = proxy::target(&state.config.proxy, path_and_query) {
+        return proxy::forward(url, request, state.auth_enabled).await;
+    }
+
     let path = request.uri().path();
 
     // Determine the file path
@@ -277,14 +547,58 @@ async fn fallback_handler(
         .unwrap_or(false)
         || file_path.ends_with("index.html");
 
-    if is_html && state.live_reload {
+    // Show the last build error instead of stale content
+    if is_html {
+        if let Some(error) = state.build_error.lock().unwrap().as_ref() {
+            let page = editor::error_page(
+                "Build failed",
+                &error.message,
+                error.location.as_ref(),
+                &state.config.editor_url,
+                &state.open_token,
+            );
+            let page = if state.live_reload {
+                inject_live_reload(&page)
+            } else {
+                page
+            };
+            return (StatusCode::INTERNAL_SERVER_ERROR, Html(page)).into_response();
+        }
+    }
+
+    // Point at the source file when a page that should exist was not generated
+    if !file_path.exists() {
+        if let Some(source) = editor::find_source_for_path(&state.source_dir, path) {
+            let page = editor::error_page(
+                "Not found",
+                &format!("{} was not generated from {}", path, source.display()),
+                Some(&SourceLocation::new(source, 1)),
+                &state.config.editor_url,
+                &state.open_token,
+            );
+            return (StatusCode::NOT_FOUND, Html(page)).into_response();
+        }
+        return not_found(&state).await;
+    }
+
+    let injected = is_html && state.live_reload;
+    let etag = caching::etag(&file_path, injected);
+    if let Some(etag) = &etag {
+        if caching::not_modified(request.headers(), etag) {
+            let mut response = StatusCode::NOT_MODIFIED.into_response();
+            caching::add_headers(response.headers_mut(), &state.config, Some(etag), is_html);
+            return response;
+        }
+    }
+
+    let mut response = if injected {
         // Read and inject live reload script
         match tokio::fs::read_to_string(&file_path).await {
             Ok(content) => {
                 let injected = inject_live_reload(&content);
                 Html(injected).into_response()
             }
-            Err(_) => (StatusCode::NOT_FOUND, "Not found").into_response(),
+            Err(_) => return not_found(&state).await,
         }
     } else {
         // Serve static file using tower-http
@@ -293,7 +607,31 @@ async fn fallback_handler(
             Ok(response) => response.into_response(),
             Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Server error").into_response(),
         }
+    };
+    if response.status().is_success() {
+        caching::add_headers(
+            response.headers_mut(),
+            &state.config,
+            etag.as_deref(),
+            is_html,
+        );
     }
+    response
+}
+
+/// The site's generated 404 page if it has one, else plain "Not found"
+async fn not_found(state: &ServerState) -> Response {
+    for page in ["404.html", "404/index.html"] {
+        if let Ok(content) = tokio::fs::read_to_string(state.public_dir.join(page)).await {
+            let content = if state.live_reload {
+                inject_live_reload(&content)
+            } else {
+                content
+            };
+            return (StatusCode::NOT_FOUND, Html(content)).into_response();
+        }
+    }
+    (StatusCode::NOT_FOUND, "Not found").into_response()
 }
 
 /// Inject live reload script into HTML content
@@ -327,3 +665,58 @@ fn open_browser(url: &str) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reload_message_json() {
+        let reload = serde_json::to_string(&ReloadMessage::Reload).unwrap();
+        assert_eq!(reload, r#"{"type":"reload"}"#);
+        let css = serde_json::to_string(&ReloadMessage::CssUpdate).unwrap();
+        assert_eq!(css, r#"{"type":"css-update"}"#);
+
+        let error = BuildError {
+            message: "bad front-matter".to_string(),
+            location: Some(SourceLocation::new(PathBuf::from("/site/a b.md"), 3)),
+        };
+        let json = serde_json::to_value(ReloadMessage::error(
+            &error,
+            "vscode://file/{file}:{line}",
+            "t0k",
+        ))
+        .unwrap();
+        assert_eq!(json["type"], "error");
+        assert_eq!(json["message"], "bad front-matter");
+        assert_eq!(json["file"], "/site/a b.md");
+        assert_eq!(json["line"], 3);
+        assert_eq!(json["editor_url"], "vscode://file//site/a b.md:3");
+        assert_eq!(
+            json["open_url"],
+            "/__open?file=/site/a%20b.md&line=3&token=t0k"
+        );
+
+        let error = BuildError {
+            message: "bad config".to_string(),
+            location: None,
+        };
+        let json = serde_json::to_value(ReloadMessage::error(&error, "", "")).unwrap();
+        assert!(json.get("file").is_none());
+    }
+
+    #[test]
+    fn test_only_stylesheets() {
+        let paths = |names: &[&str]| names.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert!(only_stylesheets(&paths(&[
+            "themes/t/source/css/style.css",
+            "themes/t/source/css/_partial/header.styl",
+        ])));
+        assert!(!only_stylesheets(&paths(&[
+            "themes/t/source/css/style.css",
+            "themes/t/layout/post.html",
+        ])));
+        assert!(!only_stylesheets(&paths(&["source/css"])));
+        assert!(!only_stylesheets(&[]));
+    }
+}
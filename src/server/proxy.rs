@@ -0,0 +1,194 @@
+//! Forwarding requests under `server.proxy` prefixes to upstream services
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, HeaderMap, HeaderName, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::sync::OnceLock;
+
+/// Largest request body forwarded upstream
+const MAX_BODY: usize = 32 * 1024 * 1024;
+
+/// Headers that only apply to one connection, never forwarded
+const HOP_BY_HOP: &[HeaderName] = &[
+    header::CONNECTION,
+    header::HOST,
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+    header::CONTENT_LENGTH,
+];
+
+/// Upstream URL for a request to `path_and_query`, if its path is under one
+/// of the `proxies` prefixes. The longest matching prefix wins, and the
+/// whole path is appended to its upstream URL.
+pub fn target(proxies: &BTreeMap<String, String>, path_and_query: &str) -> Option<String> {
+    let path = path_and_query.split('?').next().unwrap_or_default();
+    proxies
+        .iter()
+        .filter(|(prefix, _)| {
+            let prefix = prefix.trim_end_matches('/');
+            // `/api` covers `/api` and `/api/...`, but not `/apiary`
+            path.strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || prefix.is_empty())
+        })
+        .max_by_key(|(prefix, _)| prefix.trim_end_matches('/').len())
+        .map(|(_, upstream)| format!("{}{}", upstream.trim_end_matches('/'), path_and_query))
+}
+
+/// Headers of a request to pass upstream. `strip_auth` drops
+/// `Authorization` too, which carries the preview's own password when the
+/// server asks for one.
+fn forwarded_headers(headers: &HeaderMap, strip_auth: bool) -> Vec<(String, String)> {
+    let mut forwarded: Vec<(String, String)> = headers
+        .iter()
+        .filter(|(name, _)| {
+            // ureq asks for and decodes compressed responses itself
+            !HOP_BY_HOP.contains(name)
+                && *name != header::ACCEPT_ENCODING
+                && !(strip_auth && *name == header::AUTHORIZATION)
+        })
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    if let Some(host) = headers.get(header::HOST).and_then(|h| h.to_str().ok()) {
+        forwarded.push(("X-Forwarded-Host".to_string(), host.to_string()));
+    }
+    forwarded
+}
+
+/// Send `request` to `url` and return the upstream response, or a 502 if
+/// the upstream can't be reached. `strip_auth` keeps the browser's
+/// credentials for this server from reaching the upstream.
+pub async fn forward(url: String, request: Request<Body>, strip_auth: bool) -> Response {
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_BODY).await {
+        Ok(body) => body,
+        Err(e) => {
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Request body: {}", e),
+            )
+                .into_response()
+        }
+    };
+
+    let mut upstream = agent().request(parts.method.as_str(), &url);
+    for (name, value) in forwarded_headers(&parts.headers, strip_auth) {
+        upstream = upstream.set(&name, &value);
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        let response = match upstream.send_bytes(&body) {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(e.to_string()),
+        };
+        let status = response.status();
+        let headers: Vec<(String, String)> = response
+            .headers_names()
+            .into_iter()
+            .flat_map(|name| {
+                response
+                    .all(&name)
+                    .into_iter()
+                    .map(|value| (name.clone(), value.to_string()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let mut content = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut content)
+            .map_err(|e| e.to_string())?;
+        Ok((status, headers, content))
+    })
+    .await;
+
+    let (status, headers, content) = match result {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => {
+            tracing::warn!("Proxy request to {} failed: {}", url, e);
+            return (
+                StatusCode::BAD_GATEWAY,
+                format!("Proxy request to {} failed: {}", url, e),
+            )
+                .into_response();
+        }
+        Err(e) => return (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
+    };
+
+    let mut response =
+        Response::builder().status(StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY));
+    for (name, value) in headers {
+        match HeaderName::try_from(name) {
+            Ok(name) if !HOP_BY_HOP.contains(&name) => {
+                response = response.header(name, value);
+            }
+            _ => {}
+        }
+    }
+    response
+        .body(Body::from(content))
+        .unwrap_or_else(|e| (StatusCode::BAD_GATEWAY, e.to_string()).into_response())
+}
+
+/// Shared client, which keeps upstream connections alive between requests.
+/// Redirects are passed on to the browser rather than followed.
+fn agent() -> &'static ureq::Agent {
+    static AGENT: OnceLock<ureq::Agent> = OnceLock::new();
+    AGENT.get_or_init(|| ureq::AgentBuilder::new().redirects(0).build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target() {
+        let proxies = BTreeMap::from([
+            ("/api".to_string(), "http://localhost:8080".to_string()),
+            (
+                "/api/search/".to_string(),
+                "https://search.example.com/v1/".to_string(),
+            ),
+        ]);
+        assert_eq!(
+            target(&proxies, "/api/comments?post=1").as_deref(),
+            Some("http://localhost:8080/api/comments?post=1")
+        );
+        assert_eq!(
+            target(&proxies, "/api").as_deref(),
+            Some("http://localhost:8080/api")
+        );
+        assert_eq!(
+            target(&proxies, "/api/search/q").as_deref(),
+            Some("https://search.example.com/v1/api/search/q")
+        );
+        assert_eq!(target(&proxies, "/apiary/"), None);
+        assert_eq!(target(&proxies, "/about/?api"), None);
+    }
+
+    #[test]
+    fn test_forwarded_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "localhost:4000".parse().unwrap());
+        headers.insert(header::AUTHORIZATION, "Basic ZTpz".parse().unwrap());
+        headers.insert(header::ACCEPT, "application/json".parse().unwrap());
+        headers.insert(header::CONNECTION, "keep-alive".parse().unwrap());
+
+        let names = |strip_auth| {
+            forwarded_headers(&headers, strip_auth)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(true), ["accept", "X-Forwarded-Host"]);
+        assert_eq!(
+            names(false),
+            ["authorization", "accept", "X-Forwarded-Host"]
+        );
+    }
+}
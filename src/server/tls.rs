@@ -0,0 +1,74 @@
+//! TLS for `server --https`
+
+use anyhow::{anyhow, Result};
+use axum_server::tls_rustls::RustlsConfig;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cache::CACHE_DIR;
+use crate::config::ServerConfig;
+
+/// Load the certificate and key of `server.cert`/`server.key` (relative to
+/// `base_dir`), or a self-signed pair for `host` when neither is set. The
+/// generated pair is kept in the cache directory, so browsers only need to
+/// accept it once.
+pub async fn rustls_config(
+    config: &ServerConfig,
+    base_dir: &Path,
+    host: &str,
+) -> Result<RustlsConfig> {
+    // Another provider may already be installed, which is fine
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let (cert, key) = match (&config.cert, &config.key) {
+        (Some(cert), Some(key)) => (base_dir.join(cert), base_dir.join(key)),
+        (None, None) => self_signed(&base_dir.join(CACHE_DIR).join("tls"), host)?,
+        _ => return Err(anyhow!("A TLS certificate needs both a cert and a key")),
+    };
+    RustlsConfig::from_pem_file(&cert, &key).await.map_err(|e| {
+        anyhow!(
+            "Failed to load TLS certificate {:?} and key {:?}: {}",
+            cert,
+            key,
+            e
+        )
+    })
+}
+
+/// Paths of a self-signed certificate for `host` and its key in `dir`,
+/// generated unless a previous run already did
+fn self_signed(dir: &Path, host: &str) -> Result<(PathBuf, PathBuf)> {
+    let cert_path = dir.join(format!("{}.crt", host));
+    let key_path = dir.join(format!("{}.key", host));
+    if cert_path.is_file() && key_path.is_file() {
+        return Ok((cert_path, key_path));
+    }
+
+    let mut names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+    if !names.iter().any(|name| name == host) {
+        names.push(host.to_string());
+    }
+    let generated = rcgen::generate_simple_self_signed(names)?;
+    fs::create_dir_all(dir)?;
+    fs::write(&cert_path, generated.cert.pem())?;
+    fs::write(&key_path, generated.key_pair.serialize_pem())?;
+    tracing::info!("Generated a self-signed certificate: {:?}", cert_path);
+    Ok((cert_path, key_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_signed_is_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert, key) = self_signed(dir.path(), "localhost").unwrap();
+        let pem = fs::read_to_string(&cert).unwrap();
+        assert!(pem.starts_with("-----BEGIN CERTIFICATE-----"));
+        assert!(fs::read_to_string(&key).unwrap().contains("PRIVATE KEY"));
+
+        assert_eq!(self_signed(dir.path(), "localhost").unwrap().0, cert);
+        assert_eq!(fs::read_to_string(&cert).unwrap(), pem);
+    }
+}
@@ -0,0 +1,160 @@
+//! Template error reporting
+//!
+//! Tera errors are a chain of messages like "Failed to render 'post.html'" →
+//! "Variable `page.foo` not found in context while rendering
+//! 'partials/meta.html'", without line numbers for render errors. This finds
+//! the template the error happened in and the line that mentions the
+//! offending variable, filter or function, so failures point at the file,
+//! line and a few lines of context.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Lines of context shown before and after the failing line
+const CONTEXT_LINES: usize = 2;
+
+/// A template failure with its location
+#[derive(Debug)]
+pub struct TemplateError {
+    /// Template the error happened in
+    pub template: String,
+    /// 1-based line number, if it could be determined
+    pub line: Option<usize>,
+    pub message: String,
+    snippet: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.template, line, self.message)?,
+            None => write!(f, "{}: {}", self.template, self.message)?,
+        }
+        if !self.snippet.is_empty() {
+            write!(f, "\n{}", self.snippet)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl TemplateError {
+    /// Locate a Tera error raised while rendering or parsing `template`
+    pub(crate) fn new(
+        error: &tera::Error,
+        template: &str,
+        sources: &HashMap<String, String>,
+    ) -> Self {
+        let mut messages = vec![error.to_string()];
+        let mut source = std::error::Error::source(error);
+        while let Some(e) = source {
+            messages.push(e.to_string());
+            source = e.source();
+        }
+
+        // The innermost template named in the chain is the one that failed
+        let template = messages
+            .iter()
+            .rev()
+            .find_map(|m| {
+                quoted_after(m, "(error happened in '", '\'')
+                    .or_else(|| quoted_after(m, "while rendering '", '\''))
+                    .or_else(|| quoted_after(m, "Failed to parse '", '\''))
+            })
+            .unwrap_or(template)
+            .to_string();
+        let text = sources.get(&template).map(String::as_str).unwrap_or("");
+
+        // Parse errors carry a position (` --> 3:5`); render errors only name
+        // the variable, filter or function that failed
+        let line = messages.iter().find_map(|m| parse_position(m)).or_else(|| {
+            messages
+                .iter()
+                .rev()
+                .find_map(|m| culprit(m))
+                .and_then(|name| find_line(text, name))
+        });
+
+        let message = messages
+            .iter()
+            .map(|m| summary(m))
+            .filter(|m| !m.is_empty())
+            .collect::<Vec<_>>()
+            .join(": ");
+        let snippet = line.map(|line| snippet(text, line)).unwrap_or_default();
+
+        Self {
+            template,
+            line,
+            message,
+            snippet,
+        }
+    }
+}
+
+/// Text between `prefix` and the next `end` in `message`
+fn quoted_after<'a>(message: &'a str, prefix: &str, end: char) -> Option<&'a str> {
+    let start = message.find(prefix)? + prefix.len();
+    let len = message[start..].find(end)?;
+    Some(&message[start..start + len])
+}
+
+/// Line of a pest parse error position (` --> 3:5`)
+fn parse_position(message: &str) -> Option<usize> {
+    let pos = quoted_after(message, "--> ", ':')?;
+    pos.trim().parse().ok()
+}
+
+/// Name of the variable, filter, test or function an error is about
+fn culprit(message: &str) -> Option<&str> {
+    quoted_after(message, "Variable `", '`')
+        .or_else(|| quoted_after(message, "Filter '", '\''))
+        .or_else(|| quoted_after(message, "Filter call '", '\''))
+        .or_else(|| quoted_after(message, "Function '", '\''))
+        .or_else(|| quoted_after(message, "Function call '", '\''))
+        .or_else(|| quoted_after(message, "Test '", '\''))
+        .or_else(|| quoted_after(message, "Test call '", '\''))
+}
+
+/// First line (1-based) where `name` appears as a whole word
+fn find_line(text: &str, name: &str) -> Option<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    text.lines()
+        .position(|line| {
+            line.match_indices(name).any(|(i, _)| {
+                let before = line[..i].chars().next_back();
+                let after = line[i + name.len()..].chars().next();
+                !before.is_some_and(is_word) && !after.is_some_and(is_word)
+            })
+        })
+        .map(|i| i + 1)
+}
+
+/// One-line summary of a message, dropping pest's own multi-line snippet
+fn summary(message: &str) -> String {
+    if message.contains("--> ") {
+        return message
+            .lines()
+            .filter_map(|l| l.trim().strip_prefix("= "))
+            .collect::<Vec<_>>()
+            .join("; ");
+    }
+    message.to_string()
+}
+
+/// Numbered lines around `line`, with the failing line marked
+fn snippet(text: &str, line: usize) -> String {
+    let first = line.saturating_sub(CONTEXT_LINES).max(1);
+    let width = (line + CONTEXT_LINES).to_string().len();
+    text.lines()
+        .enumerate()
+        .skip(first - 1)
+        .take(line + CONTEXT_LINES + 1 - first)
+        .map(|(i, l)| {
+            let marker = if i + 1 == line { '>' } else { ' ' };
+            format!("{} {:>width$} | {}", marker, i + 1, l)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
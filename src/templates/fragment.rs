@@ -0,0 +1,129 @@
+//! Fragment caching
+//!
+//! `{{ fragment_cache(name="tagcloud", template="partials/tagcloud.html") }}`
+//! renders a template once per build with the site-wide context (`site`,
+//! `config`, `theme`, plus any extra arguments) and returns the cached HTML for
+//! every later call with the same name, like Hexo's `fragment_cache` helper.
+//! It is meant for expensive partials that don't depend on the current page,
+//! such as sidebar widgets and tag clouds.
+//!
+//! `{{ partial(template="partials/footer.html", lang=page_lang) }}` caches on
+//! the template and its arguments instead, so a header or footer rendered
+//! with the same arguments on hundreds of pages is only rendered once. Page
+//! variables it depends on have to be passed as arguments.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+use tera::{Context, Tera};
+
+use super::profile::TemplateProfile;
+use super::usage::TemplateUsage;
+
+/// Rendered fragments of the current build
+#[derive(Default)]
+pub(crate) struct FragmentCache {
+    /// Snapshot of the renderer's templates, taken when the build starts
+    tera: RwLock<Option<Tera>>,
+    globals: RwLock<Context>,
+    fragments: Mutex<HashMap<String, String>>,
+    /// Render times, shared with the renderer
+    pub(crate) profile: TemplateProfile,
+    /// Templates rendered from each template, shared with the renderer
+    pub(crate) usage: TemplateUsage,
+}
+
+impl FragmentCache {
+    /// Start a build: render with these templates and site-wide variables,
+    /// dropping fragments of the previous build
+    pub(crate) fn start(&self, tera: &Tera, globals: Context) {
+        *self.tera.write().unwrap() = Some(tera.clone());
+        *self.globals.write().unwrap() = globals;
+        self.fragments.lock().unwrap().clear();
+    }
+
+    /// Cached fragment `key`, rendering `template` on first use
+    pub(crate) fn get_or_render(
+        &self,
+        key: &str,
+        template: &str,
+        locals: &HashMap<String, tera::Value>,
+    ) -> tera::Result<String> {
+        TemplateUsage::note(template);
+        if let Some(html) = self.fragments.lock().unwrap().get(key) {
+            return Ok(html.clone());
+        }
+
+        let tera = self.tera.read().unwrap();
+        let tera = tera
+            .as_ref()
+            .ok_or_else(|| tera::Error::msg("fragment_cache: used before the build started"))?;
+        let mut context = self.globals.read().unwrap().clone();
+        for (name, value) in locals {
+            context.insert(name, value);
+        }
+        let html = self.usage.track(template, || {
+            self.profile
+                .time(template, || tera.render(template, &context))
+        })?;
+
+        self.fragments
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), html.clone());
+        Ok(html)
+    }
+}
+
+/// The `fragment_cache(name, template)` template function
+pub(crate) struct FragmentCacheFunction(pub(crate) Arc<FragmentCache>);
+
+impl tera::Function for FragmentCacheFunction {
+    fn call(&self, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let name = match args.get("name") {
+            Some(val) => tera::try_get_value!("fragment_cache", "name", String, val),
+            None => return Err("fragment_cache: missing `name` argument".into()),
+        };
+        let template = match args.get("template") {
+            Some(val) => tera::try_get_value!("fragment_cache", "template", String, val),
+            None => return Err("fragment_cache: missing `template` argument".into()),
+        };
+        let mut locals = args.clone();
+        locals.remove("name");
+        locals.remove("template");
+
+        let html = self.0.get_or_render(&name, &template, &locals)?;
+        Ok(tera::Value::String(html))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// The `partial(template, ...)` template function
+pub(crate) struct PartialFunction(pub(crate) Arc<FragmentCache>);
+
+impl tera::Function for PartialFunction {
+    fn call(&self, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let template = match args.get("template") {
+            Some(val) => tera::try_get_value!("partial", "template", String, val),
+            None => return Err("partial: missing `template` argument".into()),
+        };
+        let mut locals = args.clone();
+        locals.remove("template");
+
+        // Sorted so the same arguments always give the same key
+        let sorted: BTreeMap<&String, &tera::Value> = locals.iter().collect();
+        let key = format!(
+            "partial:{}:{}",
+            template,
+            serde_json::to_string(&sorted).unwrap_or_default()
+        );
+        let html = self.0.get_or_render(&key, &template, &locals)?;
+        Ok(tera::Value::String(html))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
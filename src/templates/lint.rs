@@ -0,0 +1,333 @@
+//! Static checks of template sources for `hexo-rs theme check`
+
+use std::fmt;
+
+/// How a helper is used in a template
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HelperKind {
+    Filter,
+    Function,
+    Test,
+}
+
+impl fmt::Display for HelperKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HelperKind::Filter => "filter",
+            HelperKind::Function => "function",
+            HelperKind::Test => "test",
+        })
+    }
+}
+
+impl HelperKind {
+    /// A template that fails with "<Kind> '<name>' not found" if `name`
+    /// isn't registered
+    pub(crate) fn probe(self, name: &str) -> String {
+        match self {
+            HelperKind::Filter => format!("{{{{ 1 | {} }}}}", name),
+            HelperKind::Function => format!("{{{{ {}() }}}}", name),
+            HelperKind::Test => format!("{{% if 1 is {} %}}{{% endif %}}", name),
+        }
+    }
+
+    /// Start of Tera's error message for an unregistered helper
+    pub(crate) fn not_found(self, name: &str) -> String {
+        let kind = match self {
+            HelperKind::Filter => "Filter",
+            HelperKind::Function => "Function",
+            HelperKind::Test => "Test",
+        };
+        format!("{} '{}' not found", kind, name)
+    }
+}
+
+/// Names that look like calls but aren't helpers
+const NOT_HELPERS: &[&str] = &[
+    "if",
+    "elif",
+    "for",
+    "in",
+    "and",
+    "or",
+    "not",
+    "is",
+    "set",
+    "set_global",
+    "macro",
+    "block",
+    "super",
+    "caller",
+    "loop",
+    "filter",
+];
+
+/// Filters, functions and tests used in a template's tags, in order of
+/// first use
+pub(crate) fn helper_calls(source: &str) -> Vec<(HelperKind, String)> {
+    let mut calls = Vec::new();
+    for tag in tags(source) {
+        for call in tag_calls(&tokens(tag)) {
+            if !calls.contains(&call) {
+                calls.push(call);
+            }
+        }
+    }
+    calls
+}
+
+/// Whether any of the templates reads the `content` of items of one of
+/// `collections` (like `"site.posts"` or a function like `"recent_posts"`),
+/// either as `site.posts[0].content` or through a loop variable:
+/// `{% for post in site.posts %}{{ post.content }}`. Loop variables are
+/// matched across all templates, since included templates see the
+/// includer's loop variables, and `{% set %}` aliases of a collection or an
+/// item count as both. When unsure, say a tag mentions a collection or item
+/// along with `content` in any form (`post["content"]`,
+/// `map(attribute="content")`), it answers yes: a wrong no renders empty
+/// content.
+pub(crate) fn reads_content(sources: &[&str], collections: &[&str]) -> bool {
+    let raw: Vec<&str> = sources.iter().flat_map(|source| tags(source)).collect();
+    let tagged: Vec<Vec<String>> = raw.iter().map(|tag| tokens(tag)).collect();
+    let mut collections: Vec<Vec<String>> = collections
+        .iter()
+        .map(|collection| tokens(collection))
+        .collect();
+    let mut items: Vec<String> = Vec::new();
+    // Any macro may be passed an item, under a different name
+    let params: Vec<String> = tagged
+        .iter()
+        .filter(|tag| tag.first().is_some_and(|t| t == "macro"))
+        .flat_map(|tag| macro_params(tag))
+        .collect();
+
+    // Aliases can be defined in any template, so repeat until nothing changes
+    loop {
+        let known = (collections.len(), items.len());
+        for tag in &tagged {
+            let mentions =
+                |from: usize| mentions(&tag[from.min(tag.len())..], &collections, &items);
+            match tag.first().map(String::as_str) {
+                Some("set") | Some("set_global")
+                    if tag.get(2).is_some_and(|t| t == "=") && mentions(3) =>
+                {
+                    // `set x = site.posts` is a collection, but
+                    // `set x = site.posts[0]` and `set x = p` are items
+                    let name = &tag[1];
+                    if !collections.iter().any(|c| c.len() == 1 && &c[0] == name) {
+                        collections.push(vec![name.clone()]);
+                    }
+                    if !items.contains(name) {
+                        items.push(name.clone());
+                    }
+                }
+                Some("for") => {
+                    let Some(in_at) = tag.iter().position(|t| t == "in") else {
+                        continue;
+                    };
+                    // `for item in ...` or `for key, item in ...`
+                    let item = &tag[in_at - 1];
+                    if mentions(in_at + 1) && !items.contains(item) {
+                        items.push(item.clone());
+                    }
+                }
+                Some("macro") => {}
+                _ if tag.iter().any(|t| t == "(") && mentions(0) => {
+                    for param in &params {
+                        if !items.contains(param) {
+                            items.push(param.clone());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if (collections.len(), items.len()) == known {
+            break;
+        }
+    }
+
+    tagged.iter().zip(&raw).any(|(tag, raw)| {
+        let reads_item = tag.iter().enumerate().any(|(i, token)| {
+            let reads = |at: usize| {
+                tag.get(at).is_some_and(|t| t == ".")
+                    && tag.get(at + 1).is_some_and(|t| t == "content")
+            };
+            let is_item = items.contains(token) && (i == 0 || tag[i - 1] != ".");
+            let indexed = collections.iter().any(|c| {
+                tag[i..].starts_with(c)
+                    && tag.get(i + c.len()).is_some_and(|t| t == "[")
+                    && tag[i + c.len()..]
+                        .iter()
+                        .position(|t| t == "]")
+                        .is_some_and(|close| reads(i + c.len() + close + 1))
+            });
+            (is_item && reads(i + 1)) || indexed
+        });
+        let mentions_content = tag.iter().any(|t| t == "content")
+            || raw.contains("\"content\"")
+            || raw.contains("'content'");
+        reads_item || (mentions_content && mentions(tag, &collections, &items))
+    })
+}
+
+/// Whether `tokens` name one of `collections` or, other than as an
+/// attribute, one of `items`
+fn mentions(tokens: &[String], collections: &[Vec<String>], items: &[String]) -> bool {
+    collections
+        .iter()
+        .any(|c| tokens.windows(c.len()).any(|w| w == c))
+        || tokens
+            .iter()
+            .enumerate()
+            .any(|(i, t)| items.contains(t) && (i == 0 || tokens[i - 1] != "."))
+}
+
+/// Parameter names of a `{% macro name(a, b=1) %}` tag
+fn macro_params(tag: &[String]) -> Vec<String> {
+    tag.windows(2)
+        .filter(|w| {
+            (w[0] == "(" || w[0] == ",")
+                && w[1].starts_with(|c: char| c.is_alphabetic() || c == '_')
+        })
+        .map(|w| w[1].clone())
+        .collect()
+}
+
+/// Contents of the `{{ }}`/`{% %}` tags of a template
+fn tags(source: &str) -> Vec<&str> {
+    let mut tags = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        let close = match rest.chars().next() {
+            Some('{') => "}}",
+            Some('%') => "%}",
+            _ => continue,
+        };
+        let Some(end) = rest.find(close) else {
+            break;
+        };
+        tags.push(rest[1..end].trim_matches('-'));
+        rest = &rest[end + 2..];
+    }
+    tags
+}
+
+/// Tokens of a tag outside string literals: identifiers and single
+/// punctuation, with each string literal as `""`
+fn tokens(tag: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut chars = tag.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '"' || c == '\'' || c == '`' {
+            for d in chars.by_ref() {
+                if d == c {
+                    break;
+                }
+            }
+            tokens.push("\"\"".to_string());
+        } else if c.is_alphabetic() || c == '_' {
+            let mut ident = c.to_string();
+            while let Some(&d) = chars.peek() {
+                if d.is_alphanumeric() || d == '_' {
+                    ident.push(d);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(ident);
+        } else if !c.is_whitespace() {
+            tokens.push(c.to_string());
+        }
+    }
+    tokens
+}
+
+/// Helper calls in the tokens of one tag
+fn tag_calls(tokens: &[String]) -> Vec<(HelperKind, String)> {
+    let is_ident = |t: &str| t.starts_with(|c: char| c.is_alphabetic() || c == '_');
+    let mut calls = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if !is_ident(token) || NOT_HELPERS.contains(&token.as_str()) {
+            continue;
+        }
+        let prev = i.checked_sub(1).map(|j| tokens[j].as_str());
+        let before_prev = i.checked_sub(2).map(|j| tokens[j].as_str());
+        let next = tokens.get(i + 1).map(String::as_str);
+        let kind = match prev {
+            // `||` is `or` in some template languages, not a filter
+            Some("|") if before_prev != Some("|") => HelperKind::Filter,
+            Some("is") => HelperKind::Test,
+            Some("not") if before_prev == Some("is") => HelperKind::Test,
+            // Attribute access, macro namespaces (`macros::name()`) and
+            // macro definitions aren't helper calls
+            Some(".") | Some(":") | Some("macro") => continue,
+            _ if next == Some("(") => HelperKind::Function,
+            _ => continue,
+        };
+        calls.push((kind, token.clone()));
+    }
+    calls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_helper_calls() {
+        let source = r#"{% import "macros.html" as m %}{% macro row(x) %}{% endmacro %}
+{{ m::row(x=1) }} {{ page.title | upper | shout(n=2) }}
+{% if a is odd and b is not glowing %}{{ url_for(path="a | b") }}{% endif %}
+{% for p in posts | reverse %}{{ loop.index }}{% endfor %}"#;
+        assert_eq!(
+            helper_calls(source),
+            vec![
+                (HelperKind::Filter, "upper".to_string()),
+                (HelperKind::Filter, "shout".to_string()),
+                (HelperKind::Test, "odd".to_string()),
+                (HelperKind::Test, "glowing".to_string()),
+                (HelperKind::Function, "url_for".to_string()),
+                (HelperKind::Filter, "reverse".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reads_content() {
+        let posts = &["site.posts", "recent_posts"];
+        let sidebar = "{% for item in site.posts %}{{ item.title }}{% endfor %}";
+        let index = "{% for post in page_posts %}{{ post.content }}{% endfor %}";
+        assert!(!reads_content(&[sidebar, index], posts));
+
+        // Through an include that uses the includer's loop variable
+        let list = r#"{% set all = site.posts | slice(end=3) %}{% for p in all %}{% include "row.html" %}{% endfor %}"#;
+        let row = "{{ p.content | striptags }}";
+        assert!(reads_content(&[row, list], posts));
+
+        assert!(reads_content(&["{{ site.posts[0].content }}"], posts));
+        assert!(reads_content(
+            &["{% for p in recent_posts(amount=2) %}{{ p.content }}{% endfor %}"],
+            posts
+        ));
+        assert!(!reads_content(
+            &["{{ page.content }}{{ site.posts[0].title }}"],
+            posts
+        ));
+
+        // Aliases of items, and forms only the fallback catches
+        for source in [
+            "{% set first = site.posts[0] %}{{ first.content }}",
+            "{% for p in site.posts %}{% set x = p %}{{ x.content }}{% endfor %}",
+            r#"{% for p in site.posts %}{{ p["content"] }}{% endfor %}"#,
+            r#"{{ site.posts | map(attribute="content") | join(sep="") }}"#,
+        ] {
+            assert!(reads_content(&[source], posts), "{}", source);
+        }
+        let macros = "{% macro card(entry) %}{{ entry.content }}{% endmacro %}";
+        let list = "{% for p in site.posts %}{{ m::card(entry=p) }}{% endfor %}";
+        assert!(reads_content(&[macros, list], posts));
+    }
+}
@@ -3,82 +3,620 @@
 //! All templates from the vexo theme are embedded directly in the binary,
 //! eliminating the need for QuickJS/EJS runtime.
 
+mod error;
+mod fragment;
+mod lint;
+mod profile;
+mod usage;
+
+pub use error::TemplateError;
+pub use lint::HelperKind;
+pub use profile::{format_report, TemplateTiming};
+
 use anyhow::Result;
+use chrono::{DateTime, Utc};
+use fragment::{FragmentCache, FragmentCacheFunction, PartialFunction};
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
 use tera::{Context, Tera};
 
-/// Template renderer with embedded vexo theme
-pub struct TemplateRenderer {
-    tera: Tera,
-}
+/// Built-in vexo templates
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("layout.html", include_str!("vexo/layout.html")),
+    ("index.html", include_str!("vexo/index.html")),
+    ("page.html", include_str!("vexo/page.html")),
+    ("archive.html", include_str!("vexo/archive.html")),
+    ("tags.html", include_str!("vexo/tags.html")),
+    ("tag_single.html", include_str!("vexo/tag_single.html")),
+    ("search.html", include_str!("vexo/search.html")),
+    ("about.html", include_str!("vexo/about.html")),
+    ("links.html", include_str!("vexo/links.html")),
+    ("project.html", include_str!("vexo/project.html")),
+    ("home.html", include_str!("vexo/home.html")),
+    // Partials
+    (
+        "partials/head.html",
+        include_str!("vexo/partials/head.html"),
+    ),
+    (
+        "partials/header.html",
+        include_str!("vexo/partials/header.html"),
+    ),
+    (
+        "partials/footer.html",
+        include_str!("vexo/partials/footer.html"),
+    ),
+    ("partials/top.html", include_str!("vexo/partials/top.html")),
+    ("partials/nav.html", include_str!("vexo/partials/nav.html")),
+    (
+        "partials/pager.html",
+        include_str!("vexo/partials/pager.html"),
+    ),
+    (
+        "partials/catalog.html",
+        include_str!("vexo/partials/catalog.html"),
+    ),
+    ("partials/tag.html", include_str!("vexo/partials/tag.html")),
+    (
+        "partials/archive_section.html",
+        include_str!("vexo/partials/archive_section.html"),
+    ),
+];
 
-impl TemplateRenderer {
-    /// Create a new renderer with all vexo templates loaded
-    pub fn new() -> Result<Self> {
-        let mut tera = Tera::default();
+/// Built-in vexo language files as `(lang, yaml)`, the base of every
+/// theme's translations
+const BUILTIN_LANGUAGES: &[(&str, &str)] =
+    &[("default", include_str!("vexo/languages/default.yml"))];
 
-        // Disable autoescaping for HTML templates since we're generating HTML
-        // and URLs/paths should not be escaped
-        tera.autoescape_on(vec![]);
+/// An embedded theme selected with `theme: builtin:<name>`. Its templates
+/// replace the vexo templates with the same name.
+pub struct BuiltinTheme {
+    pub name: &'static str,
+    pub templates: &'static [(&'static str, &'static str)],
+    /// Theme `_config.yml`
+    pub config: &'static str,
+    /// Files copied to the public directory
+    pub assets: &'static [(&'static str, &'static str)],
+}
 
-        // Register all templates
-        tera.add_raw_templates(vec![
-            ("layout.html", include_str!("vexo/layout.html")),
-            ("index.html", include_str!("vexo/index.html")),
-            ("page.html", include_str!("vexo/page.html")),
-            ("archive.html", include_str!("vexo/archive.html")),
-            ("tags.html", include_str!("vexo/tags.html")),
-            ("tag_single.html", include_str!("vexo/tag_single.html")),
-            ("search.html", include_str!("vexo/search.html")),
-            ("about.html", include_str!("vexo/about.html")),
-            ("links.html", include_str!("vexo/links.html")),
-            ("project.html", include_str!("vexo/project.html")),
-            ("home.html", include_str!("vexo/home.html")),
-            // Partials
+/// Built-in themes
+pub const BUILTIN_THEMES: &[BuiltinTheme] = &[
+    BuiltinTheme {
+        name: "vexo",
+        templates: &[],
+        config: "",
+        assets: &[],
+    },
+    BuiltinTheme {
+        name: "docs",
+        templates: &[
+            ("layout.html", include_str!("docs/layout.html")),
+            ("index.html", include_str!("docs/index.html")),
+            ("page.html", include_str!("docs/page.html")),
+            ("archive.html", include_str!("docs/archive.html")),
+            ("tags.html", include_str!("docs/tags.html")),
+            ("tag_single.html", include_str!("docs/tag_single.html")),
             (
                 "partials/head.html",
-                include_str!("vexo/partials/head.html"),
+                include_str!("docs/partials/head.html"),
             ),
             (
                 "partials/header.html",
-                include_str!("vexo/partials/header.html"),
+                include_str!("docs/partials/header.html"),
+            ),
+            (
+                "partials/sidebar.html",
+                include_str!("docs/partials/sidebar.html"),
             ),
             (
                 "partials/footer.html",
-                include_str!("vexo/partials/footer.html"),
+                include_str!("docs/partials/footer.html"),
             ),
-            ("partials/top.html", include_str!("vexo/partials/top.html")),
-            ("partials/nav.html", include_str!("vexo/partials/nav.html")),
+        ],
+        config: include_str!("docs/_config.yml"),
+        assets: &[("css/style.css", include_str!("docs/style.css"))],
+    },
+    BuiltinTheme {
+        name: "gallery",
+        templates: &[
+            ("layout.html", include_str!("gallery/layout.html")),
+            ("index.html", include_str!("gallery/index.html")),
+            ("page.html", include_str!("gallery/page.html")),
+            ("archive.html", include_str!("gallery/archive.html")),
+            ("tags.html", include_str!("gallery/tags.html")),
+            ("tag_single.html", include_str!("gallery/tag_single.html")),
             (
-                "partials/pager.html",
-                include_str!("vexo/partials/pager.html"),
+                "partials/head.html",
+                include_str!("gallery/partials/head.html"),
             ),
             (
-                "partials/catalog.html",
-                include_str!("vexo/partials/catalog.html"),
+                "partials/header.html",
+                include_str!("gallery/partials/header.html"),
             ),
-            ("partials/tag.html", include_str!("vexo/partials/tag.html")),
             (
-                "partials/archive_section.html",
-                include_str!("vexo/partials/archive_section.html"),
+                "partials/grid.html",
+                include_str!("gallery/partials/grid.html"),
+            ),
+            (
+                "partials/footer.html",
+                include_str!("gallery/partials/footer.html"),
             ),
-        ])?;
+        ],
+        config: include_str!("gallery/_config.yml"),
+        assets: &[("css/style.css", include_str!("gallery/style.css"))],
+    },
+];
+
+/// Look up a built-in theme by name
+pub fn builtin_theme(name: &str) -> Option<&'static BuiltinTheme> {
+    BUILTIN_THEMES.iter().find(|theme| theme.name == name)
+}
+
+/// The built-in vexo templates as `(name, source)` pairs
+pub fn builtin_templates() -> &'static [(&'static str, &'static str)] {
+    BUILTIN_TEMPLATES
+}
+
+/// The built-in vexo language files as `(lang, yaml)` pairs
+pub fn builtin_languages() -> &'static [(&'static str, &'static str)] {
+    BUILTIN_LANGUAGES
+}
+
+/// Custom Tera filters, functions and testers registered by library users.
+/// They are added to every renderer after the built-in ones, so they can
+/// also replace a built-in with the same name.
+#[derive(Clone, Default)]
+pub struct TemplateExtensions {
+    filters: Vec<(String, Arc<dyn tera::Filter>)>,
+    functions: Vec<(String, Arc<dyn tera::Function>)>,
+    testers: Vec<(String, Arc<dyn tera::Test>)>,
+}
+
+impl TemplateExtensions {
+    /// Register a filter, used as `{{ value | name(arg=...) }}`
+    pub fn register_filter<F: tera::Filter + 'static>(&mut self, name: &str, filter: F) {
+        self.filters.push((name.to_string(), Arc::new(filter)));
+    }
+
+    /// Register a function, used as `{{ name(arg=...) }}`
+    pub fn register_function<F: tera::Function + 'static>(&mut self, name: &str, function: F) {
+        self.functions.push((name.to_string(), Arc::new(function)));
+    }
+
+    /// Register a tester, used as `{% if value is name %}`
+    pub fn register_tester<T: tera::Test + 'static>(&mut self, name: &str, tester: T) {
+        self.testers.push((name.to_string(), Arc::new(tester)));
+    }
+
+    fn apply(&self, tera: &mut Tera) {
+        for (name, filter) in &self.filters {
+            tera.register_filter(name, Shared(filter.clone()));
+        }
+        for (name, function) in &self.functions {
+            tera.register_function(name, Shared(function.clone()));
+        }
+        for (name, tester) in &self.testers {
+            tera.register_tester(name, Shared(tester.clone()));
+        }
+    }
+}
+
+/// Lets one registered extension be handed to several Tera instances
+struct Shared<T: ?Sized>(Arc<T>);
+
+impl tera::Filter for Shared<dyn tera::Filter> {
+    fn filter(
+        &self,
+        value: &tera::Value,
+        args: &HashMap<String, tera::Value>,
+    ) -> tera::Result<tera::Value> {
+        self.0.filter(value, args)
+    }
+
+    fn is_safe(&self) -> bool {
+        self.0.is_safe()
+    }
+}
+
+impl tera::Function for Shared<dyn tera::Function> {
+    fn call(&self, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        self.0.call(args)
+    }
+
+    fn is_safe(&self) -> bool {
+        self.0.is_safe()
+    }
+}
+
+impl tera::Test for Shared<dyn tera::Test> {
+    fn test(&self, value: Option<&tera::Value>, args: &[tera::Value]) -> tera::Result<bool> {
+        self.0.test(value, args)
+    }
+}
+
+/// The embedded templates, parsed once per process so that every
+/// `TemplateRenderer` (one per rebuild in watch mode) starts from a copy
+struct BuiltinTemplates {
+    tera: Tera,
+    dependencies: HashMap<String, Vec<String>>,
+    sources: HashMap<String, String>,
+}
+
+fn parsed_builtin() -> Result<&'static BuiltinTemplates> {
+    static BUILTIN: OnceLock<std::result::Result<BuiltinTemplates, String>> = OnceLock::new();
+    let builtin = BUILTIN.get_or_init(|| {
+        let mut tera = Tera::default();
+
+        // Disable autoescaping for HTML templates since we're generating HTML
+        // and URLs/paths should not be escaped
+        tera.autoescape_on(vec![]);
+        tera.add_raw_templates(BUILTIN_TEMPLATES.to_vec())
+            .map_err(|e| format!("{:#}", anyhow::Error::from(e)))?;
+
+        let dependencies = BUILTIN_TEMPLATES
+            .iter()
+            .map(|(name, source)| (name.to_string(), template_refs(source)))
+            .collect();
+        let sources = BUILTIN_TEMPLATES
+            .iter()
+            .map(|(name, source)| (name.to_string(), source.to_string()))
+            .collect();
+        Ok(BuiltinTemplates {
+            tera,
+            dependencies,
+            sources,
+        })
+    });
+    builtin.as_ref().map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+/// Template renderer with embedded vexo theme
+pub struct TemplateRenderer {
+    tera: Tera,
+    /// Templates each template extends, includes or imports
+    dependencies: HashMap<String, Vec<String>>,
+    /// Template sources, for locating errors
+    sources: HashMap<String, String>,
+    fragments: Arc<FragmentCache>,
+    /// `site.posts` of the current build, for the post list functions
+    posts: Arc<RwLock<Vec<tera::Value>>>,
+    /// "Now" for the current build, see `set_build_time`
+    now: Arc<RwLock<DateTime<Utc>>>,
+    /// Render errors collected instead of failing, see `collect_errors`
+    errors: Option<Mutex<Vec<TemplateError>>>,
+}
+
+impl TemplateRenderer {
+    /// Create a new renderer with all vexo templates loaded
+    pub fn new() -> Result<Self> {
+        let builtin = parsed_builtin()?;
+        let mut tera = builtin.tera.clone();
 
         // Register custom filters
         tera.register_filter("strip_html", strip_html_filter);
         tera.register_filter("truncate_chars", truncate_chars_filter);
         tera.register_filter("date_format", date_format_filter);
+        tera.register_filter("number_format", number_format_filter);
+        tera.register_filter("titlecase", titlecase_filter);
+        tera.register_filter("word_wrap", word_wrap_filter);
+        tera.register_filter("sum", sum_filter);
+        tera.register_filter("min", min_filter);
+        tera.register_filter("max", max_filter);
+        tera.register_filter("regex_replace", RegexReplaceFilter::default());
+        // Nunjucks-compatible names used by translated .njk/.swig layouts
+        tera.register_filter("dump", dump_filter);
+
+        // Register helper functions
+        tera.register_function("link_to", link_to_function);
+        tera.register_function("__", TranslateFunction::new(Vec::new(), ""));
+        tera.register_function("toc", toc_function);
+        tera.register_function("meta_generator", meta_generator_function);
+        let now = Arc::new(RwLock::new(Utc::now()));
+        tera.register_filter("relative_date", RelativeDateFilter(now.clone()));
+        let fragments = Arc::new(FragmentCache::default());
+        tera.register_function("fragment_cache", FragmentCacheFunction(fragments.clone()));
+        tera.register_function("partial", PartialFunction(fragments.clone()));
+        let posts: Arc<RwLock<Vec<tera::Value>>> = Arc::default();
+        let lists: [(&str, PostListHelper); 3] = [
+            ("list_posts", list_posts_function),
+            ("recent_posts", recent_posts_function),
+            ("archive_count", archive_count_function),
+        ];
+        for (name, helper) in lists {
+            tera.register_function(
+                name,
+                PostListFunction {
+                    posts: posts.clone(),
+                    helper,
+                },
+            );
+        }
+
+        Ok(Self {
+            tera,
+            dependencies: builtin.dependencies.clone(),
+            sources: builtin.sources.clone(),
+            fragments,
+            posts,
+            now,
+            errors: None,
+        })
+    }
+
+    /// Register custom filters, functions and testers
+    pub fn extend(&mut self, extensions: &TemplateExtensions) {
+        extensions.apply(&mut self.tera);
+    }
 
-        Ok(Self { tera })
+    /// Register helpers that read the site configuration
+    pub fn configure(&mut self, config: &crate::config::SiteConfig) {
+        let config = Arc::new(config.clone());
+        let helpers: [(&str, fn(&_, &_) -> _); 6] = [
+            ("open_graph", |args, config| {
+                Ok(crate::helpers::open_graph(args, config))
+            }),
+            ("js", crate::helpers::js),
+            ("css", crate::helpers::css),
+            ("url_for", |args, config| {
+                let path = path_arg("url_for", args)?;
+                let from = args.get("from").and_then(|v| v.as_str());
+                Ok(crate::helpers::url_for(&path, config, from))
+            }),
+            ("full_url_for", |args, config| {
+                let path = path_arg("full_url_for", args)?;
+                Ok(crate::helpers::full_url_for(&path, config))
+            }),
+            ("canonical", |args, config| {
+                let path = path_arg("canonical", args)?;
+                Ok(crate::helpers::canonical(&path, config))
+            }),
+        ];
+        for (name, helper) in helpers {
+            self.tera.register_function(
+                name,
+                SiteHelper {
+                    config: config.clone(),
+                    helper,
+                },
+            );
+        }
+
+        let markdown = crate::content::MarkdownRenderer::with_config(config.markdown.clone())
+            .with_highlight(config.highlight.clone());
+        self.tera
+            .register_filter("markdown", MarkdownFilter(Arc::new(markdown)));
+    }
+
+    /// Set the theme's language files (`languages/<lang>.yml`, ancestor
+    /// themes first) for `__`, translating into `default` unless a template
+    /// asks for another language
+    pub fn set_languages(&mut self, languages: Vec<(String, serde_yaml::Value)>, default: &str) {
+        self.tera
+            .register_function("__", TranslateFunction::new(languages, default));
+    }
+
+    /// Add theme templates, replacing built-in templates with the same name
+    pub fn add_templates(&mut self, templates: Vec<(String, String)>) -> Result<()> {
+        if !templates.is_empty() {
+            for (name, source) in &templates {
+                self.dependencies
+                    .insert(name.clone(), template_refs(source));
+                self.sources.insert(name.clone(), source.clone());
+            }
+            self.tera
+                .add_raw_templates(templates)
+                .map_err(|e| TemplateError::new(&e, "theme templates", &self.sources))?;
+        }
+        Ok(())
+    }
+
+    /// Every template affected by a change to `changed`: the changed
+    /// templates plus all templates that extend, include or import them, or
+    /// were seen rendering them (see `template_usage`), directly or indirectly
+    pub fn dependents(&self, changed: &[String]) -> HashSet<String> {
+        let mut affected: HashSet<String> = changed.iter().cloned().collect();
+        let used = self.fragments.usage.get();
+        loop {
+            let before = affected.len();
+            for (name, refs) in &self.dependencies {
+                if refs.iter().any(|r| affected.contains(r)) {
+                    affected.insert(name.clone());
+                }
+            }
+            for (name, refs) in &used {
+                if refs.iter().any(|r| affected.contains(r)) {
+                    affected.insert(name.clone());
+                }
+            }
+            if affected.len() == before {
+                return affected;
+            }
+        }
+    }
+
+    /// Start a build with the site-wide variables `fragment_cache` and
+    /// `partial` render with, dropping fragments cached by the previous build.
+    /// `site.posts` is what the post list functions list.
+    pub fn start_build(&self, globals: Context) {
+        *self.posts.write().unwrap() = globals
+            .get("site")
+            .and_then(|site| site.get("posts"))
+            .and_then(|posts| posts.as_array())
+            .cloned()
+            .unwrap_or_default();
+        self.fragments.start(&self.tera, globals);
+    }
+
+    /// Templates each template rendered through `partial` or
+    /// `fragment_cache`, in this build and those added with
+    /// `add_template_usage`
+    pub fn template_usage(&self) -> BTreeMap<String, BTreeSet<String>> {
+        self.fragments.usage.get()
+    }
+
+    /// Add template usage recorded by an earlier build
+    pub fn add_template_usage(&self, used: BTreeMap<String, BTreeSet<String>>) {
+        self.fragments.usage.extend(used);
+    }
+
+    /// Set the time templates see as "now" (e.g. for `relative_date`)
+    pub fn set_build_time(&self, now: DateTime<Utc>) {
+        *self.now.write().unwrap() = now;
+    }
+
+    pub fn build_time(&self) -> DateTime<Utc> {
+        *self.now.read().unwrap()
+    }
+
+    /// Record how long each template and partial takes to render
+    pub fn enable_profiling(&self) {
+        self.fragments.profile.enable();
+    }
+
+    /// Recorded render times, slowest first
+    pub fn timings(&self) -> Vec<(String, TemplateTiming)> {
+        self.fragments.profile.timings()
+    }
+
+    /// Whether any template reads the `content` of items of `collections`,
+    /// e.g. `reads_content(&["site.posts"])` for `{{ site.posts[0].content }}`
+    /// or `{% for post in site.posts %}{{ post.content }}{% endfor %}`
+    pub fn reads_content(&self, collections: &[&str]) -> bool {
+        let sources: Vec<&str> = self.sources.values().map(String::as_str).collect();
+        lint::reads_content(&sources, collections)
+    }
+
+    /// Whether a template with this name is registered
+    pub fn has_template(&self, name: &str) -> bool {
+        self.tera.get_template_names().any(|n| n == name)
     }
 
     /// Render a template with given context
     pub fn render(&self, template_name: &str, context: &Context) -> Result<String> {
-        Ok(self.tera.render(template_name, context)?)
+        let fragments = &self.fragments;
+        let result = fragments.usage.track(template_name, || {
+            fragments
+                .profile
+                .time(template_name, || self.tera.render(template_name, context))
+        });
+        match (result, &self.errors) {
+            (Ok(html), _) => Ok(html),
+            (Err(e), Some(errors)) => {
+                let error = TemplateError::new(&e, template_name, &self.sources);
+                errors.lock().unwrap().push(error);
+                Ok(String::new())
+            }
+            (Err(e), None) => Err(TemplateError::new(&e, template_name, &self.sources).into()),
+        }
+    }
+
+    /// Keep going when a render fails, rendering an empty page and
+    /// collecting the error for `take_errors`
+    pub fn collect_errors(&mut self) {
+        self.errors = Some(Mutex::new(Vec::new()));
+    }
+
+    /// Render errors collected since `collect_errors`
+    pub fn take_errors(&self) -> Vec<TemplateError> {
+        self.errors
+            .as_ref()
+            .map(|errors| std::mem::take(&mut *errors.lock().unwrap()))
+            .unwrap_or_default()
+    }
+
+    /// Templates referenced by `extends`, `include`, `import` or
+    /// `partial(template=...)` that don't exist, as (template, reference)
+    pub fn missing_templates(&self) -> Vec<(String, String)> {
+        let mut missing: Vec<(String, String)> = self
+            .dependencies
+            .iter()
+            .flat_map(|(name, refs)| {
+                refs.iter()
+                    .filter(|r| !self.has_template(r))
+                    .map(move |r| (name.clone(), r.clone()))
+            })
+            .collect();
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+
+    /// Filters, functions and tests used by templates but not registered,
+    /// as (template, kind, name)
+    pub fn unknown_helpers(&self) -> Vec<(String, HelperKind, String)> {
+        let mut probe = self.tera.clone();
+        let mut known: HashMap<(HelperKind, String), bool> = HashMap::new();
+        let mut unknown = Vec::new();
+        let mut names: Vec<&String> = self.sources.keys().collect();
+        names.sort();
+        for name in names {
+            for (kind, helper) in lint::helper_calls(&self.sources[name]) {
+                let exists = *known.entry((kind, helper.clone())).or_insert_with(|| {
+                    // A probe that fails for any other reason means the
+                    // helper is registered
+                    probe
+                        .add_raw_template("__probe__", &kind.probe(&helper))
+                        .and_then(|_| probe.render("__probe__", &Context::new()))
+                        .err()
+                        .map(|e| {
+                            let mut source: Option<&dyn std::error::Error> = Some(&e);
+                            let not_found = kind.not_found(&helper);
+                            while let Some(err) = source {
+                                if err.to_string().contains(&not_found) {
+                                    return false;
+                                }
+                                source = err.source();
+                            }
+                            true
+                        })
+                        .unwrap_or(true)
+                });
+                if !exists {
+                    unknown.push((name.clone(), kind, helper));
+                }
+            }
+        }
+        unknown
     }
 }
 
+/// Names referenced by `extends`, `include` and `import` tags in a template
+fn template_refs(source: &str) -> Vec<String> {
+    let mut refs = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find("{%") {
+        rest = &rest[start + 2..];
+        let tag = rest.trim_start_matches('-').trim_start();
+        let Some(args) = ["extends", "include", "import"]
+            .iter()
+            .find_map(|kw| tag.strip_prefix(kw))
+        else {
+            continue;
+        };
+        let args = args.trim_start();
+        let Some(quote) = args.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            continue;
+        };
+        if let Some(end) = args[1..].find(quote) {
+            refs.push(args[1..end + 1].to_string());
+        }
+    }
+
+    // Templates rendered through `fragment_cache`/`partial(template="...")`
+    let mut rest = source;
+    while let Some(start) = rest.find("template=") {
+        rest = &rest[start + "template=".len()..];
+        let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            continue;
+        };
+        if let Some(end) = rest[1..].find(quote) {
+            refs.push(rest[1..end + 1].to_string());
+        }
+    }
+    refs
+}
+
 /// Tera filter: strip HTML tags
 fn strip_html_filter(
     value: &tera::Value,
@@ -98,6 +636,209 @@ fn strip_html_filter(
     Ok(tera::Value::String(result))
 }
 
+/// Tera filter: group digits, e.g.
+/// `count | number_format(precision=2, delimiter=",", separator=".")`
+fn number_format_filter(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let number = tera::try_get_value!("number_format", "value", f64, value);
+    let precision = match args.get("precision") {
+        Some(val) => Some(tera::try_get_value!(
+            "number_format",
+            "precision",
+            usize,
+            val
+        )),
+        None => None,
+    };
+    let delimiter = match args.get("delimiter") {
+        Some(val) => tera::try_get_value!("number_format", "delimiter", String, val),
+        None => ",".to_string(),
+    };
+    let separator = match args.get("separator") {
+        Some(val) => tera::try_get_value!("number_format", "separator", String, val),
+        None => ".".to_string(),
+    };
+    Ok(tera::Value::String(crate::helpers::number_format(
+        number, precision, &delimiter, &separator,
+    )))
+}
+
+/// Tera filter: title case, keeping short words lowercase
+fn titlecase_filter(
+    value: &tera::Value,
+    _args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let s = tera::try_get_value!("titlecase", "value", String, value);
+    Ok(tera::Value::String(crate::helpers::titlecase(&s)))
+}
+
+/// Tera filter: wrap text at `width` characters (default 80)
+fn word_wrap_filter(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let s = tera::try_get_value!("word_wrap", "value", String, value);
+    let width = match args.get("width") {
+        Some(val) => tera::try_get_value!("word_wrap", "width", usize, val),
+        None => 80,
+    };
+    Ok(tera::Value::String(crate::helpers::word_wrap(&s, width)))
+}
+
+/// The numbers in an array, or in each item's `attribute` (a dotted path
+/// like `"meta.words"`), skipping missing and null values
+fn numbers(
+    filter: &str,
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> tera::Result<Vec<serde_json::Number>> {
+    let items = tera::try_get_value!(filter, "value", Vec<tera::Value>, value);
+    let attribute = match args.get("attribute") {
+        Some(val) => Some(tera::try_get_value!(filter, "attribute", String, val)),
+        None => None,
+    };
+
+    let mut numbers = Vec::new();
+    for item in &items {
+        let item = match &attribute {
+            Some(path) => path.split('.').fold(item, |v, key| &v[key]),
+            None => item,
+        };
+        match item {
+            tera::Value::Null => {}
+            tera::Value::Number(n) => numbers.push(n.clone()),
+            other => {
+                return Err(tera::Error::msg(format!(
+                    "Filter `{}` expected numbers, got `{}`",
+                    filter, other
+                )))
+            }
+        }
+    }
+    Ok(numbers)
+}
+
+/// Tera filter: sum of an array of numbers, or of each item's `attribute`.
+/// Missing and null values count as 0.
+fn sum_filter(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let numbers = numbers("sum", value, args)?;
+    if let Some(sum) = numbers
+        .iter()
+        .try_fold(0i64, |sum, n| sum.checked_add(n.as_i64()?))
+    {
+        return Ok(tera::Value::from(sum));
+    }
+    let sum: f64 = numbers.iter().filter_map(|n| n.as_f64()).sum();
+    Ok(tera::to_value(sum)?)
+}
+
+/// Tera filter: smallest number of an array, or of each item's `attribute`;
+/// null for an empty array
+fn min_filter(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let numbers = numbers("min", value, args)?;
+    Ok(extreme(numbers, std::cmp::Ordering::Less))
+}
+
+/// Tera filter: largest number of an array, or of each item's `attribute`;
+/// null for an empty array
+fn max_filter(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let numbers = numbers("max", value, args)?;
+    Ok(extreme(numbers, std::cmp::Ordering::Greater))
+}
+
+/// The number that compares as `wanted` against all the others
+fn extreme(numbers: Vec<serde_json::Number>, wanted: std::cmp::Ordering) -> tera::Value {
+    numbers
+        .into_iter()
+        .reduce(|best, n| {
+            let (a, b) = (
+                n.as_f64().unwrap_or_default(),
+                best.as_f64().unwrap_or_default(),
+            );
+            if a.partial_cmp(&b) == Some(wanted) {
+                n
+            } else {
+                best
+            }
+        })
+        .map_or(tera::Value::Null, tera::Value::Number)
+}
+
+/// Tera filter: replace matches of the regex `pattern` with `rep`, which can
+/// refer to groups as `$1` or `${name}`. Compiled patterns are reused across
+/// renders.
+#[derive(Default)]
+struct RegexReplaceFilter(Mutex<HashMap<String, regex::Regex>>);
+
+impl tera::Filter for RegexReplaceFilter {
+    fn filter(
+        &self,
+        value: &tera::Value,
+        args: &HashMap<String, tera::Value>,
+    ) -> tera::Result<tera::Value> {
+        let s = tera::try_get_value!("regex_replace", "value", String, value);
+        let pattern = match args.get("pattern") {
+            Some(val) => tera::try_get_value!("regex_replace", "pattern", String, val),
+            None => {
+                return Err(tera::Error::msg(
+                    "Filter `regex_replace` expected an arg called `pattern`",
+                ))
+            }
+        };
+        let rep = match args.get("rep") {
+            Some(val) => tera::try_get_value!("regex_replace", "rep", String, val),
+            None => String::new(),
+        };
+
+        let mut patterns = self.0.lock().unwrap();
+        let regex = match patterns.get(&pattern) {
+            Some(regex) => regex,
+            None => {
+                let regex = regex::Regex::new(&pattern).map_err(|e| {
+                    tera::Error::msg(format!("regex_replace: invalid pattern: {}", e))
+                })?;
+                patterns.entry(pattern).or_insert(regex)
+            }
+        };
+        Ok(tera::Value::String(
+            regex.replace_all(&s, rep.as_str()).into_owned(),
+        ))
+    }
+}
+
+/// Tera filter: render markdown with the site's markdown settings
+struct MarkdownFilter(Arc<crate::content::MarkdownRenderer>);
+
+impl tera::Filter for MarkdownFilter {
+    fn filter(
+        &self,
+        value: &tera::Value,
+        _args: &HashMap<String, tera::Value>,
+    ) -> tera::Result<tera::Value> {
+        let s = tera::try_get_value!("markdown", "value", String, value);
+        let html = self
+            .0
+            .render(&s)
+            .map_err(|e| tera::Error::msg(format!("markdown: {}", e)))?;
+        Ok(tera::Value::String(html))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
 /// Tera filter: truncate by character count
 fn truncate_chars_filter(
     value: &tera::Value,
@@ -148,19 +889,272 @@ fn date_format_filter(
     Ok(tera::Value::String(s))
 }
 
+/// Tera filter: how long ago a date was at build time, e.g. "3 days ago"
+struct RelativeDateFilter(Arc<RwLock<DateTime<Utc>>>);
+
+impl tera::Filter for RelativeDateFilter {
+    fn filter(
+        &self,
+        value: &tera::Value,
+        _args: &HashMap<String, tera::Value>,
+    ) -> tera::Result<tera::Value> {
+        let s = tera::try_get_value!("relative_date", "value", String, value);
+        let date = crate::content::parse_date_string(&s)
+            .ok_or_else(|| tera::Error::msg(format!("relative_date: invalid date `{}`", s)))?;
+        let now = *self.0.read().unwrap();
+        Ok(tera::Value::String(crate::helpers::relative_date(
+            &date, &now,
+        )))
+    }
+}
+
+/// Tera function: render an anchor tag, e.g. `link_to(path=url, text=name, rel="nofollow")`
+fn link_to_function(args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+    let path = match args.get("path") {
+        Some(val) => tera::try_get_value!("link_to", "path", String, val),
+        None => return Err("link_to: missing `path` argument".into()),
+    };
+    let text = match args.get("text") {
+        Some(val) => tera::try_get_value!("link_to", "text", String, val),
+        None => path.clone(),
+    };
+    let rel = match args.get("rel") {
+        Some(val) => tera::try_get_value!("link_to", "rel", String, val),
+        None => String::new(),
+    };
+    Ok(tera::Value::String(crate::helpers::link_to(
+        &path, &text, &rel,
+    )))
+}
+
+/// Tera function backed by a helper that reads the site configuration,
+/// e.g. `open_graph(page=page)` or `js(src="app.js", defer=true)`
+struct SiteHelper {
+    config: Arc<crate::config::SiteConfig>,
+    helper: fn(&HashMap<String, tera::Value>, &crate::config::SiteConfig) -> Result<String, String>,
+}
+
+impl tera::Function for SiteHelper {
+    fn call(&self, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let html = (self.helper)(args, &self.config)?;
+        Ok(tera::Value::String(html))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// The `path` argument of a site helper
+fn path_arg(name: &str, args: &HashMap<String, tera::Value>) -> Result<String, String> {
+    args.get("path")
+        .and_then(|v| v.as_str())
+        .map(String::from)
+        .ok_or_else(|| format!("{}: missing `path` argument", name))
+}
+
+/// Tera function: `<meta name="generator">` naming hexo-rs and its version
+fn meta_generator_function(_args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+    Ok(tera::Value::String(format!(
+        r#"<meta name="generator" content="hexo-rs {}">"#,
+        env!("CARGO_PKG_VERSION")
+    )))
+}
+
+/// Tera function: table of contents of rendered HTML, e.g.
+/// `toc(content=page_content, max_depth=3, list_number=false)`
+fn toc_function(args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+    let content = match args.get("content") {
+        Some(val) => tera::try_get_value!("toc", "content", String, val),
+        None => return Err("toc: missing `content` argument".into()),
+    };
+    let mut options = crate::helpers::TocOptions::default();
+    if let Some(val) = args.get("class") {
+        options.class = tera::try_get_value!("toc", "class", String, val);
+    }
+    if let Some(val) = args.get("list_number") {
+        options.list_number = tera::try_get_value!("toc", "list_number", bool, val);
+    }
+    if let Some(val) = args.get("min_depth") {
+        options.min_depth = tera::try_get_value!("toc", "min_depth", usize, val);
+    }
+    if let Some(val) = args.get("max_depth") {
+        options.max_depth = tera::try_get_value!("toc", "max_depth", usize, val);
+    }
+    Ok(tera::Value::String(crate::helpers::toc_with(
+        &content, &options,
+    )))
+}
+
+type PostListHelper =
+    fn(&[tera::Value], &HashMap<String, tera::Value>) -> tera::Result<tera::Value>;
+
+/// Tera function listing the site's posts, e.g. `list_posts(amount=5)`
+struct PostListFunction {
+    posts: Arc<RwLock<Vec<tera::Value>>>,
+    helper: PostListHelper,
+}
+
+impl tera::Function for PostListFunction {
+    fn call(&self, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        (self.helper)(&self.posts.read().unwrap(), args)
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// Tera function: links to posts, e.g.
+/// `list_posts(amount=5, orderby="title", order=1, style="none")`
+fn list_posts_function(
+    posts: &[tera::Value],
+    args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let mut options = crate::helpers::ListPostsOptions::default();
+    if let Some(val) = args.get("amount") {
+        options.amount = tera::try_get_value!("list_posts", "amount", usize, val);
+    }
+    if let Some(val) = args.get("orderby") {
+        options.orderby = tera::try_get_value!("list_posts", "orderby", String, val);
+    }
+    if let Some(val) = args.get("order") {
+        options.order = tera::try_get_value!("list_posts", "order", i64, val);
+    }
+    if let Some(val) = args.get("style") {
+        options.style = tera::try_get_value!("list_posts", "style", String, val);
+    }
+    if let Some(val) = args.get("separator") {
+        options.separator = tera::try_get_value!("list_posts", "separator", String, val);
+    }
+    if let Some(val) = args.get("class") {
+        options.class = tera::try_get_value!("list_posts", "class", String, val);
+    }
+    Ok(tera::Value::String(crate::helpers::list_posts(
+        posts, &options,
+    )))
+}
+
+/// Tera function: the newest posts, e.g.
+/// `{% for post in recent_posts(amount=5) %}`
+fn recent_posts_function(
+    posts: &[tera::Value],
+    args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let amount = match args.get("amount") {
+        Some(val) => tera::try_get_value!("recent_posts", "amount", usize, val),
+        None => 5,
+    };
+    Ok(tera::Value::Array(crate::helpers::recent_posts(
+        posts, amount,
+    )))
+}
+
+/// Tera function: number of posts, optionally in a `year` and `month`
+fn archive_count_function(
+    posts: &[tera::Value],
+    args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let year = match args.get("year") {
+        Some(val) => Some(tera::try_get_value!("archive_count", "year", i32, val)),
+        None => None,
+    };
+    let month = match args.get("month") {
+        Some(val) => Some(tera::try_get_value!("archive_count", "month", u32, val)),
+        None => None,
+    };
+    Ok(tera::Value::from(crate::helpers::archive_count(
+        posts, year, month,
+    )))
+}
+
+/// Tera filter: serialize a value to JSON (Nunjucks `dump`)
+fn dump_filter(
+    value: &tera::Value,
+    _args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    Ok(tera::Value::String(value.to_string()))
+}
+
+/// Tera function: `__(key="...", lang="...")` i18n lookup used by Nunjucks
+/// themes, reading the theme's `languages/<lang>.yml` files. Dotted keys
+/// (`menu.home`) address nested entries. `lang` defaults to the site
+/// language, and missing entries fall back to the language without its
+/// region (`zh` for `zh-CN`), then `default.yml`, then the key itself.
+struct TranslateFunction {
+    languages: Arc<BTreeMap<String, serde_json::Value>>,
+    default: String,
+}
+
+impl TranslateFunction {
+    /// Merge `languages` over the built-in tables
+    fn new(languages: Vec<(String, serde_yaml::Value)>, default: &str) -> Self {
+        let builtin = BUILTIN_LANGUAGES.iter().filter_map(|(lang, yaml)| {
+            let table = serde_yaml::from_str::<serde_yaml::Value>(yaml).ok()?;
+            Some((lang.to_string(), table))
+        });
+        let mut merged: BTreeMap<String, serde_yaml::Value> = BTreeMap::new();
+        for (lang, table) in builtin.chain(languages) {
+            match merged.get_mut(&lang) {
+                Some(existing) => crate::config::merge_yaml(existing, table),
+                None => {
+                    merged.insert(lang, table);
+                }
+            }
+        }
+        let languages = merged
+            .into_iter()
+            .filter_map(|(lang, table)| Some((lang, serde_json::to_value(table).ok()?)))
+            .collect();
+        Self {
+            languages: Arc::new(languages),
+            default: default.to_string(),
+        }
+    }
+
+    fn lookup(&self, key: &str, lang: &str) -> Option<&str> {
+        let base = lang.split(['-', '_']).next().unwrap_or(lang);
+        [lang, base, "default"].into_iter().find_map(|lang| {
+            key.split('.')
+                .try_fold(self.languages.get(lang)?, |value, part| value.get(part))?
+                .as_str()
+        })
+    }
+}
+
+impl tera::Function for TranslateFunction {
+    fn call(&self, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let key = match args.get("key") {
+            Some(val) => tera::try_get_value!("__", "key", String, val),
+            None => return Err("__: missing `key` argument".into()),
+        };
+        let lang = args
+            .get("lang")
+            .and_then(|v| v.as_str())
+            .unwrap_or(&self.default);
+        let text = self.lookup(&key, lang).unwrap_or(&key);
+        Ok(tera::Value::String(text.to_string()))
+    }
+}
+
 /// Data structures for template context
 
 #[derive(Debug, Clone, Serialize)]
 pub struct SiteData {
     pub posts: Vec<PostData>,
     pub pages: Vec<PageData>,
-    pub tags: HashMap<String, usize>,
-    pub categories: HashMap<String, usize>,
+    pub tags: BTreeMap<String, usize>,
+    pub categories: BTreeMap<String, usize>,
     pub word_count: usize,
+    /// Term counts for custom taxonomies: taxonomy -> term -> post count
+    pub taxonomies: BTreeMap<String, BTreeMap<String, usize>>,
+    /// Data files from source/_data, keyed by file name without extension
+    pub data: BTreeMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct PostData {
+    pub uuid: String,
     pub title: String,
     pub date: String,
     pub path: String,
@@ -170,6 +1164,11 @@ pub struct PostData {
     pub content: String,
     pub excerpt: Option<String>,
     pub word_count: usize,
+    /// Estimated reading time in minutes
+    pub reading_time: usize,
+    pub is_sticky: bool,
+    /// Cover image (front matter `cover`/`photos`, or the first image)
+    pub cover: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -200,6 +1199,32 @@ pub struct NavPost {
     pub path: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesData {
+    pub name: String,
+    pub path: String,
+    /// 1-based position of the current post (0 on the series landing page)
+    pub position: usize,
+    pub total: usize,
+    /// Posts in reading order (oldest first)
+    pub posts: Vec<SeriesPost>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesPost {
+    pub title: String,
+    pub path: String,
+    pub date: String,
+    pub is_current: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranslationData {
+    pub lang: String,
+    pub title: String,
+    pub path: String,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct ArchiveYearData {
     pub year: i32,
@@ -226,6 +1251,8 @@ pub struct ConfigData {
     pub per_page: usize,
     pub github_username: String,
     pub keyword: String,
+    /// Whether css/highlight.css was generated for code blocks
+    pub highlight_css: bool,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -240,6 +1267,17 @@ pub struct ThemeData {
     pub mathjax_enable: bool,
     pub mathjax_cdn: String,
     pub comment: String,
+    pub links: Vec<LinkData>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkData {
+    pub name: String,
+    pub url: String,
+    pub description: String,
+    pub avatar: String,
+    /// Normalized `rel` attribute (e.g. "nofollow noopener")
+    pub rel: String,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -254,3 +1292,382 @@ pub struct AboutData {
     pub github_username: String,
     pub twitter_username: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_dependents() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let affected = renderer.dependents(&["partials/catalog.html".to_string()]);
+        assert!(affected.contains("page.html"));
+        assert!(!affected.contains("index.html"));
+
+        let affected = renderer.dependents(&["layout.html".to_string()]);
+        assert!(affected.contains("index.html"));
+        assert!(affected.contains("archive.html"));
+    }
+
+    #[test]
+    fn test_template_usage() {
+        let mut renderer = TemplateRenderer::new().unwrap();
+        renderer
+            .add_templates(vec![
+                ("widgets/a.html".to_string(), "a".to_string()),
+                (
+                    "widgets/b.html".to_string(),
+                    r#"b{{ partial(template="widgets/a.html") }}"#.to_string(),
+                ),
+                (
+                    "custom.html".to_string(),
+                    "{{ partial(template=widget) }}".to_string(),
+                ),
+            ])
+            .unwrap();
+        renderer.start_build(Context::new());
+        let changed = ["widgets/a.html".to_string()];
+        assert!(!renderer.dependents(&changed).contains("custom.html"));
+
+        let mut context = Context::new();
+        context.insert("widget", "widgets/b.html");
+        assert_eq!(renderer.render("custom.html", &context).unwrap(), "ba");
+        // A second render takes the partial from the cache
+        assert_eq!(renderer.render("custom.html", &context).unwrap(), "ba");
+        let affected = renderer.dependents(&changed);
+        assert!(affected.contains("custom.html"));
+        assert!(affected.contains("widgets/b.html"));
+
+        let fresh = TemplateRenderer::new().unwrap();
+        fresh.add_template_usage(renderer.template_usage());
+        assert!(fresh.dependents(&changed).contains("custom.html"));
+    }
+
+    #[test]
+    fn test_translate() {
+        let table = |yaml: &str| serde_yaml::from_str::<serde_yaml::Value>(yaml).unwrap();
+        let mut renderer = TemplateRenderer::new().unwrap();
+        renderer.set_languages(
+            vec![
+                (
+                    "default".to_string(),
+                    table("menu:\n  home: Home\n  tags: Tags\n"),
+                ),
+                ("zh".to_string(), table("menu:\n  home: 首页\n")),
+                ("zh-CN".to_string(), table("menu:\n  archives: 归档\n")),
+            ],
+            "zh-CN",
+        );
+        renderer
+            .add_templates(vec![(
+                "custom.html".to_string(),
+                "{{ __(key='menu.home') }} {{ __(key='menu.archives') }} \
+                 {{ __(key='menu.tags') }} {{ __(key='menu.gone') }} \
+                 {{ __(key='menu.home', lang='en') }} {{ __(key='sticky') }}"
+                    .to_string(),
+            )])
+            .unwrap();
+        assert_eq!(
+            renderer.render("custom.html", &Context::new()).unwrap(),
+            "首页 归档 Tags menu.gone Home 置顶"
+        );
+
+        // Themes can override the built-in labels
+        renderer.set_languages(
+            vec![(
+                "en".to_string(),
+                table(
+                    "sticky: Pinned
+",
+                ),
+            )],
+            "en",
+        );
+        assert_eq!(
+            renderer.render("custom.html", &Context::new()).unwrap(),
+            "menu.home menu.archives menu.tags menu.gone menu.home Pinned"
+        );
+    }
+
+    #[test]
+    fn test_custom_extensions() {
+        let mut extensions = TemplateExtensions::default();
+        extensions.register_filter(
+            "shout",
+            |value: &tera::Value, _: &HashMap<String, tera::Value>| {
+                let s = tera::try_get_value!("shout", "value", String, value);
+                Ok(tera::Value::String(s.to_uppercase()))
+            },
+        );
+        extensions.register_function("answer", |_: &HashMap<String, tera::Value>| {
+            Ok(tera::Value::from(42))
+        });
+        extensions.register_tester("long", |value: Option<&tera::Value>, _: &[tera::Value]| {
+            Ok(value.and_then(|v| v.as_str()).is_some_and(|s| s.len() > 3))
+        });
+
+        let mut renderer = TemplateRenderer::new().unwrap();
+        renderer.extend(&extensions);
+        renderer
+            .add_templates(vec![(
+                "custom.html".to_string(),
+                "{{ name | shout }} {{ answer() }} {% if name is long %}long{% endif %}"
+                    .to_string(),
+            )])
+            .unwrap();
+
+        let mut context = Context::new();
+        context.insert("name", "hexo");
+        let html = renderer.render("custom.html", &context).unwrap();
+        assert_eq!(html, "HEXO 42 long");
+    }
+
+    #[test]
+    fn test_fragment_cache() {
+        let mut renderer = TemplateRenderer::new().unwrap();
+        renderer
+            .add_templates(vec![
+                (
+                    "widget.html".to_string(),
+                    "{{ config.title }} {{ counter }}".to_string(),
+                ),
+                (
+                    "custom.html".to_string(),
+                    r#"{{ fragment_cache(name="w", template="widget.html", counter=counter) }}"#
+                        .to_string(),
+                ),
+            ])
+            .unwrap();
+
+        let mut globals = Context::new();
+        globals.insert("config", &serde_json::json!({ "title": "Blog" }));
+        renderer.start_build(globals.clone());
+
+        let mut context = globals.clone();
+        context.insert("counter", &1);
+        assert_eq!(renderer.render("custom.html", &context).unwrap(), "Blog 1");
+        // Later pages reuse the first rendering
+        context.insert("counter", &2);
+        assert_eq!(renderer.render("custom.html", &context).unwrap(), "Blog 1");
+        assert!(renderer
+            .dependents(&["widget.html".to_string()])
+            .contains("custom.html"));
+
+        // A new build renders it again
+        renderer.start_build(globals);
+        assert_eq!(renderer.render("custom.html", &context).unwrap(), "Blog 2");
+    }
+
+    #[test]
+    fn test_partial_cache() {
+        let mut renderer = TemplateRenderer::new().unwrap();
+        renderer
+            .add_templates(vec![
+                ("nav.html".to_string(), "{{ lang }}:{{ count }}".to_string()),
+                (
+                    "custom.html".to_string(),
+                    r#"{{ partial(template="nav.html", lang=lang) }}"#.to_string(),
+                ),
+            ])
+            .unwrap();
+        let mut globals = Context::new();
+        globals.insert("count", &1);
+        renderer.start_build(globals);
+
+        let mut context = Context::new();
+        context.insert("count", &2);
+        context.insert("lang", "en");
+        assert_eq!(renderer.render("custom.html", &context).unwrap(), "en:1");
+        context.insert("lang", "zh");
+        assert_eq!(renderer.render("custom.html", &context).unwrap(), "zh:1");
+    }
+
+    #[test]
+    fn test_template_error_location() {
+        let mut renderer = TemplateRenderer::new().unwrap();
+        renderer
+            .add_templates(vec![
+                (
+                    "custom.html".to_string(),
+                    "<main>\n{% include \"partials/meta.html\" %}\n</main>".to_string(),
+                ),
+                (
+                    "partials/meta.html".to_string(),
+                    "<p>\n{{ page.title }}\n{{ page.missing }}\n</p>".to_string(),
+                ),
+            ])
+            .unwrap();
+
+        let mut context = Context::new();
+        context.insert("page", &serde_json::json!({ "title": "Hello" }));
+        let err = renderer.render("custom.html", &context).unwrap_err();
+        let err = err.downcast_ref::<TemplateError>().unwrap();
+        assert_eq!(err.template, "partials/meta.html");
+        assert_eq!(err.line, Some(3));
+        assert!(err.to_string().contains("> 3 | {{ page.missing }}"));
+
+        let err = renderer
+            .add_templates(vec![(
+                "broken.html".to_string(),
+                "<p>\n{% if %}\n</p>".to_string(),
+            )])
+            .unwrap_err();
+        let err = err.downcast_ref::<TemplateError>().unwrap();
+        assert_eq!(err.template, "broken.html");
+        assert_eq!(err.line, Some(2));
+    }
+
+    #[test]
+    fn test_toc_function() {
+        let mut renderer = TemplateRenderer::new().unwrap();
+        renderer
+            .add_templates(vec![(
+                "custom.html".to_string(),
+                "{{ toc }}|{{ toc(content=page_content, list_number=false) }}".to_string(),
+            )])
+            .unwrap();
+        let mut context = Context::new();
+        context.insert("toc", "precomputed");
+        context.insert("page_content", "<h2>Intro</h2>");
+        assert_eq!(
+            renderer.render("custom.html", &context).unwrap(),
+            r##"precomputed|<ol class="toc"><li class="toc-item toc-level-2"><a class="toc-link" href="#Intro"><span class="toc-text">Intro</span></a></li></ol>"##
+        );
+    }
+
+    #[test]
+    fn test_post_list_functions() {
+        let mut renderer = TemplateRenderer::new().unwrap();
+        renderer
+            .add_templates(vec![(
+                "custom.html".to_string(),
+                "{% for post in recent_posts(amount=1) %}{{ post.title }}{% endfor %} {{ archive_count(year=2024) }}"
+                    .to_string(),
+            )])
+            .unwrap();
+        let mut globals = Context::new();
+        globals.insert(
+            "site",
+            &serde_json::json!({ "posts": [
+                { "title": "Old", "path": "/old/", "date": "2023-05-01" },
+                { "title": "New", "path": "/new/", "date": "2024-02-01" },
+            ] }),
+        );
+        renderer.start_build(globals);
+        assert_eq!(
+            renderer.render("custom.html", &Context::new()).unwrap(),
+            "New 1"
+        );
+    }
+
+    #[test]
+    fn test_sum_filter() {
+        let mut renderer = TemplateRenderer::new().unwrap();
+        renderer
+            .add_templates(vec![(
+                "custom.html".to_string(),
+                "{{ posts | sum(attribute=\"meta.words\") }} {{ [1, 2.5] | sum }} {{ [] | sum }}"
+                    .to_string(),
+            )])
+            .unwrap();
+        let mut context = Context::new();
+        context.insert(
+            "posts",
+            &serde_json::json!([{ "meta": { "words": 120 } }, { "meta": { "words": 30 } }, {}]),
+        );
+        assert_eq!(
+            renderer.render("custom.html", &context).unwrap(),
+            "150 3.5 0"
+        );
+    }
+
+    #[test]
+    fn test_math_filters() {
+        let mut renderer = TemplateRenderer::new().unwrap();
+        renderer
+            .add_templates(vec![(
+                "custom.html".to_string(),
+                "{{ tags | min(attribute=\"count\") }}-{{ tags | max(attribute=\"count\") }} {{ [3, -1.5] | min }} {{ -4 | abs }} {{ [] | max }}"
+                    .to_string(),
+            )])
+            .unwrap();
+        let mut context = Context::new();
+        context.insert(
+            "tags",
+            &serde_json::json!([{ "count": 4 }, { "count": 12 }, { "count": 1 }]),
+        );
+        assert_eq!(
+            renderer.render("custom.html", &context).unwrap(),
+            "1-12 -1.5 4 "
+        );
+    }
+
+    #[test]
+    fn test_regex_replace_filter() {
+        let mut renderer = TemplateRenderer::new().unwrap();
+        renderer
+            .add_templates(vec![(
+                "custom.html".to_string(),
+                r#"{{ url | regex_replace(pattern="^https?://([^/]+)/.*$", rep="$1") }} {% if url is matching("^https?://") %}external{% endif %}"#
+                    .to_string(),
+            )])
+            .unwrap();
+        let mut context = Context::new();
+        context.insert("url", "https://example.com/a/b");
+        assert_eq!(
+            renderer.render("custom.html", &context).unwrap(),
+            "example.com external"
+        );
+    }
+
+    #[test]
+    fn test_relative_date_build_time() {
+        let mut renderer = TemplateRenderer::new().unwrap();
+        renderer
+            .add_templates(vec![(
+                "custom.html".to_string(),
+                "{{ '2024-01-01' | relative_date }}".to_string(),
+            )])
+            .unwrap();
+        renderer.set_build_time(DateTime::from_timestamp(1_704_499_200, 0).unwrap());
+        assert_eq!(
+            renderer.render("custom.html", &Context::new()).unwrap(),
+            "5 days ago"
+        );
+    }
+
+    #[test]
+    fn test_template_profiling() {
+        let mut renderer = TemplateRenderer::new().unwrap();
+        renderer
+            .add_templates(vec![
+                (
+                    "custom.html".to_string(),
+                    r#"{{ partial(template="partials/part.html", n=n) }}"#.to_string(),
+                ),
+                ("partials/part.html".to_string(), "{{ n }}".to_string()),
+            ])
+            .unwrap();
+        renderer.start_build(Context::new());
+
+        let mut context = Context::new();
+        context.insert("n", &1);
+        renderer.render("custom.html", &context).unwrap();
+        assert!(renderer.timings().is_empty());
+
+        renderer.enable_profiling();
+        for n in [1, 2, 2] {
+            context.insert("n", &n);
+            renderer.render("custom.html", &context).unwrap();
+        }
+        let renders: HashMap<String, usize> = renderer
+            .timings()
+            .into_iter()
+            .map(|(name, timing)| (name, timing.renders))
+            .collect();
+        // n=1 was cached before profiling started and n=2 renders once
+        assert_eq!(renders["custom.html"], 3);
+        assert_eq!(renders["partials/part.html"], 1);
+        assert!(format_report(&renderer.timings(), 10).contains("partials/part.html"));
+    }
+}
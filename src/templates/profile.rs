@@ -0,0 +1,88 @@
+//! Template render timing
+//!
+//! When enabled, every page render and every `partial`/`fragment_cache`
+//! render records its time under the template name. Times are inclusive: a
+//! page template's time contains the partials it renders. Templates pulled in
+//! with `{% include %}` are part of their includer's time, since Tera renders
+//! them inline. Posts and pages render in parallel, so totals add up time
+//! spent on all threads and can exceed the build's wall-clock time.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Render count and total time of one template
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TemplateTiming {
+    pub renders: usize,
+    pub total: Duration,
+}
+
+/// Render times of each template across a build
+#[derive(Default)]
+pub(crate) struct TemplateProfile {
+    enabled: AtomicBool,
+    timings: Mutex<HashMap<String, TemplateTiming>>,
+}
+
+impl TemplateProfile {
+    pub(crate) fn enable(&self) {
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    /// Run `render`, recording its time under `template` when enabled
+    pub(crate) fn time<T>(&self, template: &str, render: impl FnOnce() -> T) -> T {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return render();
+        }
+        let start = Instant::now();
+        let result = render();
+        let elapsed = start.elapsed();
+
+        let mut timings = self.timings.lock().unwrap();
+        let timing = timings.entry(template.to_string()).or_default();
+        timing.renders += 1;
+        timing.total += elapsed;
+        result
+    }
+
+    /// Recorded timings, slowest total first
+    pub(crate) fn timings(&self) -> Vec<(String, TemplateTiming)> {
+        let mut timings: Vec<_> = self
+            .timings
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, timing)| (name.clone(), *timing))
+            .collect();
+        timings.sort_by(|a, b| b.1.total.cmp(&a.1.total).then_with(|| a.0.cmp(&b.0)));
+        timings
+    }
+}
+
+/// A table of the `limit` slowest templates
+pub fn format_report(timings: &[(String, TemplateTiming)], limit: usize) -> String {
+    let width = timings
+        .iter()
+        .take(limit)
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0)
+        .max("template".len());
+    let mut report = format!(
+        "{:<width$}  {:>8}  {:>10}  {:>8}\n",
+        "template", "renders", "total", "average"
+    );
+    for (name, timing) in timings.iter().take(limit) {
+        let average = timing.total / timing.renders.max(1) as u32;
+        report.push_str(&format!(
+            "{:<width$}  {:>8}  {:>8.1}ms  {:>6.2}ms\n",
+            name,
+            timing.renders,
+            timing.total.as_secs_f64() * 1000.0,
+            average.as_secs_f64() * 1000.0,
+        ));
+    }
+    report
+}
@@ -0,0 +1,59 @@
+//! Templates each template was seen using
+//!
+//! `extends`/`include`/`import` and literal `partial(template="...")`
+//! references are found in the template source. Templates rendered through
+//! `partial` or `fragment_cache` with a computed name only show up when a
+//! page renders them, so renders record what they used here. Together they
+//! tell which pages a template edit reaches.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+thread_local! {
+    /// Templates used by each render in progress on this thread, innermost last
+    static RENDERING: RefCell<Vec<BTreeSet<String>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Templates rendered by `partial`/`fragment_cache` from each template
+#[derive(Default)]
+pub(crate) struct TemplateUsage {
+    used: Mutex<BTreeMap<String, BTreeSet<String>>>,
+}
+
+impl TemplateUsage {
+    /// Run `render` for `template`, recording the templates it uses
+    pub(crate) fn track<T>(&self, template: &str, render: impl FnOnce() -> T) -> T {
+        Self::note(template);
+        RENDERING.with(|stack| stack.borrow_mut().push(BTreeSet::new()));
+        let result = render();
+        let used = RENDERING.with(|stack| stack.borrow_mut().pop().unwrap_or_default());
+        if !used.is_empty() {
+            let mut all = self.used.lock().unwrap();
+            all.entry(template.to_string()).or_default().extend(used);
+        }
+        result
+    }
+
+    /// Record that the render in progress uses `template`, also when its
+    /// output comes from a cache
+    pub(crate) fn note(template: &str) {
+        RENDERING.with(|stack| {
+            if let Some(used) = stack.borrow_mut().last_mut() {
+                used.insert(template.to_string());
+            }
+        });
+    }
+
+    pub(crate) fn get(&self) -> BTreeMap<String, BTreeSet<String>> {
+        self.used.lock().unwrap().clone()
+    }
+
+    /// Add usage recorded by an earlier build
+    pub(crate) fn extend(&self, used: BTreeMap<String, BTreeSet<String>>) {
+        let mut all = self.used.lock().unwrap();
+        for (template, templates) in used {
+            all.entry(template).or_default().extend(templates);
+        }
+    }
+}
@@ -4,23 +4,78 @@
 //! This module is responsible for:
 //! - Loading theme configuration from _config.yml
 //! - Copying theme assets (CSS, JS, images) to the public directory
+//! - Collecting Tera templates (`.html`/`.tera`, plus Nunjucks/Swig and
+//!   Pug/Jade layouts translated to Tera) from the theme's `layout/` directory, which replace
+//!   or extend the built-in vexo templates
+//!
+//! A theme can set `theme_parent: <name>` in its _config.yml to inherit from
+//! another theme in `themes/`. Config keys, templates and assets it doesn't
+//! provide itself fall back to the parent (which may have a parent of its own).
 
 use anyhow::{anyhow, Result};
 use indexmap::IndexMap;
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 use walkdir::WalkDir;
 
+use super::nunjucks::{self, NUNJUCKS_EXTENSIONS};
+use super::pug::{self, PUG_EXTENSIONS};
+use super::sass::SassCompiler;
+use super::stylus;
+use crate::cache::AssetRecord;
+use crate::config::merge_yaml;
+use crate::generator::{write_if_changed, AssetCopier};
+use crate::templates::{builtin_theme, BuiltinTheme, BUILTIN_THEMES};
+
+/// Config key naming the theme this one inherits from
+const THEME_PARENT_KEY: &str = "theme_parent";
+
+/// Theme templates as read (and translated to Tera) from disk, with the
+/// record of the file they were read from. Kept for the whole process so
+/// rebuilds in watch mode only re-read templates that changed.
+static TEMPLATE_CACHE: OnceLock<Mutex<HashMap<PathBuf, (AssetRecord, String)>>> = OnceLock::new();
+
+/// Read the template at `path`, registered as `name`, translating Nunjucks
+/// and Pug layouts
+fn read_template(path: &Path, name: &str) -> Result<String> {
+    let cache = TEMPLATE_CACHE.get_or_init(Mutex::default);
+    let record = AssetRecord::of(path)?;
+    if let Some((cached, source)) = cache.lock().unwrap().get(path) {
+        if *cached == record {
+            return Ok(source.clone());
+        }
+    }
+
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let source = fs::read_to_string(path)?;
+    let source = if NUNJUCKS_EXTENSIONS.contains(&ext) {
+        nunjucks::to_tera(&source).map_err(|e| anyhow!("Failed to translate {:?}: {}", path, e))?
+    } else if PUG_EXTENSIONS.contains(&ext) {
+        pug::to_tera(&source, name).map_err(|e| anyhow!("Failed to translate {:?}: {}", path, e))?
+    } else {
+        source
+    };
+    cache
+        .lock()
+        .unwrap()
+        .insert(path.to_path_buf(), (record, source.clone()));
+    Ok(source)
+}
+
 /// Theme loader - loads config and copies assets
 pub struct ThemeLoader {
-    /// Theme directory path
-    theme_dir: PathBuf,
+    /// Theme directories, the theme itself first and then its ancestors
+    theme_dirs: Vec<PathBuf>,
     /// Theme configuration (IndexMap preserves YAML key order for menu items)
     config: IndexMap<String, serde_yaml::Value>,
+    /// Embedded theme selected with `theme: builtin:<name>`
+    builtin: Option<&'static BuiltinTheme>,
 }
 
 impl ThemeLoader {
-    /// Load a theme from a directory
+    /// Load a theme (and any parent themes) from a directory
     pub fn load<P: AsRef<Path>>(theme_dir: P) -> Result<Self> {
         let theme_dir = theme_dir.as_ref().to_path_buf();
 
@@ -28,19 +83,60 @@ impl ThemeLoader {
             return Err(anyhow!("Theme directory not found: {:?}", theme_dir));
         }
 
-        let mut loader = Self {
-            theme_dir: theme_dir.clone(),
-            config: IndexMap::new(),
-        };
+        let mut theme_dirs = Vec::new();
+        let mut configs = Vec::new();
+        let mut next = Some(theme_dir);
+        while let Some(dir) = next.take() {
+            if theme_dirs.contains(&dir) {
+                return Err(anyhow!("Theme inheritance cycle at {:?}", dir));
+            }
 
-        // Load theme config
-        let config_path = theme_dir.join("_config.yml");
-        if config_path.exists() {
-            let content = fs::read_to_string(&config_path)?;
-            loader.config = serde_yaml::from_str(&content)?;
+            let config = load_config(&dir)?;
+            if let Some(parent) = config.get(THEME_PARENT_KEY).and_then(|v| v.as_str()) {
+                let parent_dir = dir.with_file_name(parent);
+                if !parent_dir.exists() {
+                    return Err(anyhow!(
+                        "Parent theme {:?} of {:?} not found",
+                        parent_dir,
+                        dir
+                    ));
+                }
+                next = Some(parent_dir);
+            }
+            theme_dirs.push(dir);
+            configs.push(config);
         }
 
-        Ok(loader)
+        // Start from the root ancestor so children override (but keep the order of) its keys
+        let mut config = IndexMap::new();
+        for theme_config in configs.into_iter().rev() {
+            config.extend(theme_config);
+        }
+        config.shift_remove(THEME_PARENT_KEY);
+
+        Ok(Self {
+            theme_dirs,
+            config,
+            builtin: None,
+        })
+    }
+
+    /// Load an embedded theme (`theme: builtin:<name>` in the site config)
+    pub fn builtin(name: &str) -> Result<Self> {
+        let theme = builtin_theme(name).ok_or_else(|| {
+            let names: Vec<&str> = BUILTIN_THEMES.iter().map(|t| t.name).collect();
+            anyhow!(
+                "Unknown built-in theme {:?} (available: {})",
+                name,
+                names.join(", ")
+            )
+        })?;
+        let config = serde_yaml::from_str::<Option<_>>(theme.config)?.unwrap_or_default();
+        Ok(Self {
+            theme_dirs: Vec::new(),
+            config,
+            builtin: Some(theme),
+        })
     }
 
     /// Get theme configuration
@@ -48,119 +144,378 @@ impl ThemeLoader {
         &self.config
     }
 
-    /// Copy theme source files to public directory
-    pub fn copy_source(&self, public_dir: &Path) -> Result<()> {
-        let source_dir = self.theme_dir.join("source");
-        if !source_dir.exists() {
-            return Ok(());
+    /// Apply site-level overrides (`theme_config:` in the site _config.yml and
+    /// _config.[theme].yml), merging nested mappings recursively
+    pub fn apply_overrides<'a>(
+        &mut self,
+        overrides: impl IntoIterator<Item = (&'a String, &'a serde_yaml::Value)>,
+    ) {
+        for (key, value) in overrides {
+            match self.config.get_mut(key) {
+                Some(existing) => merge_yaml(existing, value.clone()),
+                None => {
+                    self.config.insert(key.clone(), value.clone());
+                }
+            }
         }
+    }
 
-        for entry in WalkDir::new(&source_dir)
-            .follow_links(true)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            let path = entry.path();
-
-            if path.is_file() {
-                let relative = path.strip_prefix(&source_dir)?;
-
-                // Skip files in directories starting with _ or . (e.g., _partial/)
-                let should_skip = relative.components().any(|c| {
-                    c.as_os_str()
-                        .to_str()
-                        .map(|s| s.starts_with('_') || s.starts_with('.'))
-                        .unwrap_or(false)
-                });
-                if should_skip {
+    /// Language files in the themes' `languages/` directories as
+    /// `(lang, table)`, ancestors first (`languages/zh-CN.yml` -> `zh-CN`)
+    pub fn languages(&self) -> Result<Vec<(String, serde_yaml::Value)>> {
+        let mut languages = Vec::new();
+        for theme_dir in self.theme_dirs.iter().rev() {
+            let dir = theme_dir.join("languages");
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in WalkDir::new(&dir)
+                .max_depth(1)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_map(|e| e.ok())
+            {
+                let path = entry.path();
+                let is_yaml = matches!(
+                    path.extension().and_then(|e| e.to_str()),
+                    Some("yml" | "yaml")
+                );
+                let Some(lang) = path.file_stem().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                if !is_yaml || !path.is_file() {
                     continue;
                 }
+                let content = fs::read_to_string(path)?;
+                let table: Option<serde_yaml::Value> = serde_yaml::from_str(&content)
+                    .map_err(|e| anyhow!("Failed to parse {:?}: {}", path, e))?;
+                languages.push((lang.to_string(), table.unwrap_or_default()));
+            }
+        }
+        Ok(languages)
+    }
 
-                let ext = path.extension().and_then(|e| e.to_str());
-
-                // Handle Stylus files - compile to CSS
-                if ext == Some("styl") {
-                    // Check if there's a pre-compiled CSS file
-                    let css_path = path.with_extension("css");
-                    if css_path.exists() {
-                        // Use pre-compiled CSS
-                        let css_relative = relative.with_extension("css");
-                        let dest = public_dir.join(&css_relative);
-                        if let Some(parent) = dest.parent() {
-                            fs::create_dir_all(parent)?;
-                        }
-                        fs::copy(&css_path, &dest)?;
-                        tracing::debug!("Copied pre-compiled CSS: {:?} -> {:?}", css_path, dest);
-                    } else {
-                        // Try to compile with npx stylus
-                        let css_relative = relative.with_extension("css");
-                        let dest = public_dir.join(&css_relative);
+    /// Tera templates found in the themes' `layout/` directories, keyed by
+    /// their path relative to `layout/` with other template extensions
+    /// (`.tera`, `.njk`, `.swig`, `.pug`, `.jade`) mapped to `.html`
+    /// (e.g. `partials/footer.html`). They replace the built-in templates of
+    /// the same name and can `extend`/`include` each other by these names.
+    /// `.html` files left over from EJS or Swig layouts, which don't parse as
+    /// Tera or hold EJS tags, are skipped with a warning.
+    pub fn templates(&self) -> Result<Vec<(String, String)>> {
+        let mut templates: IndexMap<String, String> = IndexMap::new();
+        if let Some(theme) = self.builtin {
+            for (name, source) in theme.templates {
+                templates.insert(name.to_string(), source.to_string());
+            }
+        }
+        for theme_dir in self.theme_dirs.iter().rev() {
+            let layout_dir = theme_dir.join("layout");
+            for entry in WalkDir::new(&layout_dir)
+                .follow_links(true)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_map(|e| e.ok())
+            {
+                let path = entry.path();
+                if !path.is_file() {
+                    continue;
+                }
+                let Some(name) = template_name(theme_dir, path) else {
+                    continue;
+                };
+                let source = read_template(path, &name)?;
+                if path.extension().is_some_and(|ext| ext == "html") {
+                    if let Err(reason) = tera_html(&name, &source) {
+                        tracing::warn!("Skipping {:?}, not a Tera template: {}", path, reason);
+                        continue;
+                    }
+                }
+                templates.insert(name, source);
+            }
+        }
+        Ok(templates.into_iter().collect())
+    }
 
-                        if let Some(parent) = dest.parent() {
-                            fs::create_dir_all(parent)?;
-                        }
+    /// Copy theme source files to public directory, ancestors first so the
+    /// theme's own files win
+    pub fn copy_source(&self, sass: &SassCompiler, assets: &mut AssetCopier) -> Result<()> {
+        if let Some(theme) = self.builtin {
+            for (relative, content) in theme.assets {
+                let dest = assets.public_dir().join(relative);
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                write_if_changed(&dest, content)?;
+                assets.claim(Path::new(relative));
+            }
+        }
+        for theme_dir in self.theme_dirs.iter().rev() {
+            copy_theme_source(theme_dir, &self.config, sass, assets)?;
+        }
+        Ok(())
+    }
+}
 
-                        match compile_stylus(path, &source_dir) {
-                            Ok(css) => {
-                                fs::write(&dest, css)?;
-                                tracing::info!("Compiled Stylus: {:?} -> {:?}", path, dest);
-                            }
+/// Registered name of a template file in a theme's `layout/` directory
+/// (`layout/partials/head.njk` -> `partials/head.html`), or `None` if the
+/// file isn't a template
+pub fn template_name(theme_dir: &Path, path: &Path) -> Option<String> {
+    let ext = path.extension()?.to_str()?;
+    let is_template = matches!(ext, "html" | "tera")
+        || NUNJUCKS_EXTENSIONS.contains(&ext)
+        || PUG_EXTENSIONS.contains(&ext);
+    if !is_template {
+        return None;
+    }
+    let relative = path.strip_prefix(theme_dir.join("layout")).ok()?;
+    Some(
+        relative
+            .with_extension("html")
+            .to_string_lossy()
+            .replace('\\', "/"),
+    )
+}
+
+/// Check that an `.html` layout is Tera rather than EJS or Swig
+fn tera_html(name: &str, source: &str) -> Result<(), String> {
+    if source.contains("<%") {
+        return Err("contains EJS tags".to_string());
+    }
+    tera::Template::new(name, None, source).map(|_| ()).map_err(
+        |e| match std::error::Error::source(&e) {
+            Some(cause) => cause.to_string(),
+            None => e.to_string(),
+        },
+    )
+}
+
+/// Load a theme's _config.yml (empty if it has none)
+fn load_config(theme_dir: &Path) -> Result<IndexMap<String, serde_yaml::Value>> {
+    let config_path = theme_dir.join("_config.yml");
+    if !config_path.exists() {
+        return Ok(IndexMap::new());
+    }
+    let content = fs::read_to_string(&config_path)?;
+    Ok(serde_yaml::from_str::<Option<_>>(&content)?.unwrap_or_default())
+}
+
+/// Copy one theme's source files to the public directory
+fn copy_theme_source(
+    theme_dir: &Path,
+    config: &IndexMap<String, serde_yaml::Value>,
+    sass: &SassCompiler,
+    assets: &mut AssetCopier,
+) -> Result<()> {
+    let public_dir = assets.public_dir().to_path_buf();
+    let source_dir = theme_dir.join("source");
+    if !source_dir.exists() {
+        return Ok(());
+    }
+
+    for entry in WalkDir::new(&source_dir)
+        .follow_links(true)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+
+        if path.is_file() {
+            let relative = path.strip_prefix(&source_dir)?;
+
+            // Skip files in directories starting with _ or . (e.g., _partial/)
+            let should_skip = relative.components().any(|c| {
+                c.as_os_str()
+                    .to_str()
+                    .map(|s| s.starts_with('_') || s.starts_with('.'))
+                    .unwrap_or(false)
+            });
+            if should_skip {
+                continue;
+            }
+
+            let ext = path.extension().and_then(|e| e.to_str());
+
+            // Handle Stylus files - compile to CSS
+            if ext == Some("styl") {
+                // Check if there's a pre-compiled CSS file
+                let css_path = path.with_extension("css");
+                if css_path.exists() {
+                    // Use pre-compiled CSS
+                    assets.copy(&css_path, &relative.with_extension("css"));
+                } else {
+                    let css_relative = relative.with_extension("css");
+                    let dest = public_dir.join(&css_relative);
+                    if let Some(parent) = dest.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+
+                    let compiled = stylus::compile(path, &source_dir, config).map_err(|e| {
+                        anyhow!(
+                            "Stylus compilation failed for {:?}: {}. \
+                            Place a pre-compiled .css file alongside the .styl file instead.",
+                            path,
+                            e
+                        )
+                    })?;
+                    let css = if compiled.unsupported.is_empty() {
+                        compiled.css
+                    } else {
+                        // The real Stylus handles what the built-in compiler skipped
+                        match stylus::compile_with_node(path, &source_dir) {
+                            Ok(css) => css,
                             Err(e) => {
-                                tracing::error!(
-                                    "Failed to compile {:?}: {}. \
-                                    Please run 'npx stylus {} -o {}' to pre-compile, \
-                                    or place a pre-compiled style.css alongside the .styl file.",
+                                tracing::warn!(
+                                    "{:?} uses Stylus features that aren't supported without \
+                                    Node.js ({}), compiled without:\n  {}",
                                     path,
                                     e,
-                                    path.display(),
-                                    path.parent().unwrap_or(Path::new(".")).display()
+                                    compiled.unsupported.join("\n  ")
                                 );
-                                return Err(anyhow!(
-                                    "Stylus compilation failed for {:?}. \
-                                    Install stylus (npm install -g stylus) or provide pre-compiled CSS.",
-                                    path
-                                ));
+                                compiled.css
                             }
                         }
-                    }
-                } else {
-                    let dest = public_dir.join(relative);
-
-                    if let Some(parent) = dest.parent() {
-                        fs::create_dir_all(parent)?;
-                    }
-
-                    fs::copy(path, &dest)?;
-                    tracing::debug!("Copied: {:?} -> {:?}", path, dest);
+                    };
+                    write_if_changed(&dest, css)?;
+                    assets.claim(&css_relative);
+                    tracing::info!("Compiled Stylus: {:?} -> {:?}", path, dest);
+                }
+            } else if SassCompiler::handles(path) {
+                let css_relative = relative.with_extension("css");
+                let dest = public_dir.join(&css_relative);
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
                 }
+                write_if_changed(&dest, sass.compile(path)?)?;
+                assets.claim(&css_relative);
+                tracing::info!("Compiled Sass: {:?} -> {:?}", path, dest);
+            } else {
+                assets.copy(path, relative);
             }
         }
-
-        Ok(())
     }
+
+    Ok(())
 }
 
-/// Compile a Stylus file to CSS using npx stylus
-fn compile_stylus(styl_path: &Path, include_dir: &Path) -> Result<String> {
-    use std::process::Command;
-
-    // Try npx stylus first
-    let output = Command::new("npx")
-        .args([
-            "stylus",
-            "--print",
-            "--include",
-            include_dir.to_str().unwrap_or("."),
-            styl_path.to_str().unwrap_or(""),
-        ])
-        .output();
-
-    match output {
-        Ok(out) if out.status.success() => Ok(String::from_utf8_lossy(&out.stdout).to_string()),
-        Ok(out) => {
-            let stderr = String::from_utf8_lossy(&out.stderr);
-            Err(anyhow!("Stylus compilation failed: {}", stderr))
-        }
-        Err(e) => Err(anyhow!("Failed to run npx stylus: {}", e)),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheDb;
+
+    #[test]
+    fn test_child_theme_falls_back_to_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        let parent = dir.path().join("parent");
+        let child = dir.path().join("child");
+        fs::create_dir_all(parent.join("source/css")).unwrap();
+        fs::create_dir_all(parent.join("layout/partials")).unwrap();
+        fs::create_dir_all(child.join("source/css")).unwrap();
+        fs::create_dir_all(child.join("layout")).unwrap();
+
+        fs::write(parent.join("_config.yml"), "menu: parent\nqrcode: true\n").unwrap();
+        fs::write(parent.join("source/css/style.css"), "parent").unwrap();
+        fs::write(parent.join("source/css/extra.css"), "parent").unwrap();
+        fs::write(parent.join("layout/page.html"), "parent page").unwrap();
+        fs::write(parent.join("layout/partials/footer.html"), "parent footer").unwrap();
+        fs::write(
+            child.join("_config.yml"),
+            "theme_parent: parent\nmenu: child\n",
+        )
+        .unwrap();
+        fs::write(child.join("source/css/style.css"), "child").unwrap();
+        fs::write(child.join("layout/page.html"), "child page").unwrap();
+        fs::write(child.join("layout/post.tera"), "child post").unwrap();
+        fs::write(child.join("layout/archive.njk"), "{% extends 'page.njk' %}").unwrap();
+        fs::write(
+            child.join("layout/index.html"),
+            "<%- partial('_partial/head') %>",
+        )
+        .unwrap();
+        fs::write(
+            child.join("layout/tag.html"),
+            "{% if tag %}{{ tag }}{% endtag %}",
+        )
+        .unwrap();
+        fs::create_dir_all(parent.join("languages")).unwrap();
+        fs::create_dir_all(child.join("languages")).unwrap();
+        fs::write(parent.join("languages/en.yml"), "home: Home\n").unwrap();
+        fs::write(child.join("languages/en.yml"), "home: Start\n").unwrap();
+
+        let theme = ThemeLoader::load(&child).unwrap();
+        assert_eq!(theme.config()["menu"].as_str(), Some("child"));
+        assert_eq!(theme.config()["qrcode"].as_bool(), Some(true));
+        assert!(!theme.config().contains_key(THEME_PARENT_KEY));
+
+        let templates: std::collections::HashMap<_, _> =
+            theme.templates().unwrap().into_iter().collect();
+        assert_eq!(templates["page.html"], "child page");
+        assert_eq!(templates["partials/footer.html"], "parent footer");
+        assert_eq!(templates["post.html"], "child post");
+        assert_eq!(templates["archive.html"], "{% extends 'page.html' %}");
+        assert!(!templates.contains_key("index.html"));
+        assert!(!templates.contains_key("tag.html"));
+
+        let languages = theme.languages().unwrap();
+        let homes: Vec<_> = languages
+            .iter()
+            .map(|(lang, table)| (lang.as_str(), table["home"].as_str().unwrap()))
+            .collect();
+        assert_eq!(homes, [("en", "Home"), ("en", "Start")]);
+
+        let public = dir.path().join("public");
+        let sass = SassCompiler::new(&crate::config::SassConfig::default(), dir.path());
+        let mut assets = AssetCopier::new(&public);
+        theme.copy_source(&sass, &mut assets).unwrap();
+        assets.finish(&mut CacheDb::load(dir.path())).unwrap();
+        assert_eq!(
+            fs::read_to_string(public.join("css/style.css")).unwrap(),
+            "child"
+        );
+        assert_eq!(
+            fs::read_to_string(public.join("css/extra.css")).unwrap(),
+            "parent"
+        );
+    }
+
+    #[test]
+    fn test_builtin_theme() {
+        let theme = ThemeLoader::builtin("docs").unwrap();
+        assert!(theme.config().contains_key("menu"));
+        let templates = theme.templates().unwrap();
+        assert!(templates
+            .iter()
+            .any(|(name, _)| name == "partials/sidebar.html"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let sass = SassCompiler::new(&crate::config::SassConfig::default(), dir.path());
+        let mut assets = AssetCopier::new(dir.path());
+        theme.copy_source(&sass, &mut assets).unwrap();
+        assets.finish(&mut CacheDb::load(dir.path())).unwrap();
+        assert!(dir.path().join("css/style.css").exists());
+
+        assert!(ThemeLoader::builtin("missing").is_err());
+    }
+
+    #[test]
+    fn test_read_template_rereads_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("page.njk");
+        fs::write(&path, "{% extends 'a.njk' %}").unwrap();
+        assert_eq!(
+            read_template(&path, "page.html").unwrap(),
+            "{% extends 'a.html' %}"
+        );
+        assert_eq!(
+            read_template(&path, "page.html").unwrap(),
+            "{% extends 'a.html' %}"
+        );
+
+        fs::write(&path, "{% extends 'layout.njk' %}").unwrap();
+        assert_eq!(
+            read_template(&path, "page.html").unwrap(),
+            "{% extends 'layout.html' %}"
+        );
     }
 }
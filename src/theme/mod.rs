@@ -4,5 +4,10 @@
 //! with the vexo theme embedded directly in the binary.
 
 mod loader;
+mod nunjucks;
+mod pug;
+mod sass;
+mod stylus;
 
-pub use loader::ThemeLoader;
+pub use loader::{template_name, ThemeLoader};
+pub use sass::SassCompiler;
@@ -0,0 +1,211 @@
+//! Nunjucks/Swig compatibility shim
+//!
+//! Nunjucks and Swig layouts (`.njk`/`.swig`) are close enough to Tera's
+//! Jinja2-style syntax that the common subset used by Hexo themes renders
+//! after a few source rewrites:
+//! - template references (`extends`/`include`/`import`) point at the `.html`
+//!   names the templates are registered under
+//! - `elseif` becomes `elif` and Swig's `{% parent %}` becomes `{{ super() }}`
+//! - positional i18n calls `__('key')` get Tera's named argument (`__(key='key')`)
+//! - positional filter arguments (`truncate(20)`) get Tera's argument names
+//!   (`truncate(length=20)`); filters whose arguments can't be mapped are
+//!   reported as unsupported
+//!
+//! The `dump` filter and `__` function are registered by the template renderer.
+//! Inline `if` expressions and macros defined in the calling file are not
+//! rewritten and need manual porting.
+
+use anyhow::{anyhow, Result};
+
+use super::pug::split_top_level;
+
+/// Template file extensions handled by the shim
+pub const NUNJUCKS_EXTENSIONS: &[&str] = &["njk", "swig"];
+
+/// Tera names of the positional arguments of Nunjucks filters. Filters not
+/// listed here can't take positional arguments.
+const FILTER_ARGS: &[(&str, &[&str])] = &[
+    ("default", &["value"]),
+    ("truncate", &["length"]),
+    ("replace", &["from", "to"]),
+    ("join", &["sep"]),
+    ("round", &["precision", "method"]),
+    ("int", &["default"]),
+    ("float", &["default"]),
+    ("dump", &["spaces"]),
+    ("split", &["pat"]),
+];
+
+/// Rewrite Nunjucks/Swig template source into Tera syntax
+pub fn to_tera(source: &str) -> Result<String> {
+    let mut result = source.to_string();
+    for ext in NUNJUCKS_EXTENSIONS {
+        for quote in ['"', '\''] {
+            result = result.replace(&format!(".{}{}", ext, quote), &format!(".html{}", quote));
+        }
+    }
+
+    let result = result
+        .replace("{% elseif ", "{% elif ")
+        .replace("{%- elseif ", "{%- elif ")
+        .replace("{% parent %}", "{{ super() }}")
+        .replace("__('", "__(key='")
+        .replace("__(\"", "__(key=\"");
+    name_filter_args(&result)
+}
+
+/// Give positional filter arguments inside `{{ }}` and `{% %}` tags their
+/// Tera names: `| truncate(20)` -> `| truncate(length=20)`
+fn name_filter_args(source: &str) -> Result<String> {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find('{') {
+        let close = match rest[start..].get(..2) {
+            Some("{{") => "}}",
+            Some("{%") => "%}",
+            _ => {
+                out.push_str(&rest[..start + 1]);
+                rest = &rest[start + 1..];
+                continue;
+            }
+        };
+        let Some(end) = rest[start..].find(close).map(|i| start + i + 2) else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        out.push_str(&rewrite_filters(&rest[start..end])?);
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Rewrite the filter calls of one tag
+fn rewrite_filters(tag: &str) -> Result<String> {
+    let mut out = String::with_capacity(tag.len());
+    let mut quote: Option<char> = None;
+    let mut chars = tag.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        out.push(c);
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '|') => {
+                let after = &tag[i + 1..];
+                let name_start = after.len() - after.trim_start().len();
+                let name_len = after[name_start..]
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len() - name_start);
+                let name = &after[name_start..name_start + name_len];
+                let Some(args) = after[name_start + name_len..].strip_prefix('(') else {
+                    continue;
+                };
+                let Some(args_len) = closing_paren(args) else {
+                    continue;
+                };
+                let args = split_top_level(&args[..args_len], ',');
+                let named = named_args(name, &args)?;
+                out.push_str(&after[..name_start]);
+                out.push_str(&format!("{}({})", name, named.join(", ")));
+                // Skip past `name(...)`
+                let skip = name_start + name_len + 1 + args_len + 1;
+                while chars.peek().is_some_and(|&(j, _)| j <= i + skip) {
+                    chars.next();
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(out)
+}
+
+/// Offset of the `)` closing an argument list that starts at `args`
+fn closing_paren(args: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    for (i, c) in args.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')') if depth == 0 => return Some(i),
+            (None, ')' | ']' | '}') => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Arguments of the filter `name` with Tera names for the positional ones
+fn named_args(name: &str, args: &[String]) -> Result<Vec<String>> {
+    let names = FILTER_ARGS
+        .iter()
+        .find(|(filter, _)| *filter == name)
+        .map(|(_, names)| *names)
+        .unwrap_or_default();
+    args.iter()
+        .enumerate()
+        .map(|(i, arg)| {
+            let arg = arg.trim();
+            if is_named(arg) {
+                return Ok(arg.to_string());
+            }
+            names
+                .get(i)
+                .map(|param| format!("{}={}", param, arg))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Unsupported positional argument {} to filter `{}`",
+                        arg,
+                        name
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Whether a call argument is already `name=value`
+fn is_named(arg: &str) -> bool {
+    arg.split_once('=').is_some_and(|(name, value)| {
+        !value.starts_with('=')
+            && !name.trim().is_empty()
+            && name.trim().chars().all(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_tera() {
+        let njk = r#"{% extends '_layout.njk' %}
+{% block content %}{% parent %}
+{% include "_partials/header.swig" %}
+{% if a %}A{% elseif b %}B{% endif %}
+{{ __('menu.home') }}{% endblock %}"#;
+        let tera = to_tera(njk).unwrap();
+        assert!(tera.contains("{% extends '_layout.html' %}"));
+        assert!(tera.contains(r#"{% include "_partials/header.html" %}"#));
+        assert!(tera.contains("{% elif b %}"));
+        assert!(tera.contains("{{ super() }}"));
+        assert!(tera.contains("__(key='menu.home')"));
+    }
+
+    #[test]
+    fn test_filter_args() {
+        let njk = "{{ title | default('Untitled') | truncate(20) }}\
+                   {% set s = a|replace('|', \"b\") %}{{ n | round(2, 'floor') }}\
+                   {{ x | join(sep=', ') | upper }}{{ \"{{ not a tag\" }}";
+        assert_eq!(
+            to_tera(njk).unwrap(),
+            "{{ title | default(value='Untitled') | truncate(length=20) }}\
+             {% set s = a|replace(from='|', to=\"b\") %}{{ n | round(precision=2, method='floor') }}\
+             {{ x | join(sep=', ') | upper }}{{ \"{{ not a tag\" }}"
+        );
+        assert!(to_tera("{{ text | truncate(20, true) }}").is_err());
+        assert!(to_tera("{{ items | batch(3) }}").is_err());
+    }
+}
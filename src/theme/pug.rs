@@ -0,0 +1,767 @@
+//! Pug/Jade to Tera translation
+//!
+//! Hexo themes such as yilia ship `.pug`/`.jade` layouts. Rather than adding a
+//! second template engine, layouts are translated into Tera source and then
+//! registered like any other theme template. The translation covers the subset
+//! used by mainstream themes:
+//! - indentation-based tags with `#id`/`.class` shorthand, `(attributes)`,
+//!   inline text, `=`/`!=` buffered code, `tag: child` block expansion and
+//!   `tag.` text blocks
+//! - `| piped text`, `#{expr}`/`!{expr}` interpolation and comments
+//! - `extends`, `block`, `include`
+//! - `if`/`else if`/`else`/`unless`, `each`/`for` loops and `- var x = ...`
+//! - `mixin name(args)` definitions and `+name(args)` calls within a file
+//!
+//! JavaScript expressions are passed through with `||`, `&&`, `!` and `===`
+//! mapped to their Tera equivalents; anything beyond that needs porting.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+/// Template file extensions handled by the translator
+pub const PUG_EXTENSIONS: &[&str] = &["pug", "jade"];
+
+/// Elements that never have a closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// A source line and the lines indented below it
+struct Node {
+    line: usize,
+    text: String,
+    children: Vec<Node>,
+}
+
+/// Translate Pug source into Tera template source. `name` is the template's
+/// registered name (`partial/head.html`), which relative `extends` and
+/// `include` paths are resolved against.
+pub fn to_tera(source: &str, name: &str) -> Result<String> {
+    let nodes = parse_tree(source)?;
+    let mut translator = Translator {
+        dir: name
+            .rsplit_once('/')
+            .map(|(dir, _)| dir.to_string())
+            .unwrap_or_default(),
+        ..Translator::default()
+    };
+    translator.collect_mixins(&nodes);
+
+    let mut out = String::new();
+    translator.render_nodes(&nodes, &mut out)?;
+    Ok(out)
+}
+
+/// Build the indentation tree, dropping blank lines
+fn parse_tree(source: &str) -> Result<Vec<Node>> {
+    let mut roots: Vec<Node> = Vec::new();
+    // Stack of (indent, path of child indices from the roots)
+    let mut stack: Vec<(usize, Vec<usize>)> = Vec::new();
+
+    for (i, raw) in source.lines().enumerate() {
+        if raw.trim().is_empty() {
+            continue;
+        }
+        let indent = raw.len() - raw.trim_start().len();
+        let node = Node {
+            line: i + 1,
+            text: raw.trim().to_string(),
+            children: Vec::new(),
+        };
+
+        while stack.last().is_some_and(|(level, _)| *level >= indent) {
+            stack.pop();
+        }
+
+        let path = match stack.last() {
+            Some((_, parent_path)) => {
+                let parent = node_at(&mut roots, parent_path)
+                    .ok_or_else(|| anyhow!("Invalid indentation at line {}", i + 1))?;
+                parent.children.push(node);
+                let mut path = parent_path.clone();
+                path.push(parent.children.len() - 1);
+                path
+            }
+            None => {
+                roots.push(node);
+                vec![roots.len() - 1]
+            }
+        };
+        stack.push((indent, path));
+    }
+
+    Ok(roots)
+}
+
+fn node_at<'a>(roots: &'a mut [Node], path: &[usize]) -> Option<&'a mut Node> {
+    let (first, rest) = path.split_first()?;
+    let mut node = roots.get_mut(*first)?;
+    for &i in rest {
+        node = node.children.get_mut(i)?;
+    }
+    Some(node)
+}
+
+#[derive(Default)]
+struct Translator {
+    /// Parameter names of the mixins defined in the file
+    mixins: HashMap<String, Vec<String>>,
+    /// Directory of the template within `layout/`, empty at the top
+    dir: String,
+}
+
+impl Translator {
+    fn collect_mixins(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            if let Some(rest) = node.text.strip_prefix("mixin ") {
+                let (name, params) = split_call(rest);
+                let params = split_top_level(&params, ',')
+                    .into_iter()
+                    .map(|p| p.trim().to_string())
+                    .filter(|p| !p.is_empty())
+                    .collect();
+                self.mixins.insert(name.to_string(), params);
+            }
+            self.collect_mixins(&node.children);
+        }
+    }
+
+    fn render_nodes(&self, nodes: &[Node], out: &mut String) -> Result<()> {
+        let mut i = 0;
+        while i < nodes.len() {
+            let node = &nodes[i];
+            // Conditionals swallow the `else if`/`else` siblings that follow
+            if let Some(cond) = node
+                .text
+                .strip_prefix("if ")
+                .map(|c| c.to_string())
+                .or_else(|| {
+                    node.text
+                        .strip_prefix("unless ")
+                        .map(|c| format!("not ({})", c))
+                })
+            {
+                out.push_str(&format!("{{% if {} %}}", js_expr(&cond)));
+                self.render_nodes(&node.children, out)?;
+                while let Some(next) = nodes.get(i + 1) {
+                    if let Some(cond) = next.text.strip_prefix("else if ") {
+                        out.push_str(&format!("{{% elif {} %}}", js_expr(cond)));
+                    } else if next.text == "else" {
+                        out.push_str("{% else %}");
+                    } else {
+                        break;
+                    }
+                    self.render_nodes(&next.children, out)?;
+                    i += 1;
+                }
+                out.push_str("{% endif %}");
+            } else {
+                self.render_node(node, out)?;
+            }
+            i += 1;
+        }
+        Ok(())
+    }
+
+    fn render_node(&self, node: &Node, out: &mut String) -> Result<()> {
+        let text = node.text.as_str();
+
+        if text.starts_with("//-") {
+            return Ok(());
+        }
+        if let Some(comment) = text.strip_prefix("//") {
+            out.push_str(&format!("<!--{}-->", comment));
+            return Ok(());
+        }
+        if let Some(doctype) = text.strip_prefix("doctype") {
+            let doctype = doctype.trim();
+            let doctype = if doctype.is_empty() || doctype == "html" {
+                "html"
+            } else {
+                doctype
+            };
+            out.push_str(&format!("<!DOCTYPE {}>\n", doctype));
+            return Ok(());
+        }
+        if let Some(parent) = text.strip_prefix("extends ") {
+            let parent = template_name(&self.dir, parent, node.line)?;
+            out.push_str(&format!("{{% extends \"{}\" %}}", parent));
+            return Ok(());
+        }
+        if let Some(path) = text.strip_prefix("include ") {
+            let path = template_name(&self.dir, path, node.line)?;
+            out.push_str(&format!("{{% include \"{}\" %}}", path));
+            return Ok(());
+        }
+        if let Some(name) = text.strip_prefix("block ") {
+            let name = name
+                .trim()
+                .trim_start_matches("append ")
+                .trim_start_matches("prepend ");
+            out.push_str(&format!("{{% block {} %}}", name));
+            self.render_nodes(&node.children, out)?;
+            out.push_str(&format!("{{% endblock {} %}}", name));
+            return Ok(());
+        }
+        if let Some(rest) = text
+            .strip_prefix("each ")
+            .or_else(|| text.strip_prefix("for "))
+        {
+            let (vars, iterable) = rest
+                .split_once(" in ")
+                .ok_or_else(|| anyhow!("Expected `in` in loop at line {}", node.line))?;
+            let iterable = js_expr(iterable);
+            let vars: Vec<&str> = vars.split(',').map(str::trim).collect();
+            match vars.as_slice() {
+                // Pug's `each value, key in obj` is Tera's `for key, value in
+                // obj`, which only takes maps, so lists count the index instead
+                [value, key] => {
+                    out.push_str(&format!(
+                        "{{% if {} is object %}}{{% for {}, {} in {} %}}",
+                        iterable, key, value, iterable
+                    ));
+                    self.render_nodes(&node.children, out)?;
+                    out.push_str(&format!(
+                        "{{% endfor %}}{{% else %}}{{% for {} in {} %}}{{% set {} = loop.index0 %}}",
+                        value, iterable, key
+                    ));
+                    self.render_nodes(&node.children, out)?;
+                    out.push_str("{% endfor %}{% endif %}");
+                }
+                _ => {
+                    out.push_str(&format!("{{% for {} in {} %}}", vars.join(", "), iterable));
+                    self.render_nodes(&node.children, out)?;
+                    out.push_str("{% endfor %}");
+                }
+            }
+            return Ok(());
+        }
+        if let Some(code) = text.strip_prefix("- ") {
+            let code = code.trim().trim_end_matches(';');
+            let declaration = ["var ", "let ", "const "]
+                .iter()
+                .find_map(|kw| code.strip_prefix(kw));
+            let assignment = declaration.unwrap_or(code);
+            let is_name = |name: &str| {
+                !name.is_empty()
+                    && name
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+            };
+            match assignment.split_once('=') {
+                Some((name, value)) if is_name(name.trim()) && !value.starts_with('=') => {
+                    out.push_str(&format!(
+                        "{{% set {} = {} %}}",
+                        name.trim(),
+                        js_expr(value.trim())
+                    ));
+                }
+                // `- var x` on its own does nothing
+                None if declaration.is_some() && is_name(assignment.trim()) => {}
+                _ => return Err(anyhow!("Unsupported code at line {}: {}", node.line, text)),
+            }
+            return Ok(());
+        }
+        if let Some(rest) = text.strip_prefix("mixin ") {
+            let (name, _) = split_call(rest);
+            let params = self.mixins.get(name).cloned().unwrap_or_default();
+            out.push_str(&format!("{{% macro {}({}) %}}", name, params.join(", ")));
+            self.render_nodes(&node.children, out)?;
+            out.push_str("{% endmacro %}");
+            return Ok(());
+        }
+        if let Some(rest) = text.strip_prefix('+') {
+            let (name, args) = split_call(rest);
+            let params = self
+                .mixins
+                .get(name)
+                .ok_or_else(|| anyhow!("Unknown mixin `{}` at line {}", name, node.line))?;
+            let args: Vec<String> = split_top_level(&args, ',')
+                .iter()
+                .zip(params)
+                .map(|(arg, param)| format!("{}={}", param, js_expr(arg.trim())))
+                .collect();
+            out.push_str(&format!("{{{{ self::{}({}) }}}}", name, args.join(", ")));
+            return Ok(());
+        }
+        if let Some(piped) = text.strip_prefix('|') {
+            out.push_str(&interpolate(piped.strip_prefix(' ').unwrap_or(piped)));
+            out.push('\n');
+            return Ok(());
+        }
+        if let Some(code) = text.strip_prefix("!=") {
+            out.push_str(&format!("{{{{ {} | safe }}}}", js_expr(code.trim())));
+            return Ok(());
+        }
+        if let Some(code) = text.strip_prefix('=') {
+            out.push_str(&format!("{{{{ {} }}}}", js_expr(code.trim())));
+            return Ok(());
+        }
+        if text.starts_with('<') {
+            out.push_str(&interpolate(text));
+            self.render_nodes(&node.children, out)?;
+            return Ok(());
+        }
+
+        self.render_tag(text, &node.children, node.line, out)
+    }
+
+    fn render_tag(
+        &self,
+        text: &str,
+        children: &[Node],
+        line: usize,
+        out: &mut String,
+    ) -> Result<()> {
+        let tag = parse_tag(text).ok_or_else(|| anyhow!("Cannot parse line {}: {}", line, text))?;
+
+        out.push('<');
+        out.push_str(&tag.name);
+        if let Some(id) = &tag.id {
+            out.push_str(&format!(" id=\"{}\"", id));
+        }
+        if !tag.classes.is_empty() {
+            out.push_str(&format!(" class=\"{}\"", tag.classes.join(" ")));
+        }
+        for attr in &tag.attrs {
+            out.push(' ');
+            out.push_str(attr);
+        }
+        out.push('>');
+
+        if VOID_ELEMENTS.contains(&tag.name.as_str()) {
+            return Ok(());
+        }
+
+        match tag.content {
+            TagContent::None => {}
+            TagContent::Text(text) => out.push_str(&interpolate(&text)),
+            TagContent::Code(code) => out.push_str(&format!("{{{{ {} }}}}", js_expr(&code))),
+            TagContent::RawCode(code) => {
+                out.push_str(&format!("{{{{ {} | safe }}}}", js_expr(&code)))
+            }
+            TagContent::Nested(child) => {
+                let nested = Node {
+                    line,
+                    text: child,
+                    children: Vec::new(),
+                };
+                self.render_node(&nested, out)?;
+            }
+        }
+
+        if tag.text_block {
+            for child in children {
+                out.push_str(&interpolate(&child.text));
+                out.push('\n');
+                render_raw_lines(&child.children, out);
+            }
+        } else {
+            self.render_nodes(children, out)?;
+        }
+
+        out.push_str(&format!("</{}>", tag.name));
+        Ok(())
+    }
+}
+
+/// Lines nested in a `tag.` text block are plain text
+fn render_raw_lines(nodes: &[Node], out: &mut String) {
+    for node in nodes {
+        out.push_str(&interpolate(&node.text));
+        out.push('\n');
+        render_raw_lines(&node.children, out);
+    }
+}
+
+enum TagContent {
+    None,
+    Text(String),
+    Code(String),
+    RawCode(String),
+    Nested(String),
+}
+
+struct Tag {
+    name: String,
+    id: Option<String>,
+    classes: Vec<String>,
+    attrs: Vec<String>,
+    content: TagContent,
+    text_block: bool,
+}
+
+/// Parse `tag#id.class(attr=value) content`
+fn parse_tag(text: &str) -> Option<Tag> {
+    let name_len = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == ':'))
+        .unwrap_or(text.len());
+    // `a: b` is block expansion, not part of the tag name
+    let name_len = text[..name_len].find(':').unwrap_or(name_len);
+    let name = &text[..name_len];
+    if name.is_empty() && !text.starts_with(['.', '#']) {
+        return None;
+    }
+
+    let mut tag = Tag {
+        name: if name.is_empty() { "div" } else { name }.to_string(),
+        id: None,
+        classes: Vec::new(),
+        attrs: Vec::new(),
+        content: TagContent::None,
+        text_block: false,
+    };
+    let mut rest = &text[name_len..];
+
+    // #id and .class shorthand
+    while let Some(marker) = rest.chars().next().filter(|c| *c == '.' || *c == '#') {
+        let body = &rest[1..];
+        let len = body
+            .find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(body.len());
+        if len == 0 {
+            break;
+        }
+        if marker == '#' {
+            tag.id = Some(body[..len].to_string());
+        } else {
+            tag.classes.push(body[..len].to_string());
+        }
+        rest = &body[len..];
+    }
+
+    if rest.starts_with('(') {
+        let end = matching_paren(rest)?;
+        for attr in split_top_level(&rest[1..end], ',')
+            .into_iter()
+            .flat_map(|a| split_attr_whitespace(&a))
+        {
+            if let Some(attr) = render_attr(&attr, &mut tag.classes) {
+                tag.attrs.push(attr);
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+
+    rest = rest
+        .strip_prefix("&attributes")
+        .map_or(rest, |r| matching_paren(r).map_or(r, |end| &r[end + 1..]));
+
+    tag.content = if rest == "." {
+        tag.text_block = true;
+        TagContent::None
+    } else if let Some(code) = rest.strip_prefix("!=") {
+        TagContent::RawCode(code.trim().to_string())
+    } else if let Some(code) = rest.strip_prefix('=') {
+        TagContent::Code(code.trim().to_string())
+    } else if let Some(child) = rest.strip_prefix(':') {
+        TagContent::Nested(child.trim().to_string())
+    } else if let Some(text) = rest.strip_prefix(' ') {
+        TagContent::Text(text.to_string())
+    } else if rest.is_empty() {
+        TagContent::None
+    } else {
+        return None;
+    };
+
+    Some(tag)
+}
+
+/// Render one attribute as HTML, merging `class` into the shorthand classes
+fn render_attr(attr: &str, classes: &mut Vec<String>) -> Option<String> {
+    let attr = attr.trim();
+    if attr.is_empty() {
+        return None;
+    }
+    let (name, value) = match attr.split_once("!=").or_else(|| attr.split_once('=')) {
+        Some((name, value)) => (name.trim(), value.trim()),
+        None => return Some(attr.to_string()),
+    };
+
+    let value = match string_literal(value) {
+        Some(literal) => interpolate(&literal),
+        None => format!("{{{{ {} }}}}", js_expr(value)),
+    };
+    if name == "class" {
+        classes.push(value);
+        return None;
+    }
+    Some(format!("{}=\"{}\"", name, value))
+}
+
+/// The contents of a single- or double-quoted JS string literal
+fn string_literal(value: &str) -> Option<String> {
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let inner = value.strip_prefix(quote)?.strip_suffix(quote)?;
+    (!inner.contains(quote)).then(|| inner.to_string())
+}
+
+/// `name(args)` -> (`name`, `args`)
+fn split_call(text: &str) -> (&str, String) {
+    match text.find('(') {
+        Some(start) => {
+            let end = matching_paren(&text[start..]).map_or(text.len(), |e| start + e);
+            (
+                text[..start].trim(),
+                text[start + 1..end.min(text.len())].to_string(),
+            )
+        }
+        None => (text.trim(), String::new()),
+    }
+}
+
+/// Index of the parenthesis closing the one `text` starts with
+fn matching_paren(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'' | '`') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')' | ']' | '}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split on `sep` outside quotes and brackets
+pub(super) fn split_top_level(text: &str, sep: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'' | '`') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')' | ']' | '}') => depth -= 1,
+            (None, c) if c == sep && depth == 0 => {
+                parts.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+/// Attributes may also be separated by whitespace (`a(href=x target="_blank")`)
+fn split_attr_whitespace(text: &str) -> Vec<String> {
+    let mut parts: Vec<String> = Vec::new();
+    for piece in split_top_level(text.trim(), ' ') {
+        let piece = piece.trim();
+        if piece.is_empty() {
+            continue;
+        }
+        // Keep `a = b` together: pieces without `=` that follow or precede one
+        let joins_previous = piece.starts_with('=')
+            || piece.starts_with("!=")
+            || parts.last().is_some_and(|p| p.ends_with('='));
+        match parts.last_mut() {
+            Some(last) if joins_previous => last.push_str(piece),
+            _ => parts.push(piece.to_string()),
+        }
+    }
+    parts
+}
+
+/// Replace `#{expr}` and `!{expr}` interpolation with Tera expressions
+fn interpolate(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(['#', '!']) {
+        let marker = &rest[start..];
+        if !marker[1..].starts_with('{') {
+            out.push_str(&rest[..start + 1]);
+            rest = &rest[start + 1..];
+            continue;
+        }
+        let Some(end) = matching_paren(&marker[1..]) else {
+            break;
+        };
+        let expr = js_expr(&marker[2..end + 1]);
+        out.push_str(&rest[..start]);
+        if marker.starts_with('!') {
+            out.push_str(&format!("{{{{ {} | safe }}}}", expr));
+        } else {
+            out.push_str(&format!("{{{{ {} }}}}", expr));
+        }
+        rest = &marker[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Map JavaScript operators to Tera's
+fn js_expr(expr: &str) -> String {
+    let mut out = String::new();
+    let mut chars = expr.trim().chars().peekable();
+    let mut quote: Option<char> = None;
+
+    while let Some(c) = chars.next() {
+        if let Some(q) = quote {
+            out.push(c);
+            if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => {
+                quote = Some(c);
+                out.push(c);
+            }
+            '|' if chars.peek() == Some(&'|') => {
+                chars.next();
+                out.push_str(" or ");
+            }
+            '&' if chars.peek() == Some(&'&') => {
+                chars.next();
+                out.push_str(" and ");
+            }
+            '=' | '!' if chars.peek() == Some(&'=') => {
+                chars.next();
+                out.push(c);
+                out.push('=');
+                // `===`/`!==`
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                }
+            }
+            '!' => out.push_str("not "),
+            _ => out.push(c),
+        }
+    }
+
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Template reference to the registered name, resolved against the
+/// directory `dir` of the referencing template: in `post/index.pug`,
+/// `../partial/head` -> `partial/head.html` and `/layout` -> `layout.html`
+fn template_name(dir: &str, path: &str, line: usize) -> Result<String> {
+    let path = path.trim().trim_matches(['"', '\'']);
+    let stem = PUG_EXTENSIONS
+        .iter()
+        .find_map(|ext| path.strip_suffix(&format!(".{}", ext)))
+        .unwrap_or(path);
+
+    let (base, stem) = match stem.strip_prefix('/') {
+        Some(absolute) => ("", absolute),
+        None => (dir, stem),
+    };
+    let mut segments: Vec<&str> = base.split('/').filter(|s| !s.is_empty()).collect();
+    for segment in stem.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop().ok_or_else(|| {
+                    anyhow!("{} at line {} is outside the layout directory", path, line)
+                })?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+    Ok(format!("{}.html", segments.join("/")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(pug: &str, context: &tera::Context) -> String {
+        let mut tera = tera::Tera::default();
+        tera.autoescape_on(vec![]);
+        tera.add_raw_template("test.html", &to_tera(pug, "test.html").unwrap())
+            .unwrap();
+        tera.render("test.html", context).unwrap()
+    }
+
+    #[test]
+    fn test_tags_and_attributes() {
+        let mut context = tera::Context::new();
+        context.insert("url", "/about/");
+        context.insert("title", "About");
+        let html = render(
+            "nav#main.menu\n  a.item(href=url, class='active' target=\"_blank\")= title\n  img(src='/logo.png')\n  p Hello #{title}!\n  p.\n    Text block\n  | piped",
+            &context,
+        );
+        assert_eq!(
+            html,
+            "<nav id=\"main\" class=\"menu\"><a class=\"item active\" href=\"/about/\" target=\"_blank\">About</a><img src=\"/logo.png\"><p>Hello About!</p><p>Text block\n</p>piped\n</nav>"
+        );
+    }
+
+    #[test]
+    fn test_control_flow_and_mixins() {
+        let mut context = tera::Context::new();
+        context.insert("posts", &vec!["a", "b"]);
+        context.insert("show", &false);
+        let html = render(
+            "mixin item(name)\n  li= name\nul\n  each post in posts\n    +item(post)\nif show && posts\n  p yes\nelse if !show\n  p no\nelse\n  p never",
+            &context,
+        );
+        assert_eq!(html, "<ul><li>a</li><li>b</li></ul><p>no</p>");
+    }
+
+    #[test]
+    fn test_each_with_index() {
+        let mut context = tera::Context::new();
+        context.insert("items", &vec!["a", "b"]);
+        context.insert("links", &std::collections::BTreeMap::from([("home", "/")]));
+        let html = render(
+            "each item, i in items\n  i= i\n  b= item\neach url, name in links\n  a(href=url)= name",
+            &context,
+        );
+        assert_eq!(
+            html,
+            "<i>0</i><b>a</b><i>1</i><b>b</b><a href=\"/\">home</a>"
+        );
+    }
+
+    #[test]
+    fn test_extends_and_blocks() {
+        let tera = to_tera(
+            "extends ../layout.pug\nblock content\n  include _partial/post\n  include /footer\n",
+            "post/index.html",
+        )
+        .unwrap();
+        assert_eq!(
+            tera,
+            "{% extends \"layout.html\" %}{% block content %}{% include \"post/_partial/post.html\" %}\
+             {% include \"footer.html\" %}{% endblock content %}"
+        );
+        assert!(to_tera("extends ../layout", "index.html").is_err());
+    }
+
+    #[test]
+    fn test_code_lines() {
+        let tera = to_tera(
+            "- var title = page.title || config.title\n- var x;\np= title",
+            "a.html",
+        )
+        .unwrap();
+        assert_eq!(
+            tera,
+            "{% set title = page.title or config.title %}<p>{{ title }}</p>"
+        );
+        assert!(to_tera("- items.push(1)", "a.html").is_err());
+        assert!(to_tera("- if (a == b) x = 1", "a.html").is_err());
+    }
+}
@@ -0,0 +1,90 @@
+//! SCSS/Sass compilation for theme and site assets
+//!
+//! `.scss` and `.sass` files in `themes/<name>/source/` and `source/` are
+//! compiled to `.css` with grass. Partials (files starting with `_`) are only
+//! used through imports and are not output themselves.
+
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+
+use crate::config::SassConfig;
+
+/// Stylesheet extensions compiled by [`SassCompiler`]
+pub const SASS_EXTENSIONS: &[&str] = &["scss", "sass"];
+
+/// Compiles SCSS/Sass files with the site's `sass` settings
+pub struct SassCompiler {
+    style: grass::OutputStyle,
+    load_paths: Vec<PathBuf>,
+}
+
+impl SassCompiler {
+    /// Create a compiler, resolving load paths against the site root
+    pub fn new(config: &SassConfig, base_dir: &Path) -> Self {
+        let style = match config.style.as_str() {
+            "compressed" => grass::OutputStyle::Compressed,
+            "expanded" => grass::OutputStyle::Expanded,
+            other => {
+                tracing::warn!("Unknown sass style {:?}, using expanded", other);
+                grass::OutputStyle::Expanded
+            }
+        };
+        Self {
+            style,
+            load_paths: config.load_paths.iter().map(|p| base_dir.join(p)).collect(),
+        }
+    }
+
+    /// Whether `path` is a stylesheet this compiler handles
+    pub fn handles(path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|ext| SASS_EXTENSIONS.contains(&ext))
+    }
+
+    /// Whether `path` is a partial that is only imported by other stylesheets
+    pub fn is_partial(path: &Path) -> bool {
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with('_'))
+    }
+
+    /// Compile a stylesheet to CSS
+    pub fn compile(&self, path: &Path) -> Result<String> {
+        let options = grass::Options::default()
+            .style(self.style)
+            .load_paths(&self.load_paths);
+        grass::from_path(path, &options)
+            .map_err(|e| anyhow!("Sass compilation failed for {:?}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_compile_scss() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("lib")).unwrap();
+        fs::write(dir.path().join("lib/_colors.scss"), "$link: #258fb8;\n").unwrap();
+        let style = dir.path().join("style.scss");
+        fs::write(
+            &style,
+            "@import 'colors';\n.post {\n  a { color: $link; }\n}\n",
+        )
+        .unwrap();
+
+        let config = SassConfig {
+            style: "compressed".to_string(),
+            load_paths: vec!["lib".to_string()],
+        };
+        let compiler = SassCompiler::new(&config, dir.path());
+        assert!(SassCompiler::handles(&style));
+        assert!(SassCompiler::is_partial(
+            &dir.path().join("lib/_colors.scss")
+        ));
+        assert_eq!(compiler.compile(&style).unwrap(), ".post a{color:#258fb8}");
+    }
+}
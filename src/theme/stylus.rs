@@ -0,0 +1,1032 @@
+//! Built-in Stylus compiler
+//!
+//! Compiles the subset of Stylus used by mainstream Hexo themes to CSS
+//! without Node.js:
+//! - indentation (or brace) syntax with optional colons and semicolons
+//! - variables (`name = value`, `$name = value`, `name ?= value`) and `{name}`
+//!   interpolation in selectors and property names
+//! - nested selectors with `&` parent references, and `@media`/`@supports`
+//!   blocks bubbled out of the rules they are nested in
+//! - `@import`/`@require` of other `.styl` files, relative to the importing
+//!   file or the theme's `source/` directory (a trailing `*` imports every
+//!   file in a directory)
+//! - arithmetic on numbers with units, `if`/`else if`/`else`/`unless` blocks
+//!   and `hexo-config("key.path")` lookups in the theme config
+//!
+//! Mixins, functions, loops and color functions are not supported. Files
+//! using them are compiled with `npx stylus` instead when Node.js is
+//! available, and otherwise without those lines. Themes relying on them
+//! can also ship pre-compiled CSS next to the `.styl` file.
+
+use anyhow::{anyhow, Result};
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Output of the built-in compiler
+pub struct Compiled {
+    pub css: String,
+    /// Lines the compiler doesn't support and left out of `css`
+    pub unsupported: Vec<String>,
+}
+
+/// Compile a Stylus file to CSS, resolving imports against `include_dir`
+pub fn compile(
+    path: &Path,
+    include_dir: &Path,
+    config: &IndexMap<String, serde_yaml::Value>,
+) -> Result<Compiled> {
+    let mut compiler = Compiler {
+        include_dir,
+        config,
+        vars: HashMap::new(),
+        required: Vec::new(),
+        stack: Vec::new(),
+        unsupported: Vec::new(),
+    };
+    let mut decls = Vec::new();
+    let mut out = Vec::new();
+    compiler.compile_file(path, &[], &mut decls, &mut out)?;
+
+    // CSS only honours @import and @charset before any rule
+    let (mut items, rules): (Vec<Css>, Vec<Css>) =
+        out.into_iter().partition(|c| matches!(c, Css::Raw(_)));
+    items.extend(rules);
+    Ok(Compiled {
+        css: render(&items, 0),
+        unsupported: compiler.unsupported,
+    })
+}
+
+/// Compile a Stylus file with the real Stylus, through `npx stylus`
+pub fn compile_with_node(path: &Path, include_dir: &Path) -> Result<String> {
+    let output = std::process::Command::new("npx")
+        .args([
+            "--no-install",
+            "stylus",
+            "--print",
+            "--include",
+            include_dir.to_str().unwrap_or("."),
+            path.to_str().unwrap_or(""),
+        ])
+        .output()
+        .map_err(|e| anyhow!("Failed to run npx stylus: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "npx stylus failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// One line of source with its indentation
+struct Line {
+    indent: usize,
+    text: String,
+}
+
+/// Parsed source: a line, or a line followed by an indented block
+enum Node {
+    Line(String),
+    Block { head: String, children: Vec<Node> },
+}
+
+/// Compiled output
+enum Css {
+    Rule {
+        selector: String,
+        decls: Vec<String>,
+    },
+    /// At-rule wrapping other output (`@media`, `@keyframes`, ...)
+    Group { head: String, body: Vec<Css> },
+    /// Single-line at-rule such as `@charset` or a plain CSS `@import`
+    Raw(String),
+}
+
+struct Compiler<'a> {
+    include_dir: &'a Path,
+    config: &'a IndexMap<String, serde_yaml::Value>,
+    vars: HashMap<String, String>,
+    /// Files pulled in by `@require`, which are only included once
+    required: Vec<PathBuf>,
+    /// Files being compiled, to detect import cycles
+    stack: Vec<PathBuf>,
+    /// Lines that were skipped, with the file they are in
+    unsupported: Vec<String>,
+}
+
+impl Compiler<'_> {
+    fn compile_file(
+        &mut self,
+        path: &Path,
+        parents: &[String],
+        decls: &mut Vec<String>,
+        out: &mut Vec<Css>,
+    ) -> Result<()> {
+        if self.stack.iter().any(|p| p == path) {
+            return Err(anyhow!("Stylus import cycle at {:?}", path));
+        }
+        let source =
+            fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {:?}: {}", path, e))?;
+        let nodes = parse(&lines(&source));
+
+        self.stack.push(path.to_path_buf());
+        let result = self.eval(&nodes, parents, decls, out);
+        self.stack.pop();
+        result
+    }
+
+    /// Evaluate nodes inside the rule for `parents`: properties are added to
+    /// `decls`, nested rules and at-rules to `out`
+    fn eval(
+        &mut self,
+        nodes: &[Node],
+        parents: &[String],
+        decls: &mut Vec<String>,
+        out: &mut Vec<Css>,
+    ) -> Result<()> {
+        // Whether an earlier branch of the current if/else chain was taken
+        let mut chain: Option<bool> = None;
+
+        for node in nodes {
+            match node {
+                Node::Line(line) => {
+                    chain = None;
+                    self.eval_line(line, parents, decls, out)?;
+                }
+                Node::Block { head, children } => {
+                    if let Some(cond) = head.strip_prefix("else if ") {
+                        if chain == Some(false) && self.condition(cond) {
+                            self.eval(children, parents, decls, out)?;
+                            chain = Some(true);
+                        }
+                        continue;
+                    }
+                    if head == "else" {
+                        if chain == Some(false) {
+                            self.eval(children, parents, decls, out)?;
+                        }
+                        chain = None;
+                        continue;
+                    }
+                    if let Some(cond) = head.strip_prefix("if ") {
+                        let taken = self.condition(cond);
+                        if taken {
+                            self.eval(children, parents, decls, out)?;
+                        }
+                        chain = Some(taken);
+                        continue;
+                    }
+                    if let Some(cond) = head.strip_prefix("unless ") {
+                        let taken = !self.condition(cond);
+                        if taken {
+                            self.eval(children, parents, decls, out)?;
+                        }
+                        chain = Some(taken);
+                        continue;
+                    }
+                    chain = None;
+                    self.eval_block(head, children, parents, out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn eval_block(
+        &mut self,
+        head: &str,
+        children: &[Node],
+        parents: &[String],
+        out: &mut Vec<Css>,
+    ) -> Result<()> {
+        if is_call(head) || head.starts_with("for ") {
+            self.warn(head);
+            return Ok(());
+        }
+
+        if let Some(rest) = head.strip_prefix('@') {
+            let (name, params) = rest.split_once(' ').unwrap_or((rest, ""));
+            let head = match params.trim() {
+                "" => format!("@{}", name),
+                params => format!("@{} {}", name, unquote(&self.value(params))),
+            };
+
+            let mut own = Vec::new();
+            let mut nested = Vec::new();
+            if matches!(name, "media" | "supports") {
+                // Bubble out of the enclosing rule, keeping its selector
+                self.eval(children, parents, &mut own, &mut nested)?;
+                let mut body = Vec::new();
+                if !own.is_empty() && !parents.is_empty() {
+                    body.push(Css::Rule {
+                        selector: parents.join(",\n"),
+                        decls: own,
+                    });
+                }
+                body.extend(nested);
+                out.push(Css::Group { head, body });
+            } else if name.ends_with("keyframes") {
+                // Keyframe selectors start from the top level
+                self.eval(children, &[], &mut own, &mut nested)?;
+                out.push(Css::Group { head, body: nested });
+            } else {
+                // @font-face, @page, ...: properties of the at-rule itself
+                self.eval(children, std::slice::from_ref(&head), &mut own, &mut nested)?;
+                out.push(Css::Rule {
+                    selector: head,
+                    decls: own,
+                });
+                out.extend(nested);
+            }
+            return Ok(());
+        }
+
+        let selectors = self.selectors(head, parents);
+        let mut own = Vec::new();
+        let mut nested = Vec::new();
+        self.eval(children, &selectors, &mut own, &mut nested)?;
+        if !own.is_empty() {
+            out.push(Css::Rule {
+                selector: selectors.join(",\n"),
+                decls: own,
+            });
+        }
+        out.extend(nested);
+        Ok(())
+    }
+
+    fn eval_line(
+        &mut self,
+        line: &str,
+        parents: &[String],
+        decls: &mut Vec<String>,
+        out: &mut Vec<Css>,
+    ) -> Result<()> {
+        for (keyword, once) in [("@import ", false), ("@require ", true)] {
+            if let Some(target) = line.strip_prefix(keyword) {
+                return self.import(target.trim(), once, parents, decls, out);
+            }
+        }
+        if line.starts_with("@extend") {
+            self.warn(line);
+            return Ok(());
+        }
+        if line.starts_with('@') {
+            out.push(Css::Raw(format!("{};", line)));
+            return Ok(());
+        }
+
+        if let Some((name, value, conditional)) = assignment(line) {
+            if !(conditional && self.vars.contains_key(name)) {
+                let value = self.value(value);
+                self.vars.insert(name.to_string(), value);
+            }
+            return Ok(());
+        }
+
+        match property(line) {
+            Some((name, value)) if !parents.is_empty() && !is_call(line) => {
+                let name = self.interpolate(name);
+                let value = self.value(value);
+                // Like Stylus, drop properties set to a missing
+                // `hexo-config()` key or other null value
+                if value != "null" {
+                    decls.push(format!("{}: {}", name, value));
+                }
+            }
+            _ => self.warn(line),
+        }
+        Ok(())
+    }
+
+    fn import(
+        &mut self,
+        target: &str,
+        once: bool,
+        parents: &[String],
+        decls: &mut Vec<String>,
+        out: &mut Vec<Css>,
+    ) -> Result<()> {
+        let name = unquote(target);
+        if name.ends_with(".css") || name.starts_with("url(") || name.contains("://") {
+            out.push(Css::Raw(format!("@import {};", target)));
+            return Ok(());
+        }
+        // nib's mixins are not supported, but themes import it unconditionally
+        if name == "nib" || name.starts_with("nib/") {
+            return Ok(());
+        }
+
+        let files = self.resolve(name);
+        if files.is_empty() {
+            return Err(anyhow!(
+                "Cannot find Stylus import {:?} from {:?}",
+                name,
+                self.stack.last()
+            ));
+        }
+        for file in files {
+            if once {
+                if self.required.contains(&file) {
+                    continue;
+                }
+                self.required.push(file.clone());
+            }
+            self.compile_file(&file, parents, decls, out)?;
+        }
+        Ok(())
+    }
+
+    /// Files an import refers to, looked up next to the importing file first
+    fn resolve(&self, name: &str) -> Vec<PathBuf> {
+        let current_dir = self
+            .stack
+            .last()
+            .and_then(|p| p.parent())
+            .unwrap_or(self.include_dir);
+
+        for base in [current_dir, self.include_dir] {
+            if let Some(prefix) = name.strip_suffix('*') {
+                let dir = base.join(prefix);
+                let Ok(entries) = fs::read_dir(&dir) else {
+                    continue;
+                };
+                let mut files: Vec<PathBuf> = entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| p.is_file() && p.extension().is_some_and(|e| e == "styl"))
+                    .collect();
+                files.sort();
+                return files;
+            }
+
+            let path = base.join(name);
+            let candidates = [
+                path.with_extension("styl"),
+                path.clone(),
+                path.join("index.styl"),
+            ];
+            if let Some(found) = candidates.into_iter().find(|p| p.is_file()) {
+                return vec![found];
+            }
+        }
+        Vec::new()
+    }
+
+    /// Full selectors of a rule nested in `parents`
+    fn selectors(&self, head: &str, parents: &[String]) -> Vec<String> {
+        let head = self.interpolate(head);
+        let own: Vec<&str> = split_top(&head, ',')
+            .into_iter()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect();
+        if parents.is_empty() {
+            return own.iter().map(|s| s.to_string()).collect();
+        }
+
+        let mut selectors = Vec::new();
+        for parent in parents {
+            for selector in &own {
+                selectors.push(if selector.contains('&') {
+                    selector.replace('&', parent)
+                } else {
+                    format!("{} {}", parent, selector)
+                });
+            }
+        }
+        selectors
+    }
+
+    /// Replace `{expr}` interpolations in a selector or property name
+    fn interpolate(&self, text: &str) -> String {
+        let mut result = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            result.push_str(&rest[..start]);
+            result.push_str(unquote(&self.value(&rest[start + 1..start + len])));
+            rest = &rest[start + len + 1..];
+        }
+        result.push_str(rest);
+        result
+    }
+
+    /// Evaluate a property value or expression
+    fn value(&self, value: &str) -> String {
+        let value = self.substitute(&self.hexo_config(value.trim()));
+        math(&value)
+    }
+
+    fn condition(&self, cond: &str) -> bool {
+        let cond = cond.trim();
+        if let Some((a, b)) = cond.split_once(" or ") {
+            return self.condition(a) || self.condition(b);
+        }
+        if let Some((a, b)) = cond.split_once(" and ") {
+            return self.condition(a) && self.condition(b);
+        }
+        if let Some(rest) = cond.strip_prefix("not ").or_else(|| cond.strip_prefix('!')) {
+            return !self.condition(rest);
+        }
+        for (op, equal) in [
+            ("!=", false),
+            ("is not", false),
+            ("==", true),
+            (" is ", true),
+        ] {
+            if let Some((a, b)) = cond.split_once(op) {
+                let same = unquote(&self.value(a)) == unquote(&self.value(b));
+                return same == equal;
+            }
+        }
+        truthy(&self.value(cond))
+    }
+
+    /// Replace variable references outside strings and `url()`
+    fn substitute(&self, value: &str) -> String {
+        let chars: Vec<char> = value.chars().collect();
+        let mut result = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if c == '"' || c == '\'' {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&q| q == c)
+                    .map_or(chars.len(), |p| i + p + 2);
+                result.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+            if value[char_offset(value, i)..].starts_with("url(") {
+                let end = chars[i..]
+                    .iter()
+                    .position(|&q| q == ')')
+                    .map_or(chars.len(), |p| i + p + 1);
+                result.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+
+            let starts_ident = c.is_alphabetic()
+                || c == '$'
+                || c == '_'
+                || (c == '-' && chars.get(i + 1).is_some_and(|n| n.is_alphabetic()));
+            let after_word =
+                i > 0 && (is_ident_char(chars[i - 1]) || matches!(chars[i - 1], '#' | '.'));
+            if starts_ident && !after_word {
+                let end = chars[i..]
+                    .iter()
+                    .position(|&n| !is_ident_char(n))
+                    .map_or(chars.len(), |p| i + p);
+                let ident: String = chars[i..end].iter().collect();
+                match self.vars.get(&ident) {
+                    // A call like `foo(...)` is never a variable
+                    Some(var) if chars.get(end) != Some(&'(') => result.push_str(var),
+                    _ => result.push_str(&ident),
+                }
+                i = end;
+                continue;
+            }
+
+            result.push(c);
+            i += 1;
+        }
+        result
+    }
+
+    /// Replace `hexo-config("a.b")` calls with the theme config value
+    fn hexo_config(&self, value: &str) -> String {
+        const CALL: &str = "hexo-config(";
+        let mut result = String::new();
+        let mut rest = value;
+        while let Some(start) = rest.find(CALL) {
+            let Some(len) = rest[start..].find(')') else {
+                break;
+            };
+            let key = unquote(rest[start + CALL.len()..start + len].trim());
+            result.push_str(&rest[..start]);
+            result.push_str(&self.config_value(key));
+            rest = &rest[start + len + 1..];
+        }
+        result.push_str(rest);
+        result
+    }
+
+    fn config_value(&self, key: &str) -> String {
+        let mut parts = key.split('.');
+        let mut value = parts.next().and_then(|k| self.config.get(k));
+        for part in parts {
+            value = value.and_then(|v| v.get(part));
+        }
+        match value {
+            Some(serde_yaml::Value::String(s)) => s.clone(),
+            Some(serde_yaml::Value::Bool(b)) => b.to_string(),
+            Some(serde_yaml::Value::Number(n)) => n.to_string(),
+            Some(serde_yaml::Value::Sequence(s)) => (!s.is_empty()).to_string(),
+            Some(serde_yaml::Value::Mapping(m)) => (!m.is_empty()).to_string(),
+            _ => "null".to_string(),
+        }
+    }
+
+    fn warn(&mut self, line: &str) {
+        tracing::debug!(
+            "Unsupported Stylus in {:?}, skipped: {}",
+            self.stack.last(),
+            line
+        );
+        let file = self.stack.last().map(|p| p.display().to_string());
+        self.unsupported
+            .push(format!("{}: {}", file.unwrap_or_default(), line));
+    }
+}
+
+/// Split source into lines, dropping comments, blank lines and braces and
+/// joining selectors continued with a trailing comma
+fn lines(source: &str) -> Vec<Line> {
+    let source = strip_block_comments(source);
+    let mut lines = Vec::new();
+    let mut pending: Option<Line> = None;
+
+    for raw in source.lines() {
+        let code = strip_line_comment(raw);
+        let (code, body) = match one_line_rule(code) {
+            Some((head, body)) => (head, body),
+            None => (code, Vec::new()),
+        };
+        let text = code.trim().trim_end_matches(';').trim_end();
+        let text = text.strip_suffix('{').unwrap_or(text).trim_end();
+        if text.is_empty() || text == "}" {
+            continue;
+        }
+        let indent = code.len() - code.trim_start().len();
+
+        let line = match pending.take() {
+            Some(mut line) => {
+                line.text = format!("{} {}", line.text, text);
+                line
+            }
+            None => Line {
+                indent,
+                text: text.to_string(),
+            },
+        };
+        if line.text.ends_with(',') {
+            pending = Some(line);
+        } else {
+            let indent = line.indent + 1;
+            lines.push(line);
+            lines.extend(body.into_iter().map(|text| Line { indent, text }));
+        }
+    }
+    lines.extend(pending);
+    lines
+}
+
+/// Split a rule written on one line, `.a { color: red; margin: 0 }`, into
+/// its selector and declarations. Braces of `{name}` interpolations, which
+/// hold no declarations, are left alone.
+fn one_line_rule(code: &str) -> Option<(&str, Vec<String>)> {
+    let trimmed = code.trim_end();
+    let inner = trimmed.strip_suffix('}')?;
+    let (open, _) = inner
+        .char_indices()
+        .filter(|&(_, c)| c == '{')
+        .find(|&(i, _)| {
+            let body = &inner[i + 1..];
+            i > 0 && body.contains(':') && !body.contains(['{', '}'])
+        })?;
+    let body = inner[open + 1..]
+        .split(';')
+        .map(str::trim)
+        .filter(|decl| !decl.is_empty())
+        .map(str::to_string)
+        .collect();
+    Some((&code[..open], body))
+}
+
+fn parse(lines: &[Line]) -> Vec<Node> {
+    let mut i = 0;
+    parse_block(lines, &mut i, 0)
+}
+
+fn parse_block(lines: &[Line], i: &mut usize, indent: usize) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    while *i < lines.len() && lines[*i].indent >= indent {
+        let line = &lines[*i];
+        *i += 1;
+        if *i < lines.len() && lines[*i].indent > line.indent {
+            let children = parse_block(lines, i, lines[*i].indent);
+            nodes.push(Node::Block {
+                head: line.text.clone(),
+                children,
+            });
+        } else {
+            nodes.push(Node::Line(line.text.clone()));
+        }
+    }
+    nodes
+}
+
+fn strip_block_comments(source: &str) -> String {
+    let mut result = String::new();
+    let mut quote = None;
+    let mut chars = source.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match quote {
+            Some(q) if c == q || c == '\n' => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '/' && source[i + 1..].starts_with('*') => {
+                // Keep line breaks so the indentation structure is unchanged
+                let len = source[i + 2..]
+                    .find("*/")
+                    .map_or(source.len() - i, |l| l + 4);
+                result.extend(source[i..i + len].chars().filter(|&c| c == '\n'));
+                while chars.peek().is_some_and(|&(j, _)| j < i + len) {
+                    chars.next();
+                }
+                continue;
+            }
+            None => {}
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Drop a `//` comment, unless it is part of a string or URL
+fn strip_line_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '/' && line[i + 1..].starts_with('/') && prev.is_whitespace() => {
+                return &line[..i];
+            }
+            None => {}
+        }
+        prev = c;
+    }
+    line
+}
+
+/// `name = value`, `$name = value` or `name ?= value`
+fn assignment(line: &str) -> Option<(&str, &str, bool)> {
+    let eq = line.find('=')?;
+    let (name, conditional) = match line[..eq].strip_suffix('?') {
+        Some(name) => (name.trim(), true),
+        None => (line[..eq].trim(), false),
+    };
+    let valid = !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(is_ident_char)
+        && !line[eq + 1..].starts_with('=');
+    valid.then(|| (name, line[eq + 1..].trim(), conditional))
+}
+
+/// `name: value` or `name value`
+fn property(line: &str) -> Option<(&str, &str)> {
+    let space = line.find(char::is_whitespace);
+    let colon = line.find(':');
+    let (name, value) = match (colon, space) {
+        (Some(c), Some(s)) if c < s => (&line[..c], &line[c + 1..]),
+        (Some(c), None) => (&line[..c], &line[c + 1..]),
+        (_, Some(s)) => (&line[..s], &line[s..]),
+        (None, None) => return None,
+    };
+    let value = value.trim();
+    (!name.is_empty() && !value.is_empty()).then_some((name, value))
+}
+
+/// A mixin or function call (or definition) like `clearfix()`
+fn is_call(line: &str) -> bool {
+    match line.find('(') {
+        Some(open) => open > 0 && line.ends_with(')') && line[..open].chars().all(is_ident_char),
+        None => false,
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | '$')
+}
+
+fn char_offset(s: &str, chars: usize) -> usize {
+    s.char_indices().nth(chars).map_or(s.len(), |(i, _)| i)
+}
+
+/// Split on `sep` outside parentheses and brackets
+fn split_top(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            _ if c == sep && depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn unquote(s: &str) -> &str {
+    let s = s.trim();
+    for q in ['"', '\''] {
+        if s.len() >= 2 && s.starts_with(q) && s.ends_with(q) {
+            return &s[1..s.len() - 1];
+        }
+    }
+    s
+}
+
+fn truthy(value: &str) -> bool {
+    !matches!(unquote(value), "" | "false" | "null" | "none" | "0")
+}
+
+/// A number with an optional unit
+#[derive(Clone, Copy)]
+struct Number<'a> {
+    value: f64,
+    unit: &'a str,
+}
+
+impl<'a> Number<'a> {
+    fn parse(token: &'a str) -> Option<Self> {
+        let split = token
+            .find(|c: char| c.is_alphabetic() || c == '%')
+            .unwrap_or(token.len());
+        let (digits, unit) = token.split_at(split);
+        let numeric = digits
+            .trim_start_matches('-')
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.');
+        if digits.is_empty() || !numeric || !unit.chars().all(|c| c.is_alphabetic() || c == '%') {
+            return None;
+        }
+        Some(Self {
+            value: digits.parse().ok()?,
+            unit,
+        })
+    }
+
+    fn apply(self, op: &str, other: Number<'a>) -> Option<Number<'a>> {
+        if !self.unit.is_empty() && !other.unit.is_empty() && self.unit != other.unit {
+            return None;
+        }
+        let unit = if self.unit.is_empty() {
+            other.unit
+        } else {
+            self.unit
+        };
+        let value = match op {
+            "+" => self.value + other.value,
+            "-" => self.value - other.value,
+            "*" => self.value * other.value,
+            "/" if other.value != 0.0 => {
+                let unit = if self.unit == other.unit {
+                    ""
+                } else {
+                    self.unit
+                };
+                return Some(Number {
+                    value: self.value / other.value,
+                    unit,
+                });
+            }
+            _ => return None,
+        };
+        Some(Number { value, unit })
+    }
+
+    fn format(&self) -> String {
+        let rounded = (self.value * 1000.0).round() / 1000.0;
+        format!("{}{}", rounded, self.unit)
+    }
+}
+
+/// Evaluate arithmetic: parenthesised groups first, then `*`/`/` and `+`/`-`
+/// between space-separated numbers
+fn math(value: &str) -> String {
+    let mut value = value.to_string();
+    // Innermost groups that aren't function calls, e.g. `(10px * 2)`
+    let mut search = 0;
+    while let Some(offset) = value[search..].find('(') {
+        let open = search + offset;
+        let Some(len) = value[open + 1..].find([')', '(']) else {
+            break;
+        };
+        let close = open + 1 + len;
+        let before = &value[..open];
+        let is_call = before.ends_with(|c: char| c.is_alphanumeric() || c == '_');
+        if value.as_bytes()[close] == b')' && !is_call {
+            let mut inner = arithmetic(&value[open + 1..close]);
+            if Number::parse(&inner).is_some() {
+                // `-(expr)` negates the result
+                let negated = before == "-"
+                    || (before.ends_with('-')
+                        && before[..open - 1].ends_with(|c: char| c.is_whitespace() || c == '('));
+                let start = if negated {
+                    inner = match inner.strip_prefix('-') {
+                        Some(positive) => positive.to_string(),
+                        None => format!("-{}", inner),
+                    };
+                    open - 1
+                } else {
+                    open
+                };
+                value.replace_range(start..=close, &inner);
+                search = 0;
+                continue;
+            }
+        }
+        search = open + 1;
+    }
+    arithmetic(&value)
+}
+
+fn arithmetic(expr: &str) -> String {
+    let mut tokens: Vec<String> = expr.split_whitespace().map(str::to_string).collect();
+    if tokens.len() < 3 {
+        return expr.trim().to_string();
+    }
+    for ops in [["*", "/"], ["+", "-"]] {
+        let mut i = 1;
+        while i + 1 < tokens.len() {
+            if ops.contains(&tokens[i].as_str()) {
+                let result = Number::parse(&tokens[i - 1])
+                    .zip(Number::parse(&tokens[i + 1]))
+                    .and_then(|(a, b)| a.apply(&tokens[i], b))
+                    .map(|n| n.format());
+                if let Some(result) = result {
+                    tokens.splice(i - 1..=i + 1, [result]);
+                    continue;
+                }
+            }
+            i += 1;
+        }
+    }
+    tokens.join(" ")
+}
+
+fn render(items: &[Css], depth: usize) -> String {
+    let pad = "  ".repeat(depth);
+    let mut css = String::new();
+    for item in items {
+        match item {
+            Css::Rule { selector, decls } => {
+                if decls.is_empty() {
+                    continue;
+                }
+                let selector = selector.replace('\n', &format!("\n{}", pad));
+                css.push_str(&format!("{}{} {{\n", pad, selector));
+                for decl in decls {
+                    css.push_str(&format!("{}  {};\n", pad, decl));
+                }
+                css.push_str(&format!("{}}}\n", pad));
+            }
+            Css::Group { head, body } => {
+                let inner = render(body, depth + 1);
+                if !inner.is_empty() {
+                    css.push_str(&format!("{}{} {{\n{}{}}}\n", pad, head, inner, pad));
+                }
+            }
+            Css::Raw(line) => css.push_str(&format!("{}{}\n", pad, line)),
+        }
+    }
+    css
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_str(source: &str) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("style.styl");
+        fs::write(&path, source).unwrap();
+        compile(&path, dir.path(), &IndexMap::new()).unwrap().css
+    }
+
+    #[test]
+    fn test_variables_and_nesting() {
+        let css = compile_str(
+            r#"
+// colors
+color-link = #258fb8
+$gutter = 10px
+
+#header, .banner
+  padding: $gutter ($gutter * 2)
+  margin -($gutter / 2) 0
+  a
+    color color-link
+    &:hover
+      color: red;
+  > .title
+    margin-bottom $gutter / 2
+"#,
+        );
+        assert_eq!(
+            css,
+            "#header,\n.banner {\n  padding: 10px 20px;\n  margin: -5px 0;\n}\n\
+             #header a,\n.banner a {\n  color: #258fb8;\n}\n\
+             #header a:hover,\n.banner a:hover {\n  color: red;\n}\n\
+             #header > .title,\n.banner > .title {\n  margin-bottom: 5px;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_media_and_conditions() {
+        let css = compile_str(
+            r#"
+mq-mobile = "screen and (max-width: 479px)"
+rounded = true
+
+.post
+  width 100%
+  @media mq-mobile
+    width auto
+  if rounded
+    border-radius 4px
+  else
+    border-radius 0
+"#,
+        );
+        assert_eq!(
+            css,
+            ".post {\n  width: 100%;\n  border-radius: 4px;\n}\n\
+             @media screen and (max-width: 479px) {\n  .post {\n    width: auto;\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_imports_and_hexo_config() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("css/_partial")).unwrap();
+        fs::write(dir.path().join("css/_variables.styl"), "font-size = 14px\n").unwrap();
+        fs::write(
+            dir.path().join("css/_partial/header.styl"),
+            "#header\n  font-size font-size\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("css/style.styl"),
+            "@import \"nib\"\n@import \"_variables\"\n@import \"_partial/*\"\n\
+             body\n  font-family hexo-config(\"font.family\")\n",
+        )
+        .unwrap();
+
+        let config: IndexMap<String, serde_yaml::Value> =
+            serde_yaml::from_str("font:\n  family: Georgia\n").unwrap();
+        let css = compile(&dir.path().join("css/style.styl"), dir.path(), &config)
+            .unwrap()
+            .css;
+        assert_eq!(
+            css,
+            "#header {\n  font-size: 14px;\n}\nbody {\n  font-family: Georgia;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_one_line_rules_and_missing_config() {
+        let css = compile_str(
+            ".a { color: red; margin: 0 }\n.b:hover {color: blue}\n\
+             .c\n  color hexo-config(\"missing.key\")\n  width 1px\n",
+        );
+        assert_eq!(
+            css,
+            ".a {\n  color: red;\n  margin: 0;\n}\n.b:hover {\n  color: blue;\n}\n\
+             .c {\n  width: 1px;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_unsupported_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("style.styl");
+        fs::write(
+            &path,
+            "border-box()\n  box-sizing border-box\n.a\n  color red\n",
+        )
+        .unwrap();
+        let compiled = compile(&path, dir.path(), &IndexMap::new()).unwrap();
+        assert_eq!(compiled.css, ".a {\n  color: red;\n}\n");
+        assert_eq!(compiled.unsupported.len(), 1);
+        assert!(compiled.unsupported[0].ends_with("border-box()"));
+    }
+}